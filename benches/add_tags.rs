@@ -0,0 +1,51 @@
+//! Micro-benchmark of the tag conversion loop, with and without `--only-keys`.
+//! Run with `cargo bench --bench add_tags`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use osm2rdf::str_builder::StringBuf;
+use osm2rdf::ParserOpts;
+
+const ITERATIONS: u32 = 200_000;
+
+static TAGS: &[(&str, &str)] = &[
+    ("highway", "residential"),
+    ("name", "Main Street"),
+    ("name:en", "Main Street"),
+    ("name:de", "Hauptstraße"),
+    ("maxspeed", "50"),
+    ("surface", "asphalt"),
+    ("lanes", "2"),
+    ("oneway", "yes"),
+    ("lit", "yes"),
+    ("sidewalk", "both"),
+    ("wikidata", "Q12345"),
+    ("wikipedia", "en:Main Street"),
+    ("start_date", "1901-05-01"),
+    ("source", "survey"),
+    ("tiger:county", "Some County, XX"),
+];
+
+fn run(name: &str, opts: &ParserOpts) {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let mut value = StringBuf::default();
+        black_box(value.add_tags(TAGS.iter().copied(), opts));
+        black_box(value);
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{name:<12} {:>8.1} ns/element",
+        elapsed.as_nanos() as f64 / f64::from(ITERATIONS)
+    );
+}
+
+fn main() {
+    run("all keys", &ParserOpts::default());
+    let opts = ParserOpts {
+        only_keys: vec!["highway".to_string(), "name".to_string()],
+        ..ParserOpts::default()
+    };
+    run("only keys", &opts);
+}
@@ -0,0 +1,174 @@
+use std::fmt::Display;
+use std::io::Write as _;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use bytesize::ByteSize;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use osmnodecache::{CacheStore as _, HashMapCache};
+use osmpbf::BlobDecode;
+
+use crate::gzip::GzStrategyEncoder;
+use crate::input::PbfReader;
+use crate::parser::{Parser, PrePass, Statement};
+use crate::utils::Stats;
+use crate::{Args, Command, DeflateStrategy, ParserOpts};
+
+/// Compression codecs compared by the `bench-compression` command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    None,
+    Gzip,
+    /// Gzip with `--deflate-strategy filtered`
+    GzipFiltered,
+    /// Gzip with `--deflate-strategy rle`
+    GzipRle,
+    Zstd,
+}
+
+impl Codec {
+    fn compress(self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        Ok(match self {
+            Codec::None => data.to_vec(),
+            Codec::Gzip => {
+                let mut enc = GzEncoder::new(Vec::new(), Compression::default());
+                enc.write_all(data)?;
+                enc.finish()?
+            }
+            Codec::GzipFiltered | Codec::GzipRle => {
+                let strategy = if self == Codec::GzipRle {
+                    DeflateStrategy::Rle
+                } else {
+                    DeflateStrategy::Filtered
+                };
+                let level = Compression::default().level();
+                let mut enc = GzStrategyEncoder::new(Vec::new(), level, strategy)?;
+                enc.write_all(data)?;
+                enc.finish()?
+            }
+            Codec::Zstd => zstd::encode_all(data, zstd::DEFAULT_COMPRESSION_LEVEL)?,
+        })
+    }
+}
+
+impl Display for Codec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Codec::None => f.pad("none"),
+            Codec::Gzip => f.pad("gzip"),
+            Codec::GzipFiltered => f.pad("gzip-filtered"),
+            Codec::GzipRle => f.pad("gzip-rle"),
+            Codec::Zstd => f.pad("zstd"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CodecResult {
+    pub codec: Codec,
+    /// Compressed size in bytes
+    pub size: usize,
+    pub duration: Duration,
+}
+
+/// Results of the `bench-compression` command
+#[derive(Debug, Default)]
+pub struct CompressionReport {
+    /// Number of elements in the sample
+    pub elements: usize,
+    /// Size of the sample before compression, in bytes
+    pub uncompressed: usize,
+    pub results: Vec<CodecResult>,
+}
+
+impl Display for CompressionReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Sample of {} elements, {} uncompressed",
+            self.elements,
+            ByteSize(self.uncompressed as u64)
+        )?;
+        writeln!(
+            f,
+            "{:<13} {:>12} {:>7} {:>10}",
+            "codec", "size", "ratio", "time"
+        )?;
+        for result in &self.results {
+            let ratio = result.size as f64 / self.uncompressed.max(1) as f64 * 100.0;
+            writeln!(
+                f,
+                "{:<13} {:>12} {:>6.1}% {:>8}ms",
+                result.codec,
+                ByteSize(result.size as u64).to_string(),
+                ratio,
+                result.duration.as_millis()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Convert the first elements of the file the same way as the `parse` command,
+/// and measure the size and time of compressing them with each codec.
+pub fn bench_compression(opt: Args) -> anyhow::Result<CompressionReport> {
+    let Command::BenchCompression {
+        input_file,
+        sample_size,
+    } = opt.cmd
+    else {
+        unreachable!()
+    };
+
+    let opts = ParserOpts::default();
+    let pre_pass = PrePass::default();
+    let cache = HashMapCache::new();
+    let stats = Mutex::new(Stats::default());
+    let mut statements = Vec::new();
+    {
+        let mut parser = Parser::new(&stats, cache.get_accessor(), 1024, &opts, &pre_pass);
+        for blob in PbfReader::from_path(&input_file)? {
+            if let BlobDecode::OsmData(block) = blob?.decode()? {
+                parser.parse_block(block, |batch| {
+                    statements.extend(
+                        batch
+                            .into_iter()
+                            .filter(|v| matches!(v, Statement::Create { .. })),
+                    );
+                });
+            }
+            if statements.len() >= sample_size {
+                break;
+            }
+        }
+    }
+    statements.truncate(sample_size);
+
+    let mut sample = Vec::new();
+    for statement in &statements {
+        statement.write_to(&mut sample)?;
+    }
+    let mut report = CompressionReport {
+        elements: statements.len(),
+        uncompressed: sample.len(),
+        ..CompressionReport::default()
+    };
+    let codecs = [
+        Codec::None,
+        Codec::Gzip,
+        Codec::GzipFiltered,
+        Codec::GzipRle,
+        Codec::Zstd,
+    ];
+    for codec in codecs {
+        let start = Instant::now();
+        let size = codec.compress(&sample)?.len();
+        report.results.push(CodecResult {
+            codec,
+            size,
+            duration: start.elapsed(),
+        });
+    }
+    Ok(report)
+}
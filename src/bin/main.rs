@@ -1,16 +1,49 @@
+use std::fs::File;
+
+use anyhow::Context as _;
 use clap::Parser;
-use osm2rdf::{parser, Args, Command};
+use osm2rdf::{bench, check, compact, parser, progress, vocab, Args, Command};
 
 fn main() -> anyhow::Result<()> {
-    let env = env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "osm2rdf=info");
-    env_logger::Builder::from_env(env).init();
-
     let args = Args::parse();
+
+    let level = if args.verbose { "osm2rdf=debug" } else { "osm2rdf=info" };
+    let env = env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, level);
+    let mut logger = env_logger::Builder::from_env(env);
+    if let Some(log_file) = &args.log_file {
+        let file = File::create(log_file)
+            .with_context(|| format!("Unable to create log file {}", log_file.display()))?;
+        logger.target(env_logger::Target::Pipe(Box::new(file)));
+    }
+    progress::init_logger(logger.build())?;
+
     match args.cmd {
         Command::Parse { .. } => {
-            let stats = parser::parse(args);
+            let interrupted = parser::handle_interrupts()?;
+            let stats = parser::parse_interruptible(args, interrupted)?;
             println!("Run statistics:\n{stats:#?}");
         }
+        Command::Footer { .. } => {
+            let date_modified = parser::footer(args)?;
+            println!("Wrote the footer with schema:dateModified {date_modified}");
+        }
+        Command::CheckRefs { .. } => {
+            let report = check::check_refs(args)?;
+            print!("{report}");
+            if !report.is_valid() {
+                std::process::exit(1);
+            }
+        }
+        Command::Compact { .. } => {
+            let stats = compact::compact(args)?;
+            println!("Run statistics:\n{stats:#?}");
+        }
+        Command::BenchCompression { .. } => {
+            print!("{}", bench::bench_compression(args)?);
+        }
+        Command::Schema { format } => {
+            print!("{}", vocab::describe(format));
+        }
         //
         // Command::Update { .. } => todo!(),
     }
@@ -0,0 +1,189 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::path::Path;
+
+use anyhow::Context as _;
+use osmpbf::{Blob, BlobDecode, RelMemberType};
+use rayon::iter::{ParallelBridge as _, ParallelIterator as _};
+
+use crate::input::PbfReader;
+use crate::{Args, Command};
+
+/// Maximum number of dangling references to keep as examples
+const MAX_SAMPLES: usize = 10;
+
+/// IDs of all elements present in the file
+#[derive(Default)]
+struct KnownIds {
+    nodes: HashSet<i64>,
+    ways: HashSet<i64>,
+    rels: HashSet<i64>,
+}
+
+impl KnownIds {
+    fn from_blob(blob: Result<Blob, osmpbf::Error>) -> Result<Self, osmpbf::Error> {
+        let mut ids = Self::default();
+        if let BlobDecode::OsmData(block) = blob?.decode()? {
+            for group in block.groups() {
+                ids.nodes.extend(group.nodes().map(|v| v.id()));
+                ids.nodes.extend(group.dense_nodes().map(|v| v.id()));
+                ids.ways.extend(group.ways().map(|v| v.id()));
+                ids.rels.extend(group.relations().map(|v| v.id()));
+            }
+        }
+        Ok(ids)
+    }
+
+    fn combine(mut self, other: Self) -> Self {
+        self.nodes.extend(other.nodes);
+        self.ways.extend(other.ways);
+        self.rels.extend(other.rels);
+        self
+    }
+
+    fn contains(&self, member_type: &RelMemberType, id: i64) -> bool {
+        match member_type {
+            RelMemberType::Node => self.nodes.contains(&id),
+            RelMemberType::Way => self.ways.contains(&id),
+            RelMemberType::Relation => self.rels.contains(&id),
+        }
+    }
+}
+
+/// Results of the `check-refs` command
+#[derive(Debug, Default)]
+pub struct RefsReport {
+    pub ways: u64,
+    pub relations: u64,
+    pub ways_with_missing_nodes: u64,
+    pub missing_way_nodes: u64,
+    pub relations_with_missing_members: u64,
+    pub missing_relation_members: u64,
+    /// A few examples of the dangling references, e.g. `way/20 -> node/10`
+    pub samples: Vec<String>,
+}
+
+impl RefsReport {
+    pub fn is_valid(&self) -> bool {
+        self.missing_way_nodes == 0 && self.missing_relation_members == 0
+    }
+
+    fn add_sample(&mut self, sample: impl FnOnce() -> String) {
+        if self.samples.len() < MAX_SAMPLES {
+            self.samples.push(sample());
+        }
+    }
+
+    fn from_blob(ids: &KnownIds, blob: Result<Blob, osmpbf::Error>) -> Result<Self, osmpbf::Error> {
+        let mut report = Self::default();
+        if let BlobDecode::OsmData(block) = blob?.decode()? {
+            for group in block.groups() {
+                for way in group.ways() {
+                    report.ways += 1;
+                    let mut missing = 0;
+                    for node in way.refs().filter(|id| !ids.nodes.contains(id)) {
+                        missing += 1;
+                        report.add_sample(|| format!("way/{} -> node/{node}", way.id()));
+                    }
+                    if missing > 0 {
+                        report.ways_with_missing_nodes += 1;
+                        report.missing_way_nodes += missing;
+                    }
+                }
+                for rel in group.relations() {
+                    report.relations += 1;
+                    let mut missing = 0;
+                    for mbr in rel.members() {
+                        if !ids.contains(&mbr.member_type, mbr.member_id) {
+                            missing += 1;
+                            report.add_sample(|| {
+                                let mbr_type = match mbr.member_type {
+                                    RelMemberType::Node => "node",
+                                    RelMemberType::Way => "way",
+                                    RelMemberType::Relation => "relation",
+                                };
+                                format!("relation/{} -> {mbr_type}/{}", rel.id(), mbr.member_id)
+                            });
+                        }
+                    }
+                    if missing > 0 {
+                        report.relations_with_missing_members += 1;
+                        report.missing_relation_members += missing;
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    fn combine(mut self, other: Self) -> Self {
+        self.ways += other.ways;
+        self.relations += other.relations;
+        self.ways_with_missing_nodes += other.ways_with_missing_nodes;
+        self.missing_way_nodes += other.missing_way_nodes;
+        self.relations_with_missing_members += other.relations_with_missing_members;
+        self.missing_relation_members += other.missing_relation_members;
+        for sample in other.samples {
+            self.add_sample(|| sample);
+        }
+        self
+    }
+}
+
+impl Display for RefsReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Checked {} ways and {} relations",
+            self.ways, self.relations
+        )?;
+        writeln!(
+            f,
+            "Ways with missing nodes: {} ({} missing node references)",
+            self.ways_with_missing_nodes, self.missing_way_nodes
+        )?;
+        writeln!(
+            f,
+            "Relations with missing members: {} ({} missing member references)",
+            self.relations_with_missing_members, self.missing_relation_members
+        )?;
+        if !self.samples.is_empty() {
+            writeln!(f, "Examples of dangling references:")?;
+            for sample in &self.samples {
+                writeln!(f, "  {sample}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Verify that all way nodes and relation members referenced in the file are also present in it.
+pub fn check_refs(opt: Args) -> anyhow::Result<RefsReport> {
+    let Command::CheckRefs { input_file } = opt.cmd else {
+        unreachable!()
+    };
+    let ids = read_blobs(&input_file, KnownIds::from_blob, KnownIds::combine)?;
+    read_blobs(
+        &input_file,
+        |blob| RefsReport::from_blob(&ids, blob),
+        RefsReport::combine,
+    )
+}
+
+/// Process all blobs in parallel, and combine their results.
+/// A local thread pool is used, so that a later `parse` in the same process can still configure the global one.
+fn read_blobs<T: Default + Send>(
+    input_file: &Path,
+    map: impl Fn(Result<Blob, osmpbf::Error>) -> Result<T, osmpbf::Error> + Sync + Send,
+    combine: impl Fn(T, T) -> T + Sync + Send,
+) -> anyhow::Result<T> {
+    let reader = PbfReader::from_path(input_file)?;
+    let pool = rayon::ThreadPoolBuilder::new().build()?;
+    pool.install(|| {
+        reader
+            .par_bridge()
+            .map(map)
+            .try_reduce(T::default, |a, b| Ok(combine(a, b)))
+    })
+    .with_context(|| format!("Unable to read {}", input_file.display()))
+}
@@ -0,0 +1,117 @@
+use std::fs::File;
+use std::io::{BufRead as _, BufReader, Read as _, Write as _};
+use std::path::Path;
+
+use anyhow::{bail, Context as _};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::info;
+use path_absolutize::Absolutize as _;
+
+use crate::{Args, Command};
+
+/// Results of the `compact` command
+#[derive(Debug, Default)]
+pub struct CompactStats {
+    pub input_files: usize,
+    pub output_files: usize,
+}
+
+/// Concatenate the `.ttl.gz` files of a directory into fewer files of approximately the given size.
+/// Each output file starts with all the prefixes declared by any of the input files,
+/// while the prefix declarations of the individual input files are removed.
+pub fn compact(opt: Args) -> anyhow::Result<CompactStats> {
+    let Command::Compact {
+        max_file_size,
+        input_dir,
+        output_dir,
+    } = opt.cmd
+    else {
+        unreachable!()
+    };
+    if input_dir.canonicalize()? == output_dir.canonicalize()? {
+        bail!("Output directory must be different from the input directory");
+    }
+    let max_file_size = max_file_size * 1024 * 1024;
+
+    let mut input_files = Vec::new();
+    let dir = std::fs::read_dir(&input_dir)
+        .with_context(|| format!("Unable to read directory {}", input_dir.display()))?;
+    for entry in dir {
+        let path = entry?.path();
+        if path.to_string_lossy().ends_with(".ttl.gz") {
+            input_files.push(path);
+        }
+    }
+    input_files.sort();
+
+    // Collect all distinct prefixes, preserving the order in which they were first seen
+    let mut prefixes = Vec::new();
+    for file in &input_files {
+        for line in open_gz(file)?.lines() {
+            let line = line?;
+            if !line.starts_with("@prefix ") {
+                break;
+            }
+            if !prefixes.contains(&line) {
+                prefixes.push(line);
+            }
+        }
+    }
+
+    let mut stats = CompactStats {
+        input_files: input_files.len(),
+        ..CompactStats::default()
+    };
+    let mut encoder = None;
+    let mut size = 0_usize;
+    for file in &input_files {
+        let mut reader = open_gz(file)?;
+        let mut content = String::new();
+        // Skip the prefix declarations at the start of the file
+        while reader.read_line(&mut content)? > 0 && content.starts_with("@prefix ") {
+            content.clear();
+        }
+        reader.read_to_string(&mut content)?;
+
+        let enc = match &mut encoder {
+            Some(enc) => enc,
+            None => {
+                let enc = new_output_file(&output_dir, stats.output_files, &prefixes)?;
+                stats.output_files += 1;
+                encoder.insert(enc)
+            }
+        };
+        enc.write_all(content.as_bytes())?;
+        size += content.len();
+        if size > max_file_size {
+            encoder.take().unwrap().finish()?;
+            size = 0;
+        }
+    }
+    if let Some(enc) = encoder {
+        enc.finish()?;
+    }
+
+    Ok(stats)
+}
+
+fn open_gz(file: &Path) -> anyhow::Result<BufReader<MultiGzDecoder<File>>> {
+    let file = File::open(file).with_context(|| format!("Unable to open {}", file.display()))?;
+    Ok(BufReader::new(MultiGzDecoder::new(file)))
+}
+
+fn new_output_file(
+    output_dir: &Path,
+    index: usize,
+    prefixes: &[String],
+) -> anyhow::Result<GzEncoder<File>> {
+    let filename = output_dir.join(format!("osm-{index:06}.ttl.gz"));
+    info!("Creating {:?}", filename.absolutize()?);
+    let mut enc = GzEncoder::new(File::create(filename)?, Compression::default());
+    for prefix in prefixes {
+        writeln!(enc, "{prefix}")?;
+    }
+    Ok(enc)
+}
@@ -0,0 +1,164 @@
+use std::f64::consts::FRAC_PI_4;
+
+use geo::{BoundingRect as _, Centroid as _, Coord, LineString, Point, Polygon};
+
+use crate::CentroidMode;
+
+/// Compute the centroid of a line, treating `x` as longitude and `y` as latitude in degrees.
+pub fn centroid(line: &LineString, mode: CentroidMode) -> Option<Point> {
+    match mode {
+        CentroidMode::Planar => line.centroid(),
+        CentroidMode::Geodesic => geodesic_centroid(line),
+    }
+}
+
+/// Compute the area centroid of a polygon, treating `x` as longitude and `y` as latitude in degrees.
+/// Polygons without an area fall back to the centroid of their boundary.
+pub fn polygon_centroid(polygon: &Polygon, mode: CentroidMode) -> Option<Point> {
+    match mode {
+        CentroidMode::Planar => polygon.centroid(),
+        CentroidMode::Geodesic => geodesic_polygon_centroid(polygon.exterior())
+            .or_else(|| geodesic_centroid(polygon.exterior())),
+    }
+}
+
+/// Project longitude and latitude in degrees to Web Mercator (EPSG:3857) `x` and `y` in meters.
+pub fn to_web_mercator(lon: f64, lat: f64) -> (f64, f64) {
+    const EARTH_RADIUS: f64 = 6_378_137.0;
+    const MAX_LAT: f64 = 85.051_128_779_806_59;
+    let lat = lat.clamp(-MAX_LAT, MAX_LAT).to_radians();
+    let x = EARTH_RADIUS * lon.to_radians();
+    let y = EARTH_RADIUS * (FRAC_PI_4 + lat / 2.0).tan().ln();
+    (x, y)
+}
+
+/// Encode the location as a geohash with the given number of characters, e.g. `u4pruydqqvj`.
+/// Locations sharing a geohash prefix are close to each other.
+pub fn geohash(lat: f64, lon: f64, precision: u8) -> String {
+    const ALPHABET: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+    let (mut lat_range, mut lon_range) = ((-90.0, 90.0), (-180.0, 180.0));
+    let mut result = String::with_capacity(usize::from(precision));
+    // Bits alternate between longitude and latitude, starting with longitude
+    let mut is_lon = true;
+    for _ in 0..precision {
+        let mut idx = 0;
+        for _ in 0..5 {
+            let (range, value) = if is_lon {
+                (&mut lon_range, lon)
+            } else {
+                (&mut lat_range, lat)
+            };
+            let mid = (range.0 + range.1) / 2.0;
+            idx <<= 1;
+            if value >= mid {
+                idx |= 1;
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+            is_lon = !is_lon;
+        }
+        result.push(char::from(ALPHABET[idx]));
+    }
+    result
+}
+
+/// Check if the point lies within the bounding box of the line, extended by 5% of its size plus a rounding error.
+/// The margin accepts geodesic centroids of long east-west lines, which are shifted towards the pole.
+/// A centroid outside the bounding box usually means that one of the nodes has a bad location.
+pub fn is_within_bbox(line: &LineString, point: Point) -> bool {
+    let Some(bbox) = line.bounding_rect() else {
+        return false;
+    };
+    let margin = 1e-9 + bbox.width().max(bbox.height()) * 0.05;
+    let (min, max) = (bbox.min(), bbox.max());
+    (min.x - margin..=max.x + margin).contains(&point.x())
+        && (min.y - margin..=max.y + margin).contains(&point.y())
+}
+
+/// Centroid computed on a unit sphere: each segment contributes its great-circle midpoint,
+/// weighted by the segment's angular length. The resulting vector is projected back to lat/lon.
+fn geodesic_centroid(line: &LineString) -> Option<Point> {
+    let mut sum = [0.0; 3];
+    let mut total_len = 0.0;
+    for segment in line.lines() {
+        let start = to_vector(segment.start);
+        let end = to_vector(segment.end);
+        let len = norm(cross(start, end)).atan2(dot(start, end));
+        let mid = [start[0] + end[0], start[1] + end[1], start[2] + end[2]];
+        let mid_len = norm(mid);
+        if len > 0.0 && mid_len > 0.0 {
+            for i in 0..3 {
+                sum[i] += mid[i] / mid_len * len;
+            }
+            total_len += len;
+        }
+    }
+    if total_len == 0.0 {
+        // All points are the same (or there is only one)
+        return line.0.first().map(|c| Point::from(*c));
+    }
+    from_vector(sum)
+}
+
+/// Area centroid computed on a unit sphere: the ring is split into a fan of spherical triangles
+/// sharing the first point, and each triangle's center contributes with its signed area.
+fn geodesic_polygon_centroid(ring: &LineString) -> Option<Point> {
+    let first = to_vector(*ring.0.first()?);
+    let mut sum = [0.0; 3];
+    let mut total_area = 0.0;
+    for segment in ring.lines() {
+        let (b, c) = (to_vector(segment.start), to_vector(segment.end));
+        // Signed area (spherical excess) of the triangle, using the Van Oosterom & Strackee formula
+        let area =
+            2.0 * dot(first, cross(b, c)).atan2(1.0 + dot(first, b) + dot(b, c) + dot(c, first));
+        let center = [
+            first[0] + b[0] + c[0],
+            first[1] + b[1] + c[1],
+            first[2] + b[2] + c[2],
+        ];
+        let center_len = norm(center);
+        if center_len > 0.0 {
+            for i in 0..3 {
+                sum[i] += center[i] / center_len * area;
+            }
+            total_area += area;
+        }
+    }
+    if total_area.abs() < 1e-20 {
+        return None;
+    }
+    // A clockwise ring has a negative area, which also flips the sum
+    from_vector(sum.map(|v| v / total_area))
+}
+
+fn to_vector(coord: Coord) -> [f64; 3] {
+    let (lat, lon) = (coord.y.to_radians(), coord.x.to_radians());
+    [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()]
+}
+
+fn from_vector(v: [f64; 3]) -> Option<Point> {
+    let horizontal = v[0].hypot(v[1]);
+    if horizontal == 0.0 && v[2] == 0.0 {
+        return None;
+    }
+    let lat = v[2].atan2(horizontal).to_degrees();
+    let lon = v[1].atan2(v[0]).to_degrees();
+    Some(Point::new(lon, lat))
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn norm(v: [f64; 3]) -> f64 {
+    dot(v, v).sqrt()
+}
@@ -0,0 +1,93 @@
+use std::io::{self, Write};
+
+use flate2::Crc;
+use miniz_oxide::deflate::core::{
+    compress, create_comp_flags_from_zip_params, CompressionStrategy, CompressorOxide, TDEFLFlush,
+    TDEFLStatus,
+};
+
+use crate::DeflateStrategy;
+
+/// Gzip header without a file name or modification time, with an unknown OS
+const GZIP_HEADER: [u8; 10] = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+
+/// Size of the buffer receiving the compressed data before it is written out
+const OUT_BUF_SIZE: usize = 32 * 1024;
+
+/// Gzip encoder with a configurable deflate strategy, see `--deflate-strategy`.
+/// `flate2::write::GzEncoder` always uses the default strategy, so this one drives the raw deflate
+/// compressor of `miniz_oxide` (the `flate2` backend), and writes the gzip header and trailer itself.
+pub struct GzStrategyEncoder<W: Write> {
+    inner: W,
+    compressor: Box<CompressorOxide>,
+    crc: Crc,
+    out_buf: Vec<u8>,
+}
+
+impl<W: Write> GzStrategyEncoder<W> {
+    /// Create an encoder with a compression level from 0 to 9, and write the gzip header
+    pub fn new(mut inner: W, level: u32, strategy: DeflateStrategy) -> io::Result<Self> {
+        let strategy = match strategy {
+            DeflateStrategy::Default => CompressionStrategy::Default,
+            DeflateStrategy::Filtered => CompressionStrategy::Filtered,
+            DeflateStrategy::Rle => CompressionStrategy::RLE,
+        };
+        // Negative window bits produce a raw deflate stream, using the maximum 32KB window
+        let flags = create_comp_flags_from_zip_params(level as i32, -15, strategy as i32);
+        inner.write_all(&GZIP_HEADER)?;
+        Ok(Self {
+            inner,
+            compressor: Box::new(CompressorOxide::new(flags)),
+            crc: Crc::new(),
+            out_buf: vec![0; OUT_BUF_SIZE],
+        })
+    }
+
+    /// Complete the deflate stream, write the gzip trailer, and return the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.deflate(&[], TDEFLFlush::Finish)?;
+        self.inner.write_all(&self.crc.sum().to_le_bytes())?;
+        self.inner.write_all(&self.crc.amount().to_le_bytes())?;
+        Ok(self.inner)
+    }
+
+    /// Compress the whole input, and write out all compressed data the compressor produced
+    fn deflate(&mut self, mut input: &[u8], flush: TDEFLFlush) -> io::Result<()> {
+        loop {
+            let (status, read, written) =
+                compress(&mut self.compressor, input, &mut self.out_buf, flush);
+            self.inner.write_all(&self.out_buf[..written])?;
+            input = &input[read..];
+            match status {
+                TDEFLStatus::Done => return Ok(()),
+                // A full output buffer may mean there is more compressed data pending
+                TDEFLStatus::Okay
+                    if input.is_empty()
+                        && written < self.out_buf.len()
+                        && flush != TDEFLFlush::Finish =>
+                {
+                    return Ok(())
+                }
+                TDEFLStatus::Okay => {}
+                TDEFLStatus::BadParam | TDEFLStatus::PutBufFailed => {
+                    return Err(io::Error::other(format!(
+                        "deflate compression failed: {status:?}"
+                    )))
+                }
+            }
+        }
+    }
+}
+
+impl<W: Write> Write for GzStrategyEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.deflate(buf, TDEFLFlush::None)?;
+        self.crc.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.deflate(&[], TDEFLFlush::Sync)?;
+        self.inner.flush()
+    }
+}
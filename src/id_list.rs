@@ -0,0 +1,65 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use anyhow::{bail, Context as _};
+
+use crate::utils::Element;
+
+/// Set of elements to export, used by `--id-list`
+#[derive(Debug, Clone, Default)]
+pub struct IdList {
+    ids: HashSet<(Element, i64)>,
+}
+
+impl IdList {
+    /// Parse the list from lines like `node/123`, `way/456`, or `relation/789`.
+    /// Empty lines and `#` comments are ignored.
+    pub fn parse(list: &str) -> anyhow::Result<Self> {
+        let mut ids = HashSet::new();
+        for (idx, line) in list.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((elem, id)) = line.split_once('/') else {
+                bail!("Line {}: expected `type/id`, got `{line}`", idx + 1);
+            };
+            let elem = match elem {
+                "node" => Element::Node,
+                "way" => Element::Way,
+                "relation" => Element::Relation,
+                _ => bail!("Line {}: unknown element type `{elem}`", idx + 1),
+            };
+            let Ok(id) = id.parse() else {
+                bail!("Line {}: invalid element id `{id}`", idx + 1);
+            };
+            ids.insert((elem, id));
+        }
+        Ok(Self { ids })
+    }
+
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        let list = std::fs::read_to_string(Path::new(path))
+            .with_context(|| format!("Unable to read id list {path}"))?;
+        Self::parse(&list).with_context(|| format!("Invalid id list {path}"))
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn contains(&self, elem: Element, id: i64) -> bool {
+        self.ids.contains(&(elem, id))
+    }
+
+    /// Get the listed elements that are not in the given set, sorted by type and id
+    pub fn missing(&self, found: &HashSet<(Element, i64)>) -> Vec<(Element, i64)> {
+        let mut missing: Vec<_> = self.ids.difference(found).copied().collect();
+        missing.sort_unstable();
+        missing
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs::File;
+use std::io::{BufRead as _, BufReader, Read};
+use std::path::Path;
+
+use anyhow::{bail, Context as _};
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use osmpbf::{Blob, BlobDecode, BlobReader};
+
+/// Input file name that reads the PBF data from the standard input
+pub const STDIN: &str = "-";
+
+/// Check if the input file name is `-`, i.e. the standard input
+pub fn is_stdin(path: impl AsRef<Path>) -> bool {
+    path.as_ref().as_os_str() == STDIN
+}
+
+/// Blobs of an input PBF file. A file compressed as a whole, e.g. `extract.osm.pbf.gz`, is decompressed
+/// while reading. The decompression runs on a single thread, and the blobs have no file offsets.
+pub enum PbfReader {
+    Raw(BlobReader<BufReader<File>>),
+    /// Decompressed file or a non-seekable stream, e.g. the standard input
+    Stream(BlobReader<Box<dyn Read + Send>>),
+}
+
+impl PbfReader {
+    /// Open a PBF file, detecting gzip and bzip2 compression by the first bytes of the file.
+    /// The `-` name reads from the standard input.
+    pub fn from_path(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if is_stdin(path) {
+            return Self::from_reader(std::io::stdin());
+        }
+        let mut file = BufReader::new(
+            File::open(path).with_context(|| format!("Unable to open {}", path.display()))?,
+        );
+        if is_compressed(file.fill_buf()?) {
+            return Self::from_reader(file);
+        }
+        Ok(Self::Raw(BlobReader::from_path(path)?))
+    }
+
+    /// Read the blobs from a stream, detecting gzip and bzip2 compression by its first bytes.
+    /// The stream is read sequentially, so only the decoding of the blobs can run in parallel.
+    pub fn from_reader(reader: impl Read + Send + 'static) -> anyhow::Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let magic = reader.fill_buf()?;
+        let decoder: Box<dyn Read + Send> = if magic.starts_with(&[0x1f, 0x8b]) {
+            Box::new(BufReader::new(MultiGzDecoder::new(reader)))
+        } else if magic.starts_with(b"BZh") {
+            Box::new(BufReader::new(MultiBzDecoder::new(reader)))
+        } else {
+            Box::new(reader)
+        };
+        Ok(Self::Stream(BlobReader::new(decoder)))
+    }
+
+    /// Check that the file exists and starts with an OSM header block, before any output is created
+    pub fn check_header(path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let not_pbf = || format!("Input file {} is not an OSM PBF file", path.display());
+        let blob = Self::from_path(path)?
+            .next()
+            .with_context(|| format!("Input file {} is empty", path.display()))?
+            .with_context(not_pbf)?;
+        if !matches!(
+            blob.decode().with_context(not_pbf)?,
+            BlobDecode::OsmHeader(_)
+        ) {
+            bail!(
+                "Input file {} does not start with an OSM header block",
+                path.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for PbfReader {
+    type Item = Result<Blob, osmpbf::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Raw(reader) => reader.next(),
+            Self::Stream(reader) => reader.next(),
+        }
+    }
+}
+
+/// Check if the first bytes of a file are the gzip or bzip2 magic numbers
+fn is_compressed(magic: &[u8]) -> bool {
+    magic.starts_with(&[0x1f, 0x8b]) || magic.starts_with(b"BZh")
+}
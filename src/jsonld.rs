@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use json::JsonValue;
+
+use crate::turtle::{parse_declarations, parse_statements, split_name, Converter, Object, Term};
+
+/// Name of the context file written to each output directory with `--format jsonld`
+pub const CONTEXT_FILE: &str = "context.jsonld";
+
+/// Converts the Turtle statements generated for each element into JSON-LD, see `--format jsonld`.
+/// Each element is a single-line JSON object referring to the shared context file for the prefixes.
+pub struct JsonLd {
+    /// Prefix name to IRI, e.g. `osmnode` to `https://www.openstreetmap.org/node/`
+    prefixes: Vec<(String, String)>,
+    /// Names of the prefixes usable in the compact IRIs
+    compact: HashSet<String>,
+}
+
+impl JsonLd {
+    /// Create from the prefix declarations in the `prefix name: <iri>` form
+    pub fn new<T: AsRef<str>>(declarations: impl IntoIterator<Item = T>) -> Self {
+        let prefixes = parse_declarations(declarations);
+        // JSON-LD only uses a term as a prefix if its IRI ends with a delimiter, e.g. `/` or `#`,
+        // so the names with other prefixes are written as full IRIs
+        let compact = prefixes
+            .iter()
+            .filter(|(_, iri)| iri.ends_with(['/', '#', ':', '?', '[', ']', '@']))
+            .map(|(name, _)| name.clone())
+            .collect();
+        Self { prefixes, compact }
+    }
+
+    /// The content of the context file, with the prefixes usable in the compact IRIs
+    pub fn context(&self) -> JsonValue {
+        let mut context = JsonValue::new_object();
+        for (name, iri) in &self.prefixes {
+            if self.compact.contains(name) {
+                context[name.as_str()] = iri.as_str().into();
+            }
+        }
+        json::object! { "@context": context }
+    }
+
+    fn convert(&self, subject: &str, statements: &str) -> Result<JsonValue, String> {
+        let mut result = json::object! {
+            "@context": CONTEXT_FILE,
+            "@id": self.compact_iri(subject)?,
+        };
+        self.add_statements(&mut result, &parse_statements(statements)?)?;
+        Ok(result)
+    }
+
+    /// Add the statements as the keys of the node object. Repeated predicates get an array of values.
+    fn add_statements(
+        &self,
+        node: &mut JsonValue,
+        statements: &[(Term, Object)],
+    ) -> Result<(), String> {
+        for (predicate, object) in statements {
+            let key = match predicate {
+                Term::Type => "@type".to_string(),
+                Term::Name(name) => self.compact_iri(name)?,
+                Term::Iri(iri) => iri.to_string(),
+                Term::Literal { .. } => return Err("literal used as a predicate".to_string()),
+            };
+            // Classes are plain strings, other IRIs are node references
+            let value = match object {
+                Object::Term(Term::Name(name)) if *predicate == Term::Type => {
+                    self.compact_iri(name)?.into()
+                }
+                Object::Term(Term::Iri(iri)) if *predicate == Term::Type => (*iri).into(),
+                Object::Term(term) => self.value(*term)?,
+                Object::BlankNode(node_statements) => {
+                    let mut value = JsonValue::new_object();
+                    self.add_statements(&mut value, node_statements)?;
+                    value
+                }
+            };
+            let entry = &mut node[key.as_str()];
+            match entry {
+                JsonValue::Null => *entry = value,
+                JsonValue::Array(values) => values.push(value),
+                _ => *entry = JsonValue::Array(vec![entry.take(), value]),
+            }
+        }
+        Ok(())
+    }
+
+    /// Format an object term as a JSON-LD value
+    fn value(&self, term: Term) -> Result<JsonValue, String> {
+        Ok(match term {
+            Term::Iri(iri) => json::object! { "@id": iri },
+            Term::Name(name) => json::object! { "@id": self.compact_iri(name)? },
+            Term::Type => return Err("`a` used as an object".to_string()),
+            Term::Literal {
+                text,
+                datatype,
+                lang,
+            } => {
+                // The generated literals use the JSON string escapes
+                let value = json::parse(text).map_err(|err| format!("invalid literal: {err}"))?;
+                match (datatype, lang) {
+                    (Some(datatype), _) => {
+                        let datatype = match datatype.strip_prefix('<') {
+                            Some(iri) => iri.trim_end_matches('>').to_string(),
+                            None => self.compact_iri(datatype)?,
+                        };
+                        json::object! { "@value": value, "@type": datatype }
+                    }
+                    (None, Some(lang)) => json::object! { "@value": value, "@language": lang },
+                    (None, None) => value,
+                }
+            }
+        })
+    }
+
+    /// Keep a prefixed name like `osmnode:123` as is if its prefix is in the context, or expand it otherwise
+    fn compact_iri(&self, name: &str) -> Result<String, String> {
+        let (prefix, local) = split_name(name)?;
+        if self.compact.contains(prefix) {
+            return Ok(name.to_string());
+        }
+        let (_, iri) = self
+            .prefixes
+            .iter()
+            .find(|(v, _)| v == prefix)
+            .ok_or_else(|| format!("unknown prefix `{prefix}`"))?;
+        Ok(format!("{iri}{local}"))
+    }
+}
+
+impl Converter for JsonLd {
+    fn write_element(
+        &self,
+        out: &mut dyn Write,
+        subject: &str,
+        statements: &str,
+    ) -> io::Result<usize> {
+        let element = self
+            .convert(subject, statements)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let line = format!("{}\n", element.dump());
+        out.write_all(line.as_bytes())?;
+        Ok(line.len())
+    }
+}
@@ -1,13 +1,40 @@
 #![doc = include_str!("../README.md")]
 
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 
-use anyhow::bail;
-use clap::{Parser, Subcommand};
+use anyhow::{bail, Context};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{Args as _, FromArgMatches as _, Parser, Subcommand};
+use lazy_static::lazy_static;
+use regex::Regex;
 
+use crate::id_list::IdList;
+use crate::tag_schema::TagSchema;
+use crate::utils::{Bbox, Element};
+
+pub mod bench;
+pub mod check;
+pub mod compact;
+pub mod geometry;
+pub mod gzip;
+pub mod id_list;
+pub mod input;
+pub mod jsonld;
+pub mod ntriples;
 pub mod parser;
+pub mod progress;
+pub mod proto;
+pub mod sparql;
 pub mod str_builder;
+pub mod tag_schema;
+pub mod turtle;
 pub mod utils;
+pub mod vocab;
+
+lazy_static! {
+    static ref RE_PREFIX_NAME: Regex = Regex::new(r"^[a-zA-Z][-0-9a-zA-Z_]*$").unwrap();
+}
 
 // group = ArgGroup::with_name("cache").required(true)
 // const PLANET_URL: &str = "https://planet.openstreetmap.org/replication/minute";
@@ -15,9 +42,8 @@ pub mod utils;
 #[derive(Parser, Debug)]
 #[command(about, version)]
 pub struct Args {
-    /// Enable verbose output.
+    /// Enable verbose output, including per-thread statistics. Same as `RUST_LOG=osm2rdf=debug`.
     #[arg(short, long)]
-    #[allow(dead_code)]
     pub verbose: bool,
 
     /// File for planet-size node cache.
@@ -28,13 +54,26 @@ pub struct Args {
     #[arg(short, long, group = "cache", value_name = "file")]
     pub small_cache: Option<PathBuf>,
 
+    /// Size of the memory-mapped pages of the `--planet-cache` file, in MB. The file grows one page at a time,
+    /// and each page reserves this much address space, so use a smaller size for regional extracts
+    /// on machines with little memory.
+    #[arg(long, default_value = "10240", value_name = "MB", value_parser = clap::value_parser!(u64).range(1..))]
+    pub cache_page_size: u64,
+
+    /// Write log messages to this file instead of the standard error.
+    #[arg(long, value_name = "file")]
+    pub log_file: Option<PathBuf>,
+
     #[command(subcommand)]
     pub cmd: Command,
 }
 
+// Parsed once at startup, so the size difference between the commands does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 pub enum Command {
-    /// Parses a PBF file into multiple .ttl.gz (Turtle files)
+    /// Parses a PBF file into multiple .ttl.gz (Turtle files).
+    /// On Ctrl-C, stops reading the input and finishes the output files, producing a valid but incomplete dataset.
     Parse {
         /// Approximate maximum uncompressed file size, in MB, per output file.
         #[arg(short, long, default_value = "100")]
@@ -42,12 +81,231 @@ pub enum Command {
         /// Number of worker threads to run, or 0 to disables multithreading. Defaults to number of logical CPUs.
         #[arg(short, long)]
         workers: Option<usize>,
+        /// Number of consecutive PBF blobs to process in one parallel task. Larger values reduce
+        /// the scheduling overhead when blobs decode quickly, e.g. on machines with many cores.
+        /// Only used with multithreading.
+        #[arg(long, default_value = "1", value_name = "n")]
+        blob_chunk: NonZeroUsize,
+        /// Number of converted elements the parser threads collect before passing them to the writer.
+        /// Together with `--queue-depth`, limits the memory used by the elements waiting to be written:
+        /// a small batch and a shallow queue reduce the memory use at some throughput cost.
+        /// With `--emit-index`, each batch is a separate gzip member.
+        #[arg(long, default_value = "1024", value_name = "n")]
+        batch_size: NonZeroUsize,
+        /// Maximum number of batches waiting for the writer. Once the queue is full, the parser threads wait
+        /// for the writer instead of using more memory, e.g. when the compression cannot keep up.
+        #[arg(long, default_value = "256", value_name = "n")]
+        queue_depth: NonZeroUsize,
+        /// Number of threads compressing and writing the output files. Each thread writes to its own file,
+        /// so the elements are spread across more files, and their order is no longer deterministic.
+        /// The threads take the next batch of elements as soon as they are done with the previous one.
+        #[arg(long, visible_alias = "writers", default_value = "1", value_name = "n")]
+        io_threads: NonZeroUsize,
+        /// Flush the current output file every N seconds, so that a crash loses at most N seconds of data.
+        /// A flushed file is still missing the gzip trailer if the process is killed,
+        /// so recover it with `zcat file.ttl.gz | gzip > fixed.ttl.gz` (ignoring the "unexpected end of file" error).
+        #[arg(long, value_name = "seconds", value_parser = clap::value_parser!(u64).range(1..))]
+        flush_interval: Option<u64>,
+        /// Source of the `schema:dateModified` value: the latest timestamp of all parsed `elements`,
+        /// or the replication timestamp stored in the PBF `header`. All input files must have a header timestamp.
+        #[arg(long, value_enum, default_value_t)]
+        date_source: DateSource,
+        /// Write each batch of elements as a separate gzip member, and list the members of every
+        /// `osm-NNNNNN.ttl.gz` file in an `osm-NNNNNN.ttl.gz.gzi` text file, one `<offset>\t<first>\t<last>` line
+        /// per member, e.g. `1234\tosmnode:10\tosmway:20`. The offset is the member's position in the compressed file.
+        /// Consumers can seek to an offset and decompress just that member. Slightly reduces the compression ratio.
+        /// With `--compression zstd`, each batch is a separate zstd frame instead.
+        #[arg(long)]
+        emit_index: bool,
+        /// Write `index.csv` to the output directory, with the smallest and largest element id of each type
+        /// in every output file, one `file,type,min_id,max_id` line per file and type, e.g.
+        /// `osm-000003.ttl.gz,way,1200,5890`. Helps to find the file with the statements of an element.
+        /// The files of the deleted elements and the footer are not listed. Cannot be used with `--socket`.
+        #[arg(long)]
+        emit_file_index: bool,
+        /// Write the elements sorted by type and id, so the output does not depend on the blob order or threading.
+        /// All converted elements are kept in memory until the input is parsed, so only use it for small extracts.
+        /// Cannot be used with `--flush-interval` or multiple `--io-threads`.
+        #[arg(long)]
+        sorted_output: bool,
+        /// Base of the `osmroot:`, `osmnode:`, `osmway:`, and `osmrel:` IRIs, e.g. for a private OSM mirror.
+        /// The elements become `<base>/node/123`, `<base>/way/456`, and `<base>/relation/789`.
+        #[arg(long, value_name = "url", default_value = parser::OSM_BASE_URL, value_parser = parse_base_url)]
+        osm_base_url: String,
+        /// Write SPARQL Update requests instead of Turtle, in `osm-NNNNNN.ru.gz` files. Each element is preceded
+        /// by `DELETE WHERE { osmway:123 ?p ?o }`, so loading an element again replaces all its statements.
+        /// Only supported with the turtle output format.
+        #[arg(long)]
+        upsert: bool,
+        /// Compression of the output files: `osm-NNNNNN.ttl.gz` with gzip, `osm-NNNNNN.ttl.zst` with zstd,
+        /// or uncompressed `osm-NNNNNN.ttl` files. Zstd is much faster than gzip, which is often the bottleneck.
+        #[arg(long, value_enum, default_value_t)]
+        compression: OutputCompression,
+        /// Compression level, from 0 (fastest) to 9 (smallest). Defaults to 6 for gzip and 3 for zstd.
+        /// Cannot be used with `--compression none`.
+        #[arg(long, value_name = "0-9", value_parser = clap::value_parser!(u32).range(0..=9))]
+        compression_level: Option<u32>,
+        /// Deflate strategy of the gzip compression. `filtered` ignores the matches shorter than 5 bytes,
+        /// which slightly improves the ratio of the highly repetitive Turtle statements. `rle` only compresses
+        /// the runs of repeated bytes, such as the newlines between the elements. The deflate window
+        /// is always the maximum 32KB. Compare the strategies on a sample with `bench-compression`.
+        /// Can only be used with `--compression gzip`.
+        #[arg(long, value_enum, default_value_t)]
+        deflate_strategy: DeflateStrategy,
+        /// Stream the statements to this Unix domain socket instead of writing the output files,
+        /// e.g. to a loader process listening on it. The prefixes are sent once at the start of the stream,
+        /// and the footer at the end. Only the `issues.json` file is still written to the output directory.
+        /// If the loader closes the socket, the remaining statements are discarded and the run fails.
+        #[arg(long, value_name = "path")]
+        socket: Option<PathBuf>,
+        /// Post the statements to this SPARQL Update endpoint instead of writing the output files,
+        /// one request per batch of elements in the `--upsert` form, followed by the footer.
+        /// Repeat it to send the same requests to several endpoints, e.g. the replicas of a triple store.
+        /// Each endpoint is retried on its own, and one that still fails is given up while the others
+        /// continue; the run then fails once the input is parsed, and `--stats-json` lists the requests
+        /// sent to each endpoint. Only the `issues.json` file is still written to the output directory.
+        /// Cannot be used with `--socket`, `--dry-run`, `--shard-by-id`, `--emit-index`, `--emit-file-index`,
+        /// multiple `--io-threads`, or the other output formats.
+        #[arg(long, value_name = "url", value_parser = sparql::parse_endpoint_url)]
+        sparql_endpoint: Vec<String>,
+        /// Number of times a failed `--sparql-endpoint` request is retried, waiting twice as long each time,
+        /// starting at half a second. The requests rejected by the endpoint with a 4xx status are not retried.
+        #[arg(long, value_name = "n", default_value = "3")]
+        sparql_retries: u32,
+        /// Read the input twice: the first pass only caches the node locations of all input files,
+        /// and the second one generates the statements. Without it, a way processed by one worker may need
+        /// the nodes of a blob that is still being processed by another one, and gets a wrong location.
+        #[arg(long)]
+        two_pass: bool,
+        /// Split the output into N `shard-NNN` subdirectories, writing each element to the shard `id % N`
+        /// regardless of its type. Useful for loaders that shard by id, as the shards have a similar size.
+        /// The footer is written to the first shard. Cannot be used with `--socket`.
+        #[arg(long, value_name = "n")]
+        shard_by_id: Option<NonZeroUsize>,
+        /// File with additional Turtle prefix declarations, one `prefix name: <iri>` per line,
+        /// written to the header of each output file. A declaration with the same name as a built-in prefix
+        /// replaces it, e.g. to map `osmnode:` to another IRI. Empty lines and `#` comments are ignored.
+        #[arg(long, value_name = "file", value_parser = PrefixFile::from_file)]
+        prefixes: Option<PrefixFile>,
+        /// Write the statistics of the run to this JSON file, including the totals of the added, skipped,
+        /// and deleted elements, and the elapsed time in seconds. The file is also written if writing
+        /// the output fails once the input is parsed, with the `error` field set.
+        #[arg(long, value_name = "file")]
+        stats_json: Option<PathBuf>,
+        /// Show a progress bar with the number of processed blobs and elements, and the size of the input read.
+        /// If the standard error is not a terminal, the progress is logged every 30 seconds instead.
+        #[arg(long)]
+        progress: bool,
+        /// Parse the input with all the same options, but discard the generated statements instead of writing them,
+        /// e.g. to validate a large input before an import. The statistics match the ones of a real run,
+        /// and nothing is written to the output directory, including the footer and `issues.json`.
+        /// Cannot be used with `--socket`.
+        #[arg(long)]
+        dry_run: bool,
+        /// Save the progress to the `.osm2rdf-state` file in the output directory every N seconds, and when
+        /// the run is interrupted, so that it can be continued with `--resume`. The current output files
+        /// are finished at each checkpoint, so a short interval creates more and smaller files.
+        /// Cannot be used with the same options as `--resume`.
+        #[arg(long, value_name = "seconds", value_parser = clap::value_parser!(u64).range(1..))]
+        checkpoint_interval: Option<u64>,
+        /// Save the progress like `--checkpoint-interval`, but every N blobs of the input, e.g. to bound
+        /// the work repeated after a crash independently of the parsing speed. With multithreading,
+        /// no more blobs are read at a checkpoint until all the read ones are done, so the saved progress
+        /// is always a contiguous run of blobs. A small N leaves the worker threads idle more often.
+        /// Can be combined with `--checkpoint-interval`, whichever comes first saves the progress.
+        /// Cannot be used with the same options as `--resume`.
+        #[arg(long, value_name = "blobs", value_parser = clap::value_parser!(u64).range(1..))]
+        checkpoint_every: Option<u64>,
+        /// Stop after parsing N blobs of the input as if interrupted with Ctrl-C, and save the progress
+        /// for `--resume`, e.g. to convert a large input over several shorter runs.
+        /// Cannot be used with the same options as `--resume`.
+        #[arg(long, value_name = "blobs", value_parser = clap::value_parser!(u64).range(1..))]
+        stop_after: Option<u64>,
+        /// Continue the run saved in the `.osm2rdf-state` file of the output directory by `--checkpoint-interval`,
+        /// `--checkpoint-every`, or `--stop-after`. The blobs converted by the saved run are skipped, the output files written after
+        /// its last checkpoint are replaced, and the new files are numbered after the saved ones.
+        /// Requires the same input files and options as the saved run, otherwise the dataset is inconsistent;
+        /// only the input file names are checked. The node locations of the skipped blobs are taken from
+        /// the `--planet-cache` or `--small-cache` of the saved run, or cached again with `--two-pass`.
+        /// The `--small-cache` is only saved when a run ends, so it is incomplete after a crash.
+        /// The statistics and `issues.json` only cover the resumed part of the input.
+        /// Cannot be used with `--socket`, `--dry-run`, `--sorted-output`, `--emit-file-index`,
+        /// multiple `--io-threads`, or the standard input.
+        #[arg(long)]
+        resume: bool,
+        /// One or more OSM input PBF files, processed in order with a shared node cache.
+        /// Use this with overlapping regional extracts, optionally together with `--small-cache`
+        /// to keep the cache between runs. Files compressed with gzip or bzip2, e.g. `extract.osm.pbf.gz`,
+        /// are decompressed while reading. Use `-` to read the standard input, e.g. `osmupdate ... | osm2rdf parse - out/`.
+        /// The standard input can only be read once, so it cannot be used with `--two-pass`, `--date-source header`,
+        /// `--progress`, or the options requiring a pre-pass. Its blobs are read on a single thread,
+        /// so the workers may wait for the pipe if its producer is slower than the decoding.
+        #[arg(required = true, num_args = 1..)]
+        input_files: Vec<PathBuf>,
+        /// Output directory
+        #[arg(value_parser = parse_outdir)]
+        output_dir: PathBuf,
+        #[command(flatten)]
+        opts: ParserOpts,
+    },
+    /// Writes only the footer file with the `schema:dateModified` of a dataset, without converting the elements,
+    /// e.g. if the footer of a run was lost, or the dataset was assembled from several runs.
+    /// The file is numbered after the last `osm-NNNNNN` file in the output directory.
+    /// Unlike the `parse` footer, it does not contain the `osmm:datasetBbox` statement.
+    Footer {
+        /// Source of the `schema:dateModified` value: the latest timestamp of the `elements` converted
+        /// with the default options, the same as `parse` would write, or the replication timestamp
+        /// stored in the PBF `header`. Reading the header is much faster, as the elements are not decoded.
+        #[arg(long, value_enum, default_value_t)]
+        date_source: DateSource,
+        /// Base of the `osmroot:` IRI, must match the one of the dataset
+        #[arg(long, value_name = "url", default_value = parser::OSM_BASE_URL, value_parser = parse_base_url)]
+        osm_base_url: String,
+        /// Compression of the footer file, must match the one of the dataset
+        #[arg(long, value_enum, default_value_t)]
+        compression: OutputCompression,
+        /// One or more OSM input PBF files of the dataset
+        #[arg(required = true, num_args = 1..)]
+        input_files: Vec<PathBuf>,
+        /// Output directory of the dataset
+        #[arg(value_parser = parse_outdir)]
+        output_dir: PathBuf,
+    },
+    /// Verifies that all nodes used by ways, and all relation members are present in a PBF file.
+    /// Useful to check if an extract is complete before importing it.
+    CheckRefs {
         /// OSM input PBF file
         input_file: PathBuf,
-        /// Output directory
+    },
+    /// Concatenates the .ttl.gz files generated by the "parse" command into fewer larger files.
+    /// Prefix declarations are written once at the start of each new file.
+    Compact {
+        /// Approximate maximum uncompressed file size, in MB, per output file.
+        #[arg(short, long, default_value = "100")]
+        max_file_size: usize,
+        /// Directory with the .ttl.gz files to combine
+        input_dir: PathBuf,
+        /// Output directory, must be different from the input directory
         #[arg(value_parser = parse_outdir)]
         output_dir: PathBuf,
     },
+    /// Converts the first elements of a PBF file in memory, and compares the output size
+    /// and compression time of the supported codecs.
+    BenchCompression {
+        /// Maximum number of converted elements to compress
+        #[arg(long, default_value = "100000", value_name = "n")]
+        sample_size: usize,
+        /// OSM input PBF file
+        input_file: PathBuf,
+    },
+    /// Prints all predicates that may appear in the output, with their datatypes and descriptions.
+    /// The Turtle format also declares the classes used by `--emit-rdf-type`.
+    /// Tag predicates such as `osmt:name` and relation member roles are not listed, as their names depend on the data.
+    Schema {
+        /// Output format
+        #[arg(long, value_enum, default_value_t)]
+        format: SchemaFormat,
+    },
     // /// Download OSM incremental update files and store them as either TTL files or the RDF database.
     // Update {
     //     /// Start updating from this sequence ID. By default, gets it from RDF server.
@@ -71,6 +329,584 @@ pub enum Command {
     // },
 }
 
+/// Options controlling which statements are generated for each element
+#[derive(clap::Args, Debug, Clone)]
+pub struct ParserOpts {
+    /// Output format. `ntriples` writes the same statements as `turtle`, but with one complete triple per line
+    /// and all prefixed names expanded, which some triple stores load faster.
+    /// `jsonld` writes one JSON-LD object per line for each element, e.g. `{"@context":"context.jsonld","@id":"osmnode:1",...}`,
+    /// with the typed literals as `{"@value":"1","@type":"xsd:integer"}`. The prefixes are declared
+    /// in the `context.jsonld` file written to each output directory.
+    /// `protobuf` writes a gzip-compressed stream of length-delimited `Element` messages
+    /// as defined in `proto/osm2rdf.proto`, with the raw tags, geometry, and metadata of each element.
+    /// Options that only affect RDF statements are ignored, and no modification date file is created.
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+
+    /// Do not emit the `osmm:type` statement. The element type is already encoded in the subject prefix.
+    /// Shorthand for `--type-style class` when combined with `--emit-rdf-type`.
+    #[arg(long)]
+    pub no_type_predicate: bool,
+
+    /// Do not emit the `osmm:version`, `osmm:user`, `osmm:timestamp`, and `osmm:changeset` statements,
+    /// e.g. for a rendering database that does not need the edit history. This usually halves the output size.
+    /// The `schema:dateModified` of the dataset is still written.
+    #[arg(long)]
+    pub no_metadata: bool,
+
+    /// Emit `osmm:user osmm:anonymous` for the elements without a user name, e.g. anonymous or redacted edits.
+    /// By default, `osmm:user` is omitted for them.
+    #[arg(long)]
+    pub mark_anonymous_users: bool,
+
+    /// Format of the `osmm:timestamp` values: `iso` as `"2014-01-01 00:00:00 UTC"^^xsd:dateTime`,
+    /// or the number of milliseconds or seconds since the Unix epoch as `xsd:integer`, e.g. `"1388534400"^^xsd:integer`.
+    /// The `schema:dateModified` of the dataset always uses `xsd:dateTime`.
+    #[arg(long, value_enum, default_value_t)]
+    pub timestamp_format: TimestampFormat,
+
+    /// Emit the class of each element, i.e. `a osmm:Node` or `a osmm:Way`. Relations get a class
+    /// based on their `type` tag, e.g. `a osmm:Boundary` or `a osmm:Route`, or `a osmm:Relation` for other types.
+    /// The classes are declared by the `schema --format turtle` command. The `osmm:type` statement is still
+    /// emitted, so consumers of either convention can use the same output, unless `--no-type-predicate` is set.
+    /// Shorthand for `--type-style both`.
+    #[arg(long)]
+    pub emit_rdf_type: bool,
+
+    /// Which statements declare the element type: the `short` literal, e.g. `osmm:type "n"`, the `rdf:type`
+    /// `class`, e.g. `a osmm:Node`, or `both` of them to ease the migration between the two conventions.
+    #[arg(long, value_enum, default_value_t)]
+    pub type_style: TypeStyle,
+
+    /// How to compute the way centroids. `planar` treats lat/lon as flat coordinates, which is fast
+    /// but distorts the result for large features and at high latitudes. `geodesic` computes the centroid
+    /// on a sphere, which is accurate everywhere but requires several trigonometric calls per node.
+    #[arg(long, value_enum, default_value_t)]
+    pub centroid_mode: CentroidMode,
+
+    /// Ways that get the `osmm:isClosed` statement: `all` ways with at least two nodes, only the `areas`,
+    /// i.e. the closed ways with at least four nodes that are not tagged `area=no`, or `none` of them.
+    #[arg(long, value_enum, default_value_t)]
+    pub is_closed_for: IsClosedFor,
+
+    /// Emit `osmm:hash` with a stable fingerprint of the element's statements and version.
+    /// Compare hashes between runs with the same options to detect changed elements.
+    #[arg(long)]
+    pub emit_hash: bool,
+
+    /// Emit `osmm:isNew "true"` for the elements with version 1, i.e. the ones that were created
+    /// but never modified since, e.g. to find freshly created features.
+    #[arg(long)]
+    pub emit_new_flag: bool,
+
+    /// Only emit elements modified at or after this time, e.g. `2023-01-31` or `2023-01-31T12:00:00Z`.
+    /// Older nodes are still cached to compute way geometries.
+    #[arg(long, value_parser = parse_timestamp, value_name = "iso8601")]
+    pub modified_since: Option<DateTime<Utc>>,
+
+    /// Coordinate reference system of the emitted points. `4326` emits WGS 84 longitude and latitude in degrees.
+    /// `3857` emits Web Mercator coordinates in meters, and declares the CRS in each literal
+    /// as required by GeoSPARQL, e.g. `"<http://www.opengis.net/def/crs/EPSG/0/3857> Point(x y)"^^geo:wktLiteral`.
+    /// Latitudes beyond ±85.051129° are clamped to the edge of the Web Mercator square.
+    /// Precision options apply to the emitted values, i.e. to meters in the `3857` mode.
+    #[arg(long, value_enum, default_value_t)]
+    pub crs: Crs,
+
+    /// Declare the CRS in the WGS 84 literals too, e.g. `"<http://www.opengis.net/def/crs/OGC/1.3/CRS84> Point(x y)"`,
+    /// so that the strict GeoSPARQL engines interpret the longitude and latitude order unambiguously.
+    /// The `3857` literals always declare their CRS.
+    #[arg(long)]
+    pub wkt_crs_uri: bool,
+
+    /// Round node coordinates to this many digits after the decimal point. Full precision by default.
+    #[arg(long, value_name = "digits", value_parser = clap::value_parser!(u8).range(0..=15))]
+    pub coord_precision: Option<u8>,
+
+    /// Emit `osmm:geohash` with this many characters for the nodes and way centroids, e.g. `u33d` for 4.
+    /// Elements sharing a geohash prefix are close to each other, which allows simple spatial grouping.
+    #[arg(long, value_name = "chars", value_parser = clap::value_parser!(u8).range(1..=12))]
+    pub geohash_precision: Option<u8>,
+
+    /// Round way centroids to this many digits after the decimal point. Defaults to `--coord-precision`.
+    #[arg(long, value_name = "digits", value_parser = clap::value_parser!(u8).range(0..=15))]
+    pub centroid_precision: Option<u8>,
+
+    /// Verify that each way centroid lies within the way's bounding box, and emit
+    /// `osmm:geometryWarning "centroid_outside_bbox"` otherwise. This usually indicates a node with a bad location.
+    #[arg(long)]
+    pub check_geometry: bool,
+
+    /// Emit `osmm:wayMembershipCount` with the number of ways referencing each node,
+    /// and also emit untagged nodes shared by several ways, e.g. intersections.
+    /// This requires an extra pass over the input file to count way node references,
+    /// and keeps the count for every referenced node in memory.
+    #[arg(long)]
+    pub emit_membership_count: bool,
+
+    /// Emit `osmm:roleCount` for relations with the number of members with each role, e.g. `outer=3;inner=1`,
+    /// in the order of the first appearance of each role. Members without a role are counted under `none`,
+    /// e.g. `stop=1;none=3`, together with the members whose role is literally `none`.
+    #[arg(long)]
+    pub emit_role_counts: bool,
+
+    /// Emit `osmm:member` for each relation member with its position in the member list, its IRI, and its role,
+    /// e.g. `osmm:member [osmm:index "0"^^xsd:integer; osmm:ref osmway:456; osmm:role "outer"]`.
+    /// The index starts at 0 and counts all members in the input, so the skipped ones leave gaps.
+    /// Unlike `osmm:has`, this keeps the order and the repeated members, e.g. to assemble multipolygon rings.
+    #[arg(long)]
+    pub emit_member_order: bool,
+
+    /// Emit `osmm:danglingMembers` with the number of relation members missing from the input,
+    /// which is common in extracts. This requires an extra pass over the input file, and keeps the ids
+    /// of all elements in memory during that pass, so it is intended for extracts rather than the full planet.
+    #[arg(long)]
+    pub validate_members: bool,
+
+    /// Do not emit `osmm:has` and the role statements of the members missing from the input.
+    /// Requires `--validate-members`.
+    #[arg(long)]
+    pub omit_dangling_members: bool,
+
+    /// Do not emit untagged ways that are only used as members of `type=multipolygon` relations.
+    /// Such ways usually exist only to form the multipolygon's shape, while the relation carries the tags.
+    /// Ways with any tags (except `created_by`) or used by any other relation type are still emitted.
+    /// This requires an extra pass over the input file to find the relation members.
+    #[arg(long)]
+    pub skip_multipolygon_ways: bool,
+
+    /// Do not emit ways without any tags (except `created_by`). Their nodes are still cached,
+    /// so the geometry of the relations and other ways is not affected.
+    #[arg(long)]
+    pub skip_tagless_ways: bool,
+
+    /// Do not emit relations without any tags (except `created_by`).
+    #[arg(long)]
+    pub skip_tagless_relations: bool,
+
+    /// Emit `osmm:locWarning "null_island"` for nodes located exactly at (0, 0),
+    /// which usually means that the location was lost somewhere upstream.
+    #[arg(long)]
+    pub flag_null_island: bool,
+
+    /// Emit `osmm:badWikidata` and `osmm:badWikipedia` with the original value of the `wikidata` and `wikipedia`
+    /// tags that are not valid references, e.g. `wikidata=Q` or `wikipedia=Some title` without a language.
+    /// The keys are matched by the `wikidata-ref` and `wikipedia` datatypes of `--tag-schema`.
+    /// The values are still emitted as plain strings.
+    #[arg(long)]
+    pub flag_bad_wiki: bool,
+
+    /// Emit `osmm:geometry` for ways with the line string of all way nodes, in addition to the `osmm:loc` centroid,
+    /// e.g. `"LINESTRING(2 1, 2.5 1.5)"^^geo:wktLiteral`. Ways with fewer than two nodes have no line string.
+    /// This significantly increases the output size.
+    #[arg(long)]
+    pub emit_geometry: bool,
+
+    /// Emit `osmm:nodeCount` with the number of node refs of each way, and `osmm:length` with the great-circle
+    /// length of the way in meters, e.g. `"1234.57"^^xsd:decimal`. The length is only emitted for the ways
+    /// with a computed geometry, i.e. not for the ways with an `osmm:loc:error` or too few nodes.
+    #[arg(long)]
+    pub emit_metrics: bool,
+
+    /// Minimum number of nodes a way needs to get a location and a geometry. Ways with fewer nodes get
+    /// `osmm:geometryWarning "too_few_nodes"` instead, but keep their tags. By default, the single-node ways
+    /// are located at their only node, use 2 to flag them instead. Ways without nodes always have
+    /// `osmm:loc:error "no_nodes"`.
+    #[arg(long, value_name = "n", default_value_t = 1)]
+    pub min_way_nodes: usize,
+
+    /// Emit `osmm:hasGeometry "false"` for the ways with fewer than `--min-way-nodes` nodes, and for the relations
+    /// without a `--route-geometry` or `--emit-special-points` location. This distinguishes elements without
+    /// geometry from the ones whose geometry could not be computed, which have an `osmm:loc:error` statement
+    /// instead, e.g. `"no_nodes"` for the ways without nodes.
+    #[arg(long)]
+    pub emit_has_geometry: bool,
+
+    /// Validate the `phone`, `contact:phone`, `email`, and `contact:email` tags. Valid values are also emitted
+    /// in a normalized form as `schema:telephone` and `schema:email`, e.g. `+15551234567`, and the keys
+    /// with invalid values are flagged with `osmm:contactWarning`. The raw tag values are always kept.
+    #[arg(long)]
+    pub validate_contacts: bool,
+
+    /// Write the data problems of the individual elements to `issues.json` in the output directory,
+    /// one entry per problem with the element, the issue kind, and the original value if any.
+    /// Covers the bad tag keys, invalid contacts and versions, geometry errors, and the `--check-geometry`
+    /// and `--flag-null-island` warnings. The statements in the output files are not changed.
+    #[arg(long)]
+    pub emit_issues: bool,
+
+    /// Emit `osmm:routeGeometry` for `type=route` relations, with the line strings of the member ways
+    /// in the member order. Members and nodes missing from the input are skipped, and so are the ways left
+    /// with fewer than 2 nodes, and the relation is marked with `osmm:loc:partial "true"`. The node lists of
+    /// all ways and the ids of all nodes are kept in memory during the pre-pass, so this is intended for
+    /// extracts rather than the full planet.
+    #[arg(long)]
+    pub route_geometry: bool,
+
+    /// Emit `osmm:labelLoc` and `osmm:adminCentreLoc` for relations with `label` and `admin_centre` member nodes,
+    /// using the locations of those nodes. The member nodes must appear in the input before the relation.
+    #[arg(long)]
+    pub emit_special_points: bool,
+
+    /// File with the rules choosing the datatype of each tag value by its key, one `key: datatype` per line,
+    /// e.g. `height: decimal` or `name:*: langString`. The first matching rule is used, and `*` matches any text.
+    /// Supported datatypes: string, langString, integer, decimal, boolean, date, month-day, wikidata-ref, wikipedia,
+    /// wikimedia-commons.
+    /// Values that do not fit their datatype are emitted as strings. Replaces the default rules,
+    /// see `DEFAULT_TAG_SCHEMA` in `src/tag_schema.rs`.
+    #[arg(long, value_name = "file", value_parser = TagSchema::from_file)]
+    pub tag_schema: Option<TagSchema>,
+
+    /// Silently drop the tags whose keys cannot be used as predicate names, instead of emitting `osmm:badkey`.
+    /// The dropped keys are still counted in the warnings summary.
+    #[arg(long)]
+    pub drop_bad_keys: bool,
+
+    /// Emit `osmm:tags` with all tags of the element as a JSON object, e.g. `"{\"highway\":\"primary\"}"`
+    /// typed as `rdf:JSON`, so that the original keys and values are preserved even if they could not be mapped
+    /// to predicates. The object includes the tags dropped by the other options, e.g. `created_by` or the bad keys,
+    /// but it is only emitted for the elements with at least one other tag statement,
+    /// so it does not change which elements are skipped.
+    #[arg(long)]
+    pub emit_raw_tags_json: bool,
+
+    /// Only emit the tags with these exact keys, e.g. `--only-keys name,highway,wikidata`. All other tags are
+    /// dropped without any validation. Elements without any of these keys are skipped entirely.
+    #[arg(long, value_name = "key", value_delimiter = ',')]
+    pub only_keys: Vec<String>,
+
+    #[command(flatten)]
+    pub tag_filter: TagFilter,
+
+    /// File with the elements to export, one `node/123`, `way/456`, or `relation/789` per line.
+    /// All other elements are skipped, but node locations are still cached for the way geometries.
+    /// Listed elements missing from the input are reported at the end.
+    #[arg(long, value_name = "file", value_parser = IdList::from_file)]
+    pub id_list: Option<IdList>,
+
+    /// Only emit the elements inside this box, e.g. `--bbox 2.2,48.8,2.5,48.9` for Paris. Ways are kept if any
+    /// of their nodes is inside the box, and relations if any member node or a node of a member way is inside.
+    /// Use `--bbox=-74.3,40.5,-73.7,40.9` when the box starts with a negative longitude.
+    /// The node refs of all relation member ways are kept in memory during the pre-pass.
+    #[arg(long, value_name = "min_lon,min_lat,max_lon,max_lat", value_parser = parse_bbox)]
+    pub bbox: Option<Bbox>,
+
+    /// Only emit the elements of these types, e.g. `--types node,way`. By default, all types are emitted.
+    /// Node locations are always cached, so the ways still get their geometry when nodes are excluded.
+    #[arg(long, value_enum, value_name = "type", value_delimiter = ',')]
+    pub types: Vec<Element>,
+
+    /// Order of the statement sections of each element, e.g. `--predicate-order geometry,tags,metadata`.
+    /// Sections that are not listed keep their relative order after the listed ones.
+    /// By default, statements are written in the order they are generated.
+    #[arg(long, value_enum, value_name = "section", value_delimiter = ',')]
+    pub predicate_order: Vec<Section>,
+
+    /// Emit tags whose key starts with `prefix:` under a separate namespace, with the prefix removed,
+    /// e.g. `--key-namespace addr=osmaddr` emits `addr:street` as `osmaddr:street`.
+    /// The namespace IRI is the same as `osmt:prefix:`, so the full predicate IRI does not change.
+    /// May be given multiple times; the first matching rule is used. Other keys stay under `osmt:`.
+    #[arg(long, value_name = "prefix=namespace", value_parser = parse_key_namespace)]
+    pub key_namespace: Vec<KeyNamespace>,
+
+    /// Rename the tag keys before they are emitted, e.g. `--tag-rename phone=contact:phone`.
+    /// May be given multiple times, once per key. `--only-keys`, `--include-tags`, and `--exclude-tags`
+    /// match the original keys, while the key validation, `--key-namespace`, `--tag-schema`,
+    /// and `--validate-contacts` use the new ones.
+    #[arg(long, value_name = "old=new", value_parser = parse_tag_rename)]
+    pub tag_rename: Vec<TagRename>,
+}
+
+/// Keys of the emitted tags, see `--include-tags` and `--exclude-tags`
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct TagFilter {
+    /// Only emit the tags with these keys, e.g. `--include-tags highway,name,name:*`. A trailing `*` matches
+    /// any key starting with the rest of the pattern. Unlike `--only-keys`, elements without any of these tags
+    /// are still emitted. The `created_by` tags are never emitted.
+    #[arg(long, value_name = "key", value_delimiter = ',', value_parser = parse_tag_pattern)]
+    pub include_tags: Vec<String>,
+
+    /// Do not emit the tags with these keys, e.g. `--exclude-tags note,fixme,source:*`.
+    /// Uses the same patterns as `--include-tags`, and takes precedence over it.
+    #[arg(long, value_name = "key", value_delimiter = ',', value_parser = parse_tag_pattern)]
+    pub exclude_tags: Vec<String>,
+}
+
+impl TagFilter {
+    /// Check if the tags with this key should be emitted
+    pub fn is_allowed(&self, key: &str) -> bool {
+        let is_match = |pattern: &String| match pattern.strip_suffix('*') {
+            Some(prefix) => key.starts_with(prefix),
+            None => key == pattern,
+        };
+        !self.exclude_tags.iter().any(is_match)
+            && (self.include_tags.is_empty() || self.include_tags.iter().any(is_match))
+    }
+}
+
+/// Rule to emit tags with a given key prefix under a separate RDF namespace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyNamespace {
+    /// Key prefix without the trailing `:`, e.g. `addr`
+    pub key_prefix: String,
+    /// Turtle prefix name to use for the matching keys, e.g. `osmaddr`
+    pub namespace: String,
+}
+
+impl KeyNamespace {
+    /// Turtle prefix declaration for this namespace, without the leading `@` and trailing `.`
+    pub fn declaration(&self) -> String {
+        format!(
+            "prefix {}: <https://wiki.openstreetmap.org/wiki/Key:{}:>",
+            self.namespace, self.key_prefix
+        )
+    }
+}
+
+/// Rule to emit a tag under a different key, see `--tag-rename`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagRename {
+    /// Key in the input data, e.g. `phone`
+    pub from: String,
+    /// Key to emit instead, e.g. `contact:phone`
+    pub to: String,
+}
+
+/// Prefix declarations read from a `--prefixes` file, added to the built-in ones
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrefixFile {
+    /// Prefix name and IRI pairs, e.g. `osmnode` and `https://example.org/node/`
+    pub prefixes: Vec<(String, String)>,
+}
+
+impl PrefixFile {
+    /// Parse lines like `prefix foo: <https://example.org/foo/>`, optionally in the `@prefix foo: <...> .` form.
+    /// Empty lines and `#` comments are ignored.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut prefixes: Vec<(String, String)> = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let declaration = match line.strip_prefix('@') {
+                Some(v) => v.strip_suffix('.').map(str::trim_end),
+                None => Some(line),
+            };
+            let Some((name, iri)) = declaration
+                .and_then(|v| v.strip_prefix("prefix "))
+                .and_then(|v| v.split_once(':'))
+                .map(|(name, iri)| (name.trim(), iri.trim()))
+            else {
+                bail!(
+                    "Line {}: expected `prefix name: <iri>`, got `{line}`",
+                    idx + 1
+                );
+            };
+            if !RE_PREFIX_NAME.is_match(name) {
+                bail!("Line {}: `{name}` is not a valid prefix name", idx + 1);
+            }
+            let Some(iri) = iri
+                .strip_prefix('<')
+                .and_then(|v| v.strip_suffix('>'))
+                .filter(|v| {
+                    !v.is_empty()
+                        && !v.contains(|c: char| c.is_whitespace() || c == '<' || c == '>')
+                })
+            else {
+                bail!("Line {}: `{iri}` is not an IRI in angle brackets", idx + 1);
+            };
+            if prefixes.iter().any(|(v, _)| v == name) {
+                bail!(
+                    "Line {}: prefix `{name}` is declared more than once",
+                    idx + 1
+                );
+            }
+            prefixes.push((name.to_string(), iri.to_string()));
+        }
+        Ok(Self { prefixes })
+    }
+
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read prefixes {path}"))?;
+        Self::parse(&text).with_context(|| format!("Invalid prefixes {path}"))
+    }
+
+    /// Replace the declarations of the same prefix names, and append the new ones.
+    /// The declarations are in the `prefix name: <iri>` form.
+    pub fn merge(&self, mut declarations: Vec<String>) -> Vec<String> {
+        for (name, iri) in &self.prefixes {
+            let declaration = format!("prefix {name}: <{iri}>");
+            let existing = declarations.iter_mut().find(|v| {
+                v.strip_prefix("prefix ")
+                    .and_then(|v| v.split_once(':'))
+                    .is_some_and(|(v, _)| v == name)
+            });
+            match existing {
+                Some(v) => *v = declaration,
+                None => declarations.push(declaration),
+            }
+        }
+        declarations
+    }
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SchemaFormat {
+    #[default]
+    Json,
+    Turtle,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Turtle,
+    #[value(name = "ntriples")]
+    NTriples,
+    #[value(name = "jsonld")]
+    JsonLd,
+    Protobuf,
+}
+
+impl OutputFormat {
+    /// File extension, without the compression suffix
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Turtle => "ttl",
+            OutputFormat::NTriples => "nt",
+            OutputFormat::JsonLd => "jsonld",
+            OutputFormat::Protobuf => "pb",
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputCompression {
+    #[default]
+    Gzip,
+    /// Zstandard at the default level 3
+    Zstd,
+    None,
+}
+
+impl OutputCompression {
+    /// Suffix appended to the output file extension, e.g. `.gz`
+    pub fn suffix(self) -> &'static str {
+        match self {
+            OutputCompression::Gzip => ".gz",
+            OutputCompression::Zstd => ".zst",
+            OutputCompression::None => "",
+        }
+    }
+}
+
+/// Deflate strategy of the gzip output, see `--deflate-strategy`
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DeflateStrategy {
+    /// Regular LZ77 matching, as used by `gzip`
+    #[default]
+    Default,
+    /// Only use matches of at least 5 bytes, and encode shorter repetitions as literals
+    Filtered,
+    /// Only match the repetitions of the previous byte, i.e. run-length encoding of the runs of newlines
+    /// and spaces in the Turtle output. Repeated predicates are not matched, so the files are much larger.
+    Rle,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Crs {
+    /// WGS 84, the GeoSPARQL default CRS
+    #[default]
+    #[value(name = "4326")]
+    Wgs84,
+    /// Web Mercator, EPSG:3857
+    #[value(name = "3857")]
+    WebMercator,
+    /// WGS 84 with the CRS declared in each literal, selected by `--wkt-crs-uri`
+    #[value(skip)]
+    Crs84,
+}
+
+impl Default for ParserOpts {
+    /// Same options as the `parse` command without any flags, e.g. `min_way_nodes` is 2 rather than 0
+    fn default() -> Self {
+        let cmd = Self::augment_args(clap::Command::new("parse"));
+        Self::from_arg_matches(&cmd.get_matches_from(["parse"])).unwrap()
+    }
+}
+
+impl ParserOpts {
+    /// Whether to emit the `osmm:type` literal, see `--type-style`
+    pub fn emit_type_literal(&self) -> bool {
+        self.type_style != TypeStyle::Class && !self.no_type_predicate
+    }
+
+    /// Whether to emit the `rdf:type` class, see `--type-style`
+    pub fn emit_type_class(&self) -> bool {
+        self.type_style != TypeStyle::Short || self.emit_rdf_type
+    }
+
+    /// CRS of the emitted WKT literals, which also determines the CRS declaration of each literal
+    pub fn wkt_crs(&self) -> Crs {
+        match self.crs {
+            Crs::Wgs84 if self.wkt_crs_uri => Crs::Crs84,
+            crs => crs,
+        }
+    }
+}
+
+/// Group of related statements of an element
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    /// Tag values, including the `osmm:badkey` statements
+    Tags,
+    /// Location, closedness, and geometry warnings
+    Geometry,
+    /// Relation members and their roles
+    Members,
+    /// Element type, version, user, timestamp, changeset, and other statistics
+    Metadata,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DateSource {
+    #[default]
+    Elements,
+    Header,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+    #[default]
+    Iso,
+    EpochMillis,
+    EpochSeconds,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TypeStyle {
+    #[default]
+    Short,
+    Class,
+    Both,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IsClosedFor {
+    #[default]
+    All,
+    Areas,
+    None,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CentroidMode {
+    #[default]
+    Planar,
+    Geodesic,
+}
+
 // enum Foo {
 //     /// Host URL to upload data. Default: %(default)s
 //     #[arg(
@@ -80,6 +916,95 @@ pub enum Command {
 //     host: String,
 // }
 
+fn parse_timestamp(value: &str) -> anyhow::Result<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
+    match DateTime::parse_from_rfc3339(value) {
+        Ok(ts) => Ok(ts.with_timezone(&Utc)),
+        Err(_) => bail!("Timestamp `{value}` must be either YYYY-MM-DD or in the RFC 3339 format"),
+    }
+}
+
+fn parse_tag_pattern(value: &str) -> anyhow::Result<String> {
+    if value.is_empty() || value.trim_end_matches('*').contains('*') || value.ends_with("**") {
+        bail!("Tag key pattern `{value}` must be a key, optionally ending with a single `*`")
+    }
+    Ok(value.to_string())
+}
+
+fn parse_key_namespace(value: &str) -> anyhow::Result<KeyNamespace> {
+    let Some((key_prefix, namespace)) = value.split_once('=') else {
+        bail!("Key namespace `{value}` must be in the `prefix=namespace` form, e.g. `addr=osmaddr`")
+    };
+    if !str_builder::RE_SIMPLE_LOCAL_NAME.is_match(key_prefix) {
+        bail!("Key prefix `{key_prefix}` is not a valid tag key")
+    }
+    if !RE_PREFIX_NAME.is_match(namespace) {
+        bail!("Namespace `{namespace}` is not a valid prefix name")
+    }
+    if parser::is_known_prefix(namespace) {
+        bail!("Namespace `{namespace}` is already used by osm2rdf")
+    }
+    Ok(KeyNamespace {
+        key_prefix: key_prefix.to_string(),
+        namespace: namespace.to_string(),
+    })
+}
+
+fn parse_tag_rename(value: &str) -> anyhow::Result<TagRename> {
+    let Some((from, to)) = value.split_once('=') else {
+        bail!("Tag rename `{value}` must be in the `old=new` form, e.g. `phone=contact:phone`")
+    };
+    if from.is_empty() {
+        bail!("Tag rename `{value}` has an empty key to rename")
+    }
+    if !str_builder::RE_SIMPLE_LOCAL_NAME.is_match(to) {
+        bail!("New key `{to}` is not a valid tag key")
+    }
+    Ok(TagRename {
+        from: from.to_string(),
+        to: to.to_string(),
+    })
+}
+
+fn parse_bbox(value: &str) -> anyhow::Result<Bbox> {
+    let values = value
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>();
+    let Ok([min_lon, min_lat, max_lon, max_lat]) = values.as_deref() else {
+        bail!("Bounding box `{value}` must be four numbers `min_lon,min_lat,max_lon,max_lat`")
+    };
+    let bbox = Bbox {
+        min_lat: *min_lat,
+        min_lon: *min_lon,
+        max_lat: *max_lat,
+        max_lon: *max_lon,
+    };
+    if !(-90.0..=90.0).contains(&bbox.min_lat) || !(-90.0..=90.0).contains(&bbox.max_lat) {
+        bail!("Bounding box latitudes must be between -90 and 90")
+    }
+    if !(-180.0..=180.0).contains(&bbox.min_lon) || !(-180.0..=180.0).contains(&bbox.max_lon) {
+        bail!("Bounding box longitudes must be between -180 and 180")
+    }
+    if bbox.min_lat > bbox.max_lat || bbox.min_lon > bbox.max_lon {
+        bail!("Bounding box `{value}` must have the minimum values before the maximum ones")
+    }
+    Ok(bbox)
+}
+
+fn parse_base_url(value: &str) -> anyhow::Result<String> {
+    let url = value.trim_end_matches('/');
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        bail!("OSM base URL `{value}` must start with http:// or https://")
+    }
+    if url.contains(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"')) {
+        bail!("OSM base URL `{value}` is not a valid IRI")
+    }
+    Ok(url.to_string())
+}
+
 fn parse_outdir(path_str: &str) -> anyhow::Result<PathBuf> {
     let path = PathBuf::from(path_str);
     if !path.is_dir() {
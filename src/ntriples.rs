@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::turtle::{parse_declarations, parse_statements, split_name, Converter, Object, Term};
+
+const RDF_TYPE: &str = "<http://www.w3.org/1999/02/22-rdf-syntax-ns#type>";
+
+/// Converts the Turtle statements generated for each element into N-Triples, see `--format ntriples`.
+/// Every prefixed name is expanded into a full IRI, so each line is a complete triple.
+pub struct NTriples {
+    /// Prefix name to IRI, e.g. `osmnode` to `https://www.openstreetmap.org/node/`
+    prefixes: HashMap<String, String>,
+}
+
+impl NTriples {
+    /// Create from the prefix declarations in the `prefix name: <iri>` form
+    pub fn new<T: AsRef<str>>(declarations: impl IntoIterator<Item = T>) -> Self {
+        let prefixes = parse_declarations(declarations).into_iter().collect();
+        Self { prefixes }
+    }
+
+    fn convert(&self, subject: &str, statements: &str) -> Result<String, String> {
+        // Blank node labels only need to be unique within the output file, so they are based on the subject
+        let label: String = subject
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let mut blank_nodes = 0;
+        let mut result = String::new();
+        let subject = self.expand(subject)?;
+        let statements = parse_statements(statements)?;
+        self.convert_statements(&subject, &statements, &label, &mut blank_nodes, &mut result)?;
+        Ok(result)
+    }
+
+    /// Write the triples of a subject, followed by the triples of its blank node objects
+    fn convert_statements(
+        &self,
+        subject: &str,
+        statements: &[(Term, Object)],
+        label: &str,
+        blank_nodes: &mut usize,
+        result: &mut String,
+    ) -> Result<(), String> {
+        for (predicate, object) in statements {
+            let predicate = self.term(*predicate)?;
+            match object {
+                Object::Term(term) => {
+                    let term = self.term(*term)?;
+                    result.push_str(&format!("{subject} {predicate} {term} .\n"));
+                }
+                Object::BlankNode(node_statements) => {
+                    let node = format!("_:{label}_{blank_nodes}");
+                    *blank_nodes += 1;
+                    result.push_str(&format!("{subject} {predicate} {node} .\n"));
+                    self.convert_statements(&node, node_statements, label, blank_nodes, result)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Format a term in the N-Triples form, expanding the prefixed names
+    fn term(&self, term: Term) -> Result<String, String> {
+        Ok(match term {
+            Term::Iri(iri) => format!("<{iri}>"),
+            Term::Name(name) => self.expand(name)?,
+            Term::Type => RDF_TYPE.to_string(),
+            Term::Literal {
+                text,
+                datatype: Some(datatype),
+                ..
+            } => match datatype.strip_prefix('<') {
+                Some(_) => format!("{text}^^{datatype}"),
+                None => format!("{text}^^{}", self.expand(datatype)?),
+            },
+            Term::Literal {
+                text,
+                lang: Some(lang),
+                ..
+            } => format!("{text}@{lang}"),
+            Term::Literal { text, .. } => text.to_string(),
+        })
+    }
+
+    /// Expand a prefixed name like `osmnode:123` into `<https://www.openstreetmap.org/node/123>`
+    fn expand(&self, name: &str) -> Result<String, String> {
+        let (prefix, local) = split_name(name)?;
+        let iri = self
+            .prefixes
+            .get(prefix)
+            .ok_or_else(|| format!("unknown prefix `{prefix}`"))?;
+        Ok(format!("<{iri}{local}>"))
+    }
+}
+
+impl Converter for NTriples {
+    fn write_element(
+        &self,
+        out: &mut dyn Write,
+        subject: &str,
+        statements: &str,
+    ) -> io::Result<usize> {
+        let triples = self
+            .convert(subject, statements)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        out.write_all(triples.as_bytes())?;
+        Ok(triples.len())
+    }
+}
@@ -1,43 +1,89 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsStr;
 use std::fs::File;
-use std::io::{Read, Write as _};
+use std::io::{BufWriter, Seek as _, Write};
+use std::iter;
 use std::mem;
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicI64, AtomicU32, Ordering};
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU32, Ordering};
+use std::sync::mpsc::{channel, sync_channel, Receiver, RecvTimeoutError, SyncSender};
 use std::sync::Mutex;
-use std::thread::{Builder, JoinHandle};
+use std::thread::{self, Builder, JoinHandle};
+use std::time::{Duration, Instant};
 
+use anyhow::{bail, Context as _};
 use bytesize::ByteSize;
+use chrono::{DateTime, TimeZone as _, Utc};
+use clap::ValueEnum as _;
 use flate2::write::GzEncoder;
 use flate2::Compression;
-use geo::{Centroid, LineString};
-use log::{info, warn};
+use geo::{HaversineLength as _, LineString, Polygon};
+use log::{debug, info, warn};
 use osmnodecache::{Cache, CacheStore, DenseFileCache, DenseFileCacheOpts, HashMapCache};
-use osmpbf::{Blob, BlobDecode, BlobReader, DenseNode, Node, PrimitiveBlock, Relation, Way};
+use osmpbf::{Blob, BlobDecode, DenseNode, Node, PrimitiveBlock, RelMemberType, Relation, Way};
 use path_absolutize::Absolutize as _;
+use prost::Message as _;
 use rayon::iter::{ParallelBridge as _, ParallelIterator as _};
 
+use crate::geometry::{centroid, geohash, is_within_bbox, polygon_centroid};
+use crate::gzip::GzStrategyEncoder;
+use crate::input::{is_stdin, PbfReader};
+use crate::jsonld::{JsonLd, CONTEXT_FILE};
+use crate::ntriples::NTriples;
+use crate::progress::Progress;
+use crate::sparql::Endpoints;
 use crate::str_builder::{
-    StringBuf, XsdBoolean, XsdDateTime, XsdElement, XsdPoint, XsdRelMember, XsdStr,
+    StringBuf, XsdBbox, XsdBoolean, XsdDateTime, XsdElement, XsdInteger, XsdLineCollection,
+    XsdLineString, XsdMember, XsdPoint, XsdRelMember, XsdStr, XsdTyped,
 };
-use crate::utils::{Element, ElementInfo, Stats};
-use crate::{Args, Command};
+use crate::turtle::Converter;
+use crate::utils::{Bbox, Element, ElementInfo, Issue, IssueKind, Stats, Warning, Warnings};
+use crate::{proto, vocab};
+use crate::{
+    Args, Command, Crs, DateSource, DeflateStrategy, IsClosedFor, KeyNamespace, OutputCompression,
+    OutputFormat, ParserOpts, PrefixFile, Section, TimestampFormat,
+};
+
+/// Set by the first Ctrl-C, see `handle_interrupts`
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// How often to log the size of the in-memory node cache
+const CACHE_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Approximate memory used by one node location in the in-memory cache, including the hash map overhead
+const CACHE_ENTRY_SIZE: u64 = 24;
 
+/// Default base of the element IRIs, see `--osm-base-url`
+pub const OSM_BASE_URL: &str = "https://www.openstreetmap.org";
+
+/// Prefix declarations written at the start of each output file, with the element IRIs under `base_url`
 //noinspection HttpUrlsUsage
-static PREFIXES: &[&str] = &[
-    // Wikidata
-    "prefix wd: <http://www.wikidata.org/entity/>",
-    "prefix xsd: <http://www.w3.org/2001/XMLSchema#>",
-    "prefix geo: <http://www.opengis.net/ont/geosparql#>",
-    "prefix schema: <http://schema.org/>",
-    // OSM
-    "prefix osmroot: <https://www.openstreetmap.org>",
-    "prefix osmnode: <https://www.openstreetmap.org/node/>",
-    "prefix osmway: <https://www.openstreetmap.org/way/>",
-    "prefix osmrel: <https://www.openstreetmap.org/relation/>",
-    "prefix osmt: <https://wiki.openstreetmap.org/wiki/Key:>",
-    "prefix osmm: <https://www.openstreetmap.org/meta/>",
-];
+pub(crate) fn prefixes(base_url: &str) -> Vec<String> {
+    vec![
+        // Wikidata
+        "prefix wd: <http://www.wikidata.org/entity/>".to_string(),
+        "prefix xsd: <http://www.w3.org/2001/XMLSchema#>".to_string(),
+        "prefix geo: <http://www.opengis.net/ont/geosparql#>".to_string(),
+        "prefix schema: <http://schema.org/>".to_string(),
+        // OSM
+        format!("prefix osmroot: <{base_url}>"),
+        format!("prefix osmnode: <{base_url}/node/>"),
+        format!("prefix osmway: <{base_url}/way/>"),
+        format!("prefix osmrel: <{base_url}/relation/>"),
+        "prefix osmt: <https://wiki.openstreetmap.org/wiki/Key:>".to_string(),
+        "prefix osmm: <https://www.openstreetmap.org/meta/>".to_string(),
+    ]
+}
+
+/// Check if the prefix name is already declared in the output files
+pub fn is_known_prefix(name: &str) -> bool {
+    prefixes(OSM_BASE_URL).iter().any(|v| {
+        v.strip_prefix("prefix ")
+            .and_then(|v| v.strip_prefix(name))
+            .is_some_and(|v| v.starts_with(':'))
+    })
+}
 
 #[derive(Debug)]
 pub enum Statement {
@@ -52,6 +98,194 @@ pub enum Statement {
         ts: i64,
         val: StringBuf,
     },
+    /// Binary-encoded element for the non-text output formats
+    Encoded {
+        elem: Element,
+        id: i64,
+        ts: i64,
+        val: Vec<u8>,
+    },
+    /// All previous statements must be written before saving the progress of the run, see `--resume`
+    Checkpoint(ResumeState),
+}
+
+impl Statement {
+    /// Element type and id used to sort the output, see `--sorted-output`
+    fn sort_key(&self) -> Option<(Element, i64)> {
+        match self {
+            Statement::Skip | Statement::Checkpoint(_) => None,
+            Statement::Delete { elem, id }
+            | Statement::Create { elem, id, .. }
+            | Statement::Encoded { elem, id, .. } => Some((*elem, *id)),
+        }
+    }
+
+    /// Write the element as it appears in the output files, and return the size of its value.
+    /// Skipped and deleted elements, and checkpoints, are not written.
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<usize> {
+        match self {
+            Statement::Create { elem, id, val, .. } => {
+                write!(writer, "\n{elem}:{id}\n{val}")?;
+                Ok(val.len())
+            }
+            Statement::Encoded { val, .. } => {
+                writer.write_all(val)?;
+                Ok(val.len())
+            }
+            Statement::Skip | Statement::Delete { .. } | Statement::Checkpoint(_) => Ok(0),
+        }
+    }
+
+    /// Write the element as a SPARQL Update request that replaces all statements of the subject,
+    /// see `--upsert`. Other statements are written the same way as `write_to`.
+    pub fn write_upsert_to(&self, writer: &mut impl Write) -> std::io::Result<usize> {
+        let Statement::Create { elem, id, .. } = self else {
+            return self.write_to(writer);
+        };
+        write!(
+            writer,
+            "\nDELETE WHERE {{ {elem}:{id} ?p ?o }};\nINSERT DATA {{"
+        )?;
+        let len = self.write_to(writer)?;
+        writeln!(writer, "}};")?;
+        Ok(len)
+    }
+}
+
+/// Data collected by a separate pass over all input files before any statements are generated.
+/// Only the data required by the enabled options is collected.
+#[derive(Debug, Default)]
+pub struct PrePass {
+    /// Number of ways referencing each node, used by `--emit-membership-count`
+    pub way_refs: HashMap<i64, u32>,
+    /// Ways used by relations, set to `true` if all of them are multipolygons. Used by `--skip-multipolygon-ways`
+    pub multipolygon_ways: HashMap<i64, bool>,
+    /// Node refs of the ways, used by `--route-geometry` and `--bbox`. Only the members of route relations,
+    /// or of all relations with `--bbox`, are kept once the pre-pass is done.
+    pub route_ways: HashMap<i64, Vec<i64>>,
+    /// Ways used by route relations, or by all relations with `--bbox`
+    route_members: HashSet<i64>,
+    /// Nodes with a cached location, used by `--route-geometry` to find the missing ones.
+    /// Only the nodes of the kept `route_ways` are kept once the pre-pass is done.
+    pub route_nodes: HashSet<i64>,
+    /// Members of all relations, used by `--validate-members`.
+    /// Only the members present in the input are kept once the pre-pass is done.
+    pub members: HashSet<(Element, i64)>,
+    /// All elements of the input, used to find the members present in the input
+    elements: HashSet<(Element, i64)>,
+}
+
+impl PrePass {
+    pub fn is_needed(opts: &ParserOpts) -> bool {
+        opts.emit_membership_count
+            || opts.skip_multipolygon_ways
+            || opts.route_geometry
+            || opts.bbox.is_some()
+            || opts.validate_members
+    }
+
+    /// Check if the way is only used to form the geometry of multipolygon relations
+    pub fn is_multipolygon_only(&self, way_id: i64) -> bool {
+        self.multipolygon_ways.get(&way_id) == Some(&true)
+    }
+
+    fn from_blob(
+        opts: &ParserOpts,
+        blob: Result<Blob, osmpbf::Error>,
+    ) -> Result<Self, osmpbf::Error> {
+        let mut pre_pass = Self::default();
+        if let BlobDecode::OsmData(block) = blob?.decode()? {
+            for group in block.groups() {
+                if opts.emit_membership_count {
+                    for way in group.ways() {
+                        for id in way.refs() {
+                            *pre_pass.way_refs.entry(id).or_default() += 1;
+                        }
+                    }
+                }
+                if opts.skip_multipolygon_ways {
+                    for rel in group.relations() {
+                        let is_mp = rel.tags().any(|v| v == ("type", "multipolygon"));
+                        for mbr in rel.members() {
+                            if mbr.member_type == RelMemberType::Way {
+                                pre_pass.add_way_relation(mbr.member_id, is_mp);
+                            }
+                        }
+                    }
+                }
+                if opts.route_geometry {
+                    // Deleted nodes are not cached, see `cache_node`
+                    let nodes = group.nodes().filter(|v| !v.info().deleted());
+                    pre_pass.route_nodes.extend(nodes.map(|v| v.id()));
+                    let nodes = group
+                        .dense_nodes()
+                        .filter(|v| !v.info().is_some_and(|i| i.deleted()));
+                    pre_pass.route_nodes.extend(nodes.map(|v| v.id()));
+                }
+                if opts.route_geometry || opts.bbox.is_some() {
+                    for way in group.ways() {
+                        pre_pass.route_ways.insert(way.id(), way.refs().collect());
+                    }
+                    for rel in group.relations() {
+                        if opts.bbox.is_some() || rel.tags().any(|v| v == ("type", "route")) {
+                            let ways = rel
+                                .members()
+                                .filter(|v| v.member_type == RelMemberType::Way);
+                            pre_pass.route_members.extend(ways.map(|v| v.member_id));
+                        }
+                    }
+                }
+                if opts.validate_members {
+                    let elements = &mut pre_pass.elements;
+                    elements.extend(group.nodes().map(|v| (Element::Node, v.id())));
+                    elements.extend(group.dense_nodes().map(|v| (Element::Node, v.id())));
+                    elements.extend(group.ways().map(|v| (Element::Way, v.id())));
+                    elements.extend(group.relations().map(|v| (Element::Relation, v.id())));
+                    for rel in group.relations() {
+                        let members = rel
+                            .members()
+                            .map(|v| ((&v.member_type).into(), v.member_id));
+                        pre_pass.members.extend(members);
+                    }
+                }
+            }
+        }
+        Ok(pre_pass)
+    }
+
+    /// Drop the node refs of the ways that are not used by any route relation, and the nodes not used by the rest
+    fn retain_route_ways(&mut self) {
+        let members = mem::take(&mut self.route_members);
+        self.route_ways.retain(|id, _| members.contains(id));
+        let refs: HashSet<_> = self.route_ways.values().flatten().collect();
+        self.route_nodes.retain(|id| refs.contains(id));
+    }
+
+    /// Drop the relation members that are not present in the input
+    fn retain_present_members(&mut self) {
+        let elements = mem::take(&mut self.elements);
+        self.members.retain(|v| elements.contains(v));
+    }
+
+    fn add_way_relation(&mut self, way_id: i64, is_multipolygon: bool) {
+        let only_mp = self.multipolygon_ways.entry(way_id).or_insert(true);
+        *only_mp &= is_multipolygon;
+    }
+
+    fn combine(mut self, other: Self) -> Self {
+        for (id, count) in other.way_refs {
+            *self.way_refs.entry(id).or_default() += count;
+        }
+        for (id, is_multipolygon) in other.multipolygon_ways {
+            self.add_way_relation(id, is_multipolygon);
+        }
+        self.route_ways.extend(other.route_ways);
+        self.route_members.extend(other.route_members);
+        self.route_nodes.extend(other.route_nodes);
+        self.members.extend(other.members);
+        self.elements.extend(other.elements);
+        self
+    }
 }
 
 pub struct Parser<'a> {
@@ -59,11 +293,25 @@ pub struct Parser<'a> {
     stats: Stats,
     cache: Box<dyn Cache + 'a>,
     batch_size: usize,
+    opts: &'a ParserOpts,
+    pre_pass: &'a PrePass,
+    /// Element types that produce output, see `--types`
+    types: HashSet<Element>,
+    /// False when the node locations were already cached by the first pass of `--two-pass`
+    cache_locations: bool,
 }
 
 impl<'a> Drop for Parser<'a> {
     fn drop(&mut self) {
         let stats = mem::take(&mut self.stats);
+        debug!(
+            "{}: processed {} elements, added {} nodes, {} ways, {} relations",
+            thread::current().name().unwrap_or("main"),
+            stats.elements(),
+            stats.added_nodes,
+            stats.added_ways,
+            stats.added_rels,
+        );
         self.parent_stats.lock().unwrap().combine(stats);
     }
 }
@@ -73,38 +321,75 @@ impl<'a> Parser<'a> {
         parent_stats: &'a Mutex<Stats>,
         cache: Box<dyn 'a + Cache>,
         batch_size: usize,
+        opts: &'a ParserOpts,
+        pre_pass: &'a PrePass,
     ) -> Parser<'a> {
         Parser {
             parent_stats,
             stats: Stats::default(),
             cache,
             batch_size,
+            opts,
+            pre_pass,
+            types: if opts.types.is_empty() {
+                HashSet::from([Element::Node, Element::Way, Element::Relation])
+            } else {
+                opts.types.iter().copied().collect()
+            },
+            cache_locations: true,
         }
     }
 
     pub fn parse_block(&mut self, block: PrimitiveBlock, mut writer: impl FnMut(Vec<Statement>)) {
         let batch_size = self.batch_size;
         let mut result: Vec<Statement> = Vec::with_capacity(batch_size);
+        // Skipped elements are not written, so only the other ones count toward the batch size
+        let mut elements = 0;
         let mut enqueue = |s: Statement| {
+            if !matches!(s, Statement::Skip) {
+                elements += 1;
+            }
             result.push(s);
-            if result.len() > batch_size {
+            if elements >= batch_size {
                 writer(mem::replace(&mut result, Vec::with_capacity(batch_size)));
+                elements = 0;
             }
         };
 
+        let with_nodes = self.types.contains(&Element::Node);
+        let with_ways = self.types.contains(&Element::Way);
+        let with_rels = self.types.contains(&Element::Relation);
+
         for group in block.groups() {
-            // FIXME: possible concurrency bug: a non-node element may need coords of a node that hasn't been processed yet
+            // Without `--two-pass`, a way may need the coords of a node in a blob that hasn't been processed yet
             for node in group.nodes() {
-                enqueue(self.on_node(&node));
+                if with_nodes {
+                    enqueue(self.on_node(&node));
+                } else {
+                    self.cache_node(node.info().into(), node.id(), node.lat(), node.lon());
+                }
             }
             for node in group.dense_nodes() {
-                enqueue(self.on_dense_node(&node));
+                if with_nodes {
+                    enqueue(self.on_dense_node(&node));
+                } else {
+                    let info = node.info().map_or_else(ElementInfo::default, Into::into);
+                    self.cache_node(info, node.id(), node.lat(), node.lon());
+                }
             }
             for way in group.ways() {
-                enqueue(self.on_way(&way));
+                if with_ways {
+                    enqueue(self.on_way(&way));
+                } else {
+                    self.stats.skipped_types += 1;
+                }
             }
             for rel in group.relations() {
-                enqueue(self.on_relation(&rel));
+                if with_rels {
+                    enqueue(self.on_relation(&rel));
+                } else {
+                    self.stats.skipped_types += 1;
+                }
             }
         }
 
@@ -113,13 +398,53 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Only cache the locations of the nodes, without generating any statements, see `--two-pass`
+    pub fn cache_block(&mut self, block: PrimitiveBlock) {
+        for group in block.groups() {
+            for node in group.nodes() {
+                let info: ElementInfo<'_> = node.info().into();
+                if !info.is_deleted {
+                    self.cache_location(node.id(), node.lat(), node.lon());
+                }
+            }
+            for node in group.dense_nodes() {
+                let info: ElementInfo<'_> =
+                    node.info().map_or_else(ElementInfo::default, Into::into);
+                if !info.is_deleted {
+                    self.cache_location(node.id(), node.lat(), node.lon());
+                }
+            }
+        }
+    }
+
+    /// Only cache the location of a node excluded by `--types`, so that the ways can still use it
+    fn cache_node(&mut self, info: ElementInfo<'_>, id: i64, lat: f64, lon: f64) {
+        self.stats.skipped_types += 1;
+        if !info.is_deleted {
+            self.cache_location(id, lat, lon);
+        }
+    }
+
+    fn cache_location(&mut self, id: i64, lat: f64, lon: f64) {
+        // Negative ids would wrap around into huge cache indexes, see `is_negative`
+        if id >= 0 && self.cache_locations {
+            self.cache.set_lat_lon(id as usize, lat, lon);
+            self.stats.cached_nodes += 1;
+        }
+    }
+
+    /// Get the cached location of a node, or `None` for the negative ids that are never cached
+    fn node_location(&self, id: i64) -> Option<(f64, f64)> {
+        (id >= 0).then(|| self.cache.get_lat_lon(id as usize))
+    }
+
     fn on_node(&mut self, node: &Node) -> Statement {
         let info = node.info().into();
         self.process_node(info, node.id(), node.tags(), node.lat(), node.lon())
     }
 
     fn on_dense_node(&mut self, node: &DenseNode) -> Statement {
-        let info = node.info().unwrap().into();
+        let info = node.info().map_or_else(ElementInfo::default, Into::into);
         self.process_node(info, node.id(), node.tags(), node.lat(), node.lon())
     }
 
@@ -131,6 +456,15 @@ impl<'a> Parser<'a> {
         lat: f64,
         lon: f64,
     ) -> Statement {
+        if self.is_negative(Element::Node, id) {
+            return Statement::Skip;
+        }
+        if self.is_unlisted(Element::Node, id) {
+            if !info.is_deleted {
+                self.cache_location(id, lat, lon);
+            }
+            return Statement::Skip;
+        }
         if info.is_deleted {
             self.stats.deleted_nodes += 1;
             Statement::Delete {
@@ -138,27 +472,63 @@ impl<'a> Parser<'a> {
                 id,
             }
         } else {
-            self.cache.set_lat_lon(id as usize, lat, lon);
+            self.cache_location(id, lat, lon);
+            if self.is_too_old(&info) || self.is_outside_bbox(|_, bbox| bbox.contains(lat, lon)) {
+                return Statement::Skip;
+            }
+            if self.opts.format == OutputFormat::Protobuf {
+                return self.encode_node(&info, id, tags, lat, lon);
+            }
             let mut value = StringBuf::default();
-            value.add_tags(tags);
-            if value.is_empty() {
+            value.begin_subject(Element::Node, id);
+            let bad_keys = value.add_tags(tags, self.opts);
+            self.stats.warnings.add(Warning::BadKey, bad_keys);
+            self.add_tag_issues(Element::Node, id, &mut value);
+            let way_refs = self.pre_pass.way_refs.get(&id).copied().unwrap_or_default();
+            // Untagged nodes are only emitted if they are shared by multiple ways, e.g. intersections
+            if value.is_empty() && (way_refs < 2 || self.is_filtered_by_keys()) {
                 self.stats.skipped_nodes += 1;
                 Statement::Skip
             } else {
-                value.add_value("osmm:loc", XsdPoint { lat, lon });
-                value.add_value("osmm:type", XsdElement(Element::Node));
+                value.start_section(Section::Geometry);
+                let precision = self.opts.coord_precision;
+                let point = XsdPoint {
+                    lat,
+                    lon,
+                    precision,
+                    crs: self.opts.wkt_crs(),
+                };
+                value.add_value(vocab::LOC, point);
+                self.add_geohash(&mut value, lat, lon);
+                self.stats.add_point(lat, lon);
+                // Only the exact origin is flagged, as it is usually a missing location upstream
+                if self.opts.flag_null_island && lat == 0.0 && lon == 0.0 {
+                    value.add_value(vocab::LOC_WARNING, XsdStr("null_island"));
+                    self.stats.warnings.add(Warning::NullIsland, 1);
+                    self.add_issue(Element::Node, id, IssueKind::NullIsland, None);
+                }
+                self.add_type(&mut value, Element::Node);
+                self.add_rdf_type(&mut value, vocab::NODE);
+                if way_refs > 0 {
+                    let count = XsdInteger(i64::from(way_refs));
+                    value.add_value(vocab::WAY_MEMBERSHIP_COUNT, count);
+                }
+                self.check_version(Element::Node, id, &info);
                 self.stats.added_nodes += 1;
                 Statement::Create {
                     elem: Element::Node,
                     id,
-                    ts: info.milli_timestamp,
-                    val: value.finalize(info),
+                    ts: info.milli_timestamp.unwrap_or_default(),
+                    val: value.finalize(info, self.opts),
                 }
             }
         }
     }
 
     fn on_way(&mut self, way: &Way) -> Statement {
+        if self.is_negative(Element::Way, way.id()) || self.is_unlisted(Element::Way, way.id()) {
+            return Statement::Skip;
+        }
         let info: ElementInfo = way.info().into();
         if info.is_deleted {
             self.stats.deleted_ways += 1;
@@ -167,23 +537,51 @@ impl<'a> Parser<'a> {
                 id: way.id(),
             };
         }
+        if self.is_too_old(&info)
+            || self.is_tagless(self.opts.skip_tagless_ways, way.tags())
+            || self.is_outside_bbox(|p, bbox| p.has_node_inside(bbox, way.refs()))
+        {
+            return Statement::Skip;
+        }
+        if self.opts.format == OutputFormat::Protobuf {
+            return self.encode_way(&info, way);
+        }
         let mut value = StringBuf::default();
-        value.add_tags(way.tags());
-        value.add_value("osmm:type", XsdElement(Element::Way));
+        value.begin_subject(Element::Way, way.id());
+        let bad_keys = value.add_tags(way.tags(), self.opts);
+        self.stats.warnings.add(Warning::BadKey, bad_keys);
+        self.add_tag_issues(Element::Way, way.id(), &mut value);
+        if value.is_empty()
+            && (self.pre_pass.is_multipolygon_only(way.id()) || self.is_filtered_by_keys())
+        {
+            self.stats.skipped_ways += 1;
+            return Statement::Skip;
+        }
+        self.add_type(&mut value, Element::Way);
+        self.add_rdf_type(&mut value, vocab::WAY);
         if let Err(err) = self.parse_way_geometry(&mut value, way) {
-            value.add_value("osmm:loc:error", XsdStr(&err.to_string()));
+            value.add_value(vocab::LOC_ERROR, XsdStr(&err.to_string()));
+            self.stats.warnings.add(Warning::GeometryError, 1);
+            let err = Some(err.to_string());
+            self.add_issue(Element::Way, way.id(), IssueKind::GeometryError, err);
         }
 
+        self.check_version(Element::Way, way.id(), &info);
         self.stats.added_ways += 1;
         Statement::Create {
             elem: Element::Way,
             id: way.id(),
-            ts: info.milli_timestamp,
-            val: value.finalize(info),
+            ts: info.milli_timestamp.unwrap_or_default(),
+            val: value.finalize(info, self.opts),
         }
     }
 
     fn on_relation(&mut self, rel: &Relation) -> Statement {
+        if self.is_negative(Element::Relation, rel.id())
+            || self.is_unlisted(Element::Relation, rel.id())
+        {
+            return Statement::Skip;
+        }
         let info: ElementInfo = rel.info().into();
         if info.is_deleted {
             self.stats.deleted_rels += 1;
@@ -192,59 +590,507 @@ impl<'a> Parser<'a> {
                 id: rel.id(),
             };
         }
+        if self.is_too_old(&info)
+            || self.is_tagless(self.opts.skip_tagless_relations, rel.tags())
+            || self.is_outside_bbox(|p, bbox| p.has_member_inside(bbox, rel))
+        {
+            return Statement::Skip;
+        }
+        if self.opts.format == OutputFormat::Protobuf {
+            return self.encode_relation(&info, rel);
+        }
 
         let mut value = StringBuf::default();
-        value.add_tags(rel.tags());
-        value.add_value("osmm:type", XsdElement(Element::Relation));
+        value.begin_subject(Element::Relation, rel.id());
+        let bad_keys = value.add_tags(rel.tags(), self.opts);
+        self.stats.warnings.add(Warning::BadKey, bad_keys);
+        self.add_tag_issues(Element::Relation, rel.id(), &mut value);
+        if value.is_empty() && self.is_filtered_by_keys() {
+            self.stats.skipped_rels += 1;
+            return Statement::Skip;
+        }
+        // Boundaries are usually queried by their level, so a numeric one is also emitted as a number
+        if rel.tags().any(|v| v == ("type", "boundary")) {
+            let level = rel.tags().find(|(k, _)| *k == "admin_level");
+            if let Some(Ok(level)) = level.map(|(_, v)| v.parse::<i64>()) {
+                value.add_value(vocab::ADMIN_LEVEL, XsdInteger(level));
+            }
+        }
+        self.add_type(&mut value, Element::Relation);
+        if self.opts.emit_type_class() {
+            let relation_type = rel.tags().find(|(k, _)| *k == "type").map(|(_, v)| v);
+            self.add_rdf_type(&mut value, vocab::relation_class(relation_type));
+        }
+        value.start_section(Section::Geometry);
+        let mut has_geometry = false;
+        if self.opts.route_geometry && rel.tags().any(|v| v == ("type", "route")) {
+            has_geometry |= self.add_route_geometry(&mut value, rel);
+        }
+        if self.opts.emit_special_points {
+            has_geometry |= self.add_special_points(&mut value, rel);
+        }
+        if self.opts.emit_has_geometry && !has_geometry {
+            // Only the route lines and the special points are computed for the relations
+            value.add_value(vocab::HAS_GEOMETRY, XsdBoolean(false));
+        }
 
-        for mbr in rel.members() {
+        value.start_section(Section::Members);
+        let mut dangling = 0;
+        for (index, mbr) in rel.members().enumerate() {
+            // Members with negative ids are skipped, the same as the elements themselves
+            if mbr.member_id < 0 {
+                continue;
+            }
+            if self.opts.validate_members
+                && !self
+                    .pre_pass
+                    .members
+                    .contains(&((&mbr.member_type).into(), mbr.member_id))
+            {
+                dangling += 1;
+                if self.opts.omit_dangling_members {
+                    continue;
+                }
+            }
             // Produce two statements - one to find all members of a relation,
             // and another to find the role of that relation
             //     osmrel:123  osmm:has    osmway:456
             //     osmrel:123  osmway:456  "inner"    (this is added only if non-empty)
-            value.add_value("osmm:has", XsdRelMember(&mbr));
+            value.add_value(vocab::HAS, XsdRelMember(&mbr));
             let role = mbr.role().unwrap();
             if !role.is_empty() {
                 value.add_value(XsdRelMember(&mbr), XsdStr(role));
             }
+            if self.opts.emit_member_order {
+                let member = XsdMember {
+                    member: &mbr,
+                    index,
+                };
+                value.add_value(vocab::MEMBER, member);
+            }
+        }
+        if dangling > 0 {
+            value.add_value(vocab::DANGLING_MEMBERS, XsdInteger(dangling));
+        }
+        if self.opts.emit_role_counts {
+            let counts = role_counts(rel);
+            if !counts.is_empty() {
+                value.add_value(vocab::ROLE_COUNT, XsdStr(&counts));
+            }
         }
 
+        self.check_version(Element::Relation, rel.id(), &info);
+        let ts = info.milli_timestamp.unwrap_or_default();
+        let value = value.finalize(info, self.opts);
+        // E.g. a relation without tags and members, with `--no-metadata` and `--no-type-predicate`
+        if value.is_empty() {
+            self.stats.skipped_rels += 1;
+            return Statement::Skip;
+        }
         self.stats.added_rels += 1;
         Statement::Create {
             elem: Element::Relation,
             id: rel.id(),
-            ts: info.milli_timestamp,
-            val: value.finalize(info),
+            ts,
+            val: value,
+        }
+    }
+
+    fn encode_node<'t>(
+        &mut self,
+        info: &ElementInfo,
+        id: i64,
+        tags: impl Iterator<Item = (&'t str, &'t str)>,
+        lat: f64,
+        lon: f64,
+    ) -> Statement {
+        let tags = proto::tags(tags);
+        if tags.is_empty() {
+            self.stats.skipped_nodes += 1;
+            return Statement::Skip;
+        }
+        let mut element = proto::Element::new(&Element::Node, id, tags, info);
+        element.loc = Some(proto::Point { lat, lon });
+        self.stats.added_nodes += 1;
+        encoded(Element::Node, info, element)
+    }
+
+    fn encode_way(&mut self, info: &ElementInfo, way: &Way) -> Statement {
+        let tags = proto::tags(way.tags());
+        let mut element = proto::Element::new(&Element::Way, way.id(), tags, info);
+        element.geometry = way
+            .refs()
+            .filter_map(|id| {
+                let (lat, lon) = self.node_location(id)?;
+                Some(proto::Point { lat, lon })
+            })
+            .collect();
+        let line: LineString = element.geometry.iter().map(|p| [p.lon, p.lat]).collect();
+        element.is_closed = Some(line.is_closed());
+        element.loc = centroid(&line, self.opts.centroid_mode).map(|c| proto::Point {
+            lat: c.y(),
+            lon: c.x(),
+        });
+        self.stats.added_ways += 1;
+        encoded(Element::Way, info, element)
+    }
+
+    fn encode_relation(&mut self, info: &ElementInfo, rel: &Relation) -> Statement {
+        let tags = proto::tags(rel.tags());
+        let mut element = proto::Element::new(&Element::Relation, rel.id(), tags, info);
+        element.members = rel.members().map(|mbr| (&mbr).into()).collect();
+        self.stats.added_rels += 1;
+        encoded(Element::Relation, info, element)
+    }
+
+    /// Negative ids are used by the editors for the elements that were not uploaded yet, e.g. in `.osc` files.
+    /// They cannot be used as cache indexes or in the element IRIs, so such elements are skipped with a warning.
+    fn is_negative(&mut self, elem: Element, id: i64) -> bool {
+        if id >= 0 {
+            return false;
+        }
+        warn!("{elem}:{id} has a negative id and is skipped");
+        self.stats.warnings.add(Warning::NegativeId, 1);
+        match elem {
+            Element::Node => self.stats.skipped_nodes += 1,
+            Element::Way => self.stats.skipped_ways += 1,
+            Element::Relation => self.stats.skipped_rels += 1,
+        }
+        true
+    }
+
+    /// Check if the element is older than the `--modified-since` threshold, and count it as skipped.
+    /// Elements without a timestamp are always kept.
+    fn is_too_old(&mut self, info: &ElementInfo) -> bool {
+        match (self.opts.modified_since, info.milli_timestamp) {
+            (Some(since), Some(ts)) if ts < since.timestamp_millis() => {
+                self.stats.skipped_old += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Check if the element is not in the `--id-list` file and count it as skipped, or record it as found
+    fn is_unlisted(&mut self, elem: Element, id: i64) -> bool {
+        let Some(id_list) = &self.opts.id_list else {
+            return false;
+        };
+        if id_list.contains(elem, id) {
+            self.stats.listed_found.insert((elem, id));
+            false
+        } else {
+            self.stats.skipped_unlisted += 1;
+            true
+        }
+    }
+
+    /// Check if the element has no tags other than `created_by` and should be skipped, and count it as skipped
+    fn is_tagless<'t>(
+        &mut self,
+        skip: bool,
+        mut tags: impl Iterator<Item = (&'t str, &'t str)>,
+    ) -> bool {
+        if skip && tags.all(|(key, _)| key == "created_by") {
+            self.stats.skipped_tagless += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Check if the element is outside the `--bbox` box, and count it as skipped.
+    /// The `is_inside` callback is only called when the box is set.
+    fn is_outside_bbox(&mut self, is_inside: impl FnOnce(&Self, &Bbox) -> bool) -> bool {
+        let opts = self.opts;
+        let is_outside = opts
+            .bbox
+            .as_ref()
+            .is_some_and(|bbox| !is_inside(self, bbox));
+        if is_outside {
+            self.stats.skipped_outside_bbox += 1;
+        }
+        is_outside
+    }
+
+    /// Check if any of the nodes is inside the box, using their cached locations
+    fn has_node_inside(&self, bbox: &Bbox, mut refs: impl Iterator<Item = i64>) -> bool {
+        refs.any(|id| {
+            self.node_location(id)
+                .is_some_and(|(lat, lon)| bbox.contains(lat, lon))
+        })
+    }
+
+    /// Check if any member node, or any node of a member way is inside the box.
+    /// The nodes of the member ways are collected by the pre-pass, and member relations are ignored.
+    fn has_member_inside(&self, bbox: &Bbox, rel: &Relation) -> bool {
+        rel.members().any(|mbr| match mbr.member_type {
+            RelMemberType::Node => self.has_node_inside(bbox, iter::once(mbr.member_id)),
+            RelMemberType::Way => self
+                .pre_pass
+                .route_ways
+                .get(&mbr.member_id)
+                .is_some_and(|refs| self.has_node_inside(bbox, refs.iter().copied())),
+            RelMemberType::Relation => false,
+        })
+    }
+
+    /// Elements without any tags are skipped when only some keys are emitted, see `--only-keys`
+    fn is_filtered_by_keys(&self) -> bool {
+        !self.opts.only_keys.is_empty()
+    }
+
+    fn add_type(&self, value: &mut StringBuf, elem: Element) {
+        value.start_section(Section::Metadata);
+        if self.opts.emit_type_literal() {
+            value.add_value(vocab::TYPE, XsdElement(elem));
+        }
+    }
+
+    /// Versions start at 1, so smaller ones are flagged with `osmm:versionWarning` by `StringBuf::finalize`
+    fn check_version(&mut self, elem: Element, id: i64, info: &ElementInfo) {
+        let Some(version) = info.version else {
+            return;
+        };
+        if version < 1 {
+            warn!("{elem}:{id} has an invalid version {version}");
+            self.stats.warnings.add(Warning::InvalidVersion, 1);
+            let version = Some(version.to_string());
+            self.add_issue(elem, id, IssueKind::InvalidVersion, version);
+        }
+    }
+
+    /// Move the problems found in the tags to the issues of the run
+    fn add_tag_issues(&mut self, elem: Element, id: i64, value: &mut StringBuf) {
+        for (kind, val) in value.issues.drain(..) {
+            self.add_issue(elem, id, kind, Some(val));
+        }
+    }
+
+    fn add_issue(&mut self, elem: Element, id: i64, kind: IssueKind, value: Option<String>) {
+        if self.opts.emit_issues {
+            let issue = Issue {
+                elem,
+                id,
+                kind,
+                value,
+            };
+            self.stats.issues.push(issue);
+        }
+    }
+
+    fn add_rdf_type(&self, value: &mut StringBuf, class: vocab::Class) {
+        if self.opts.emit_type_class() {
+            value.add_value("a", class);
+        }
+    }
+
+    fn add_geohash(&self, value: &mut StringBuf, lat: f64, lon: f64) {
+        if let Some(precision) = self.opts.geohash_precision {
+            value.add_value(vocab::GEOHASH, XsdStr(&geohash(lat, lon, precision)));
+        }
+    }
+
+    /// Add the locations of the member nodes with the `label` and `admin_centre` roles, returning whether any was found
+    fn add_special_points(&mut self, value: &mut StringBuf, rel: &Relation) -> bool {
+        let mut added = false;
+        for mbr in rel.members() {
+            if mbr.member_type != RelMemberType::Node {
+                continue;
+            }
+            let predicate = match mbr.role().unwrap() {
+                "label" => vocab::LABEL_LOC,
+                "admin_centre" => vocab::ADMIN_CENTRE_LOC,
+                _ => continue,
+            };
+            let Some((lat, lon)) = self.node_location(mbr.member_id) else {
+                continue;
+            };
+            let point = XsdPoint {
+                lat,
+                lon,
+                precision: self.opts.coord_precision,
+                crs: self.opts.wkt_crs(),
+            };
+            value.add_value(predicate, point);
+            added = true;
         }
+        added
     }
 
-    fn parse_way_geometry(&self, value: &mut StringBuf, way: &Way) -> anyhow::Result<()> {
+    /// Collect the line strings of all member ways in the member order, skipping the missing ones.
+    /// The nodes missing from the input are skipped too, and so are the lines left with fewer than 2 nodes.
+    /// Returns whether the route geometry was added.
+    fn add_route_geometry(&mut self, value: &mut StringBuf, rel: &Relation) -> bool {
+        let mut lines = Vec::new();
+        let mut is_partial = false;
+        for mbr in rel.members() {
+            if mbr.member_type != RelMemberType::Way {
+                continue;
+            }
+            let Some(refs) = self.pre_pass.route_ways.get(&mbr.member_id) else {
+                is_partial = true;
+                continue;
+            };
+            // The cache has no empty value, so the missing nodes would be placed at the origin
+            let line: Vec<_> = refs
+                .iter()
+                .filter(|&id| self.pre_pass.route_nodes.contains(id))
+                .filter_map(|&id| self.node_location(id))
+                .collect();
+            if line.len() < 2 {
+                is_partial = true;
+                continue;
+            }
+            is_partial |= line.len() < refs.len();
+            lines.push(line);
+        }
+        if !lines.is_empty() {
+            let geometry = XsdLineCollection {
+                lines: &lines,
+                precision: self.opts.coord_precision,
+                crs: self.opts.wkt_crs(),
+            };
+            value.add_value(vocab::ROUTE_GEOMETRY, geometry);
+        }
+        if is_partial {
+            value.add_value(vocab::LOC_PARTIAL, XsdBoolean(true));
+        }
+        !lines.is_empty()
+    }
+
+    fn parse_way_geometry(&mut self, value: &mut StringBuf, way: &Way) -> anyhow::Result<()> {
+        value.start_section(Section::Geometry);
+        if self.opts.emit_metrics {
+            let count = way.refs().len();
+            value.add_value(vocab::NODE_COUNT, XsdInteger(count as i64));
+        }
         let geometry: LineString = way
             .refs()
             .map(|id| {
-                let (lat, lng) = self.cache.get_lat_lon(id as usize);
-                [lat, lng]
+                let (lat, lon) = self.node_location(id).context("negative_node_id")?;
+                Ok([lon, lat])
             })
-            .collect();
+            .collect::<anyhow::Result<_>>()?;
+
+        // Degenerate ways have no line to compute the closedness or the centroid of
+        match geometry.0.len() {
+            // Reported as `osmm:loc:error` rather than `osmm:hasGeometry`
+            0 => bail!("no_nodes"),
+            n if n < self.opts.min_way_nodes => {
+                if self.opts.emit_has_geometry {
+                    value.add_value(vocab::HAS_GEOMETRY, XsdBoolean(false));
+                }
+                value.add_value(vocab::GEOMETRY_WARNING, XsdStr("too_few_nodes"));
+                self.stats.warnings.add(Warning::TooFewNodes, 1);
+                return Ok(());
+            }
+            1 => {
+                // Use the node location as is, the same as `process_node`
+                let (lat, lon) = (geometry.0[0].y, geometry.0[0].x);
+                self.stats.add_point(lat, lon);
+                let point = XsdPoint {
+                    lat,
+                    lon,
+                    precision: self.opts.coord_precision,
+                    crs: self.opts.wkt_crs(),
+                };
+                value.add_value(vocab::LOC, point);
+                self.add_geohash(value, lat, lon);
+                return Ok(());
+            }
+            _ => {}
+        }
 
+        // Closedness is based on the node locations rather than the node ids, so a way ending at a different node
+        // placed at the same location as the first one is also closed. Only ways have `osmm:isClosed`.
         let value1 = geometry.is_closed();
-        value.add_value("osmm:isClosed", XsdBoolean(value1));
+        // Closed ways are treated as areas unless tagged otherwise, and labeled at their area centroid
+        let is_area = value1 && geometry.0.len() >= 4 && !way.tags().any(|v| v == ("area", "no"));
+        let emit_is_closed = match self.opts.is_closed_for {
+            IsClosedFor::All => true,
+            IsClosedFor::Areas => is_area,
+            IsClosedFor::None => false,
+        };
+        if emit_is_closed {
+            value.add_value(vocab::IS_CLOSED, XsdBoolean(value1));
+        }
+        if self.opts.emit_geometry {
+            let line: Vec<_> = geometry.coords().map(|c| (c.y, c.x)).collect();
+            let line = XsdLineString {
+                line: &line,
+                precision: self.opts.coord_precision,
+                crs: self.opts.wkt_crs(),
+            };
+            value.add_value(vocab::GEOMETRY, line);
+        }
+        if self.opts.emit_metrics {
+            let length = format!("{:.2}", geometry.haversine_length());
+            value.add_value(vocab::LENGTH, XsdTyped(&length, "decimal"));
+        }
 
-        if let Some(g) = geometry.centroid() {
+        let mode = self.opts.centroid_mode;
+        let point = if is_area {
+            polygon_centroid(&Polygon::new(geometry.clone(), vec![]), mode)
+        } else {
+            centroid(&geometry, mode)
+        };
+        if let Some(g) = point {
             let point = XsdPoint {
                 lat: g.y(),
                 lon: g.x(),
+                precision: self.opts.centroid_precision.or(self.opts.coord_precision),
+                crs: self.opts.wkt_crs(),
             };
-            value.add_value("osmm:loc", point);
+            self.stats.add_point(point.lat, point.lon);
+            let (lat, lon) = (point.lat, point.lon);
+            value.add_value(vocab::LOC, point);
+            self.add_geohash(value, lat, lon);
+            if self.opts.check_geometry && !is_within_bbox(&geometry, g) {
+                value.add_value(vocab::GEOMETRY_WARNING, XsdStr("centroid_outside_bbox"));
+                self.stats.warnings.add(Warning::CentroidOutsideBbox, 1);
+                let kind = IssueKind::CentroidOutsideBbox;
+                self.add_issue(Element::Way, way.id(), kind, None);
+            }
         }
 
         Ok(())
     }
 }
 
-fn create_flat_cache(filename: PathBuf) -> anyhow::Result<DenseFileCache> {
+/// Number of members with each role, in the order of their first appearance, e.g. `outer=3;inner=1`.
+/// The members without a role are counted as `none`.
+fn role_counts(rel: &Relation) -> String {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for mbr in rel.members() {
+        let role = match mbr.role().unwrap() {
+            "" => "none",
+            role => role,
+        };
+        match counts.iter_mut().find(|(v, _)| *v == role) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((role, 1)),
+        }
+    }
+    counts
+        .iter()
+        .map(|(role, count)| format!("{role}={count}"))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+fn encoded(elem: Element, info: &ElementInfo, element: proto::Element) -> Statement {
+    Statement::Encoded {
+        elem,
+        id: element.id,
+        ts: info.milli_timestamp.unwrap_or_default(),
+        val: element.encode_length_delimited_to_vec(),
+    }
+}
+
+fn create_flat_cache(filename: PathBuf, page_size: usize) -> anyhow::Result<DenseFileCache> {
     Ok(DenseFileCacheOpts::new(filename)
-        .page_size(10 * 1024 * 1024 * 1024)
+        .page_size(page_size)
         .on_size_change(Some(|old_size, new_size| {
             info!(
                 "Growing cache {} ➡ {}",
@@ -255,92 +1101,1303 @@ fn create_flat_cache(filename: PathBuf) -> anyhow::Result<DenseFileCache> {
         .open()?)
 }
 
-fn start_writer_thread(
-    output_dir: &Path,
-    max_file_size: usize,
-    receiver: Receiver<Vec<Statement>>,
-) -> JoinHandle<()> {
-    let output_dir = output_dir.to_path_buf();
-    let file_index = AtomicU32::new(0);
-    let oldest_ts = AtomicI64::new(0);
+/// Output files shared by all writer threads, numbered sequentially in the order of creation
+struct OutputFiles {
+    output_dir: PathBuf,
+    file_index: AtomicU32,
+    /// Index of the next `deletes-NNNNNN` file
+    delete_index: AtomicU32,
+    format: OutputFormat,
+    compression: OutputCompression,
+    compression_level: Option<u32>,
+    deflate_strategy: DeflateStrategy,
+    key_namespaces: Vec<KeyNamespace>,
+    emit_index: bool,
+    osm_base_url: String,
+    /// Write SPARQL Update requests replacing each subject instead of Turtle, see `--upsert`
+    upsert: bool,
+    /// Converter of the statements with `--format ntriples` and `--format jsonld`
+    converter: Option<Box<dyn Converter>>,
+    /// Number of output subdirectories, see `--shard-by-id`
+    shard_by_id: Option<NonZeroUsize>,
+    /// Declarations added to or replacing the built-in prefixes, see `--prefixes`
+    prefixes: PrefixFile,
+    /// Element id ranges of the completed output files, see `--emit-file-index`
+    file_ranges: Option<Mutex<Vec<FileRanges>>>,
+}
 
-    Builder::new()
-        .name("gz_writer".into())
-        .spawn(move || {
-            let mut encoder = None;
-            let mut size = 0_usize;
-            while let Ok(batch) = receiver.recv() {
-                for statement in batch {
-                    match statement {
-                        Statement::Create { elem, id, val, ts } => {
-                            oldest_ts.fetch_max(ts, Ordering::Relaxed);
-
-                            let enc = encoder
-                                .get_or_insert_with(|| new_gz_file(&output_dir, &file_index));
-                            write!(enc, "\n{elem}:{id}\n{val}").unwrap();
-
-                            size += val.len();
-                            if size > max_file_size {
-                                encoder.take().unwrap().finish().unwrap();
-                                size = 0;
-                            }
-                        }
-                        Statement::Skip => {}
-                        Statement::Delete { elem, id } => {
-                            warn!("Delete {elem}:{id} is not supported");
-                        }
-                    }
-                }
-            }
+impl OutputFiles {
+    fn create(&self) -> Encoder {
+        self.create_file(0).1
+    }
 
-            // Create a separate file with the date of the last modification
-            let mut enc = new_gz_file(&output_dir, &file_index);
-            let ts = XsdDateTime(oldest_ts.load(Ordering::SeqCst));
-            writeln!(enc, "\nosmroot: schema:dateModified {ts}.").unwrap();
-        })
-        .unwrap()
-}
+    /// Number of shards, 1 without `--shard-by-id`
+    fn shard_count(&self) -> usize {
+        self.shard_by_id.map_or(1, NonZeroUsize::get)
+    }
 
-fn new_gz_file(output_dir: &Path, file_index: &AtomicU32) -> GzEncoder<File> {
-    let index = file_index.fetch_add(1, Ordering::Relaxed);
-    let filename = output_dir.join(format!("osm-{index:06}.ttl.gz"));
-    info!("Creating {:?}", filename.absolutize().unwrap());
-    let file = File::create(filename).unwrap();
-    let mut enc = GzEncoder::new(file, Compression::default());
-    for prefix in PREFIXES {
-        writeln!(enc, "@{prefix}.").unwrap();
+    /// Index of the shard of an element, see `--shard-by-id`
+    fn shard(&self, id: i64) -> usize {
+        id.rem_euclid(self.shard_count() as i64) as usize
     }
-    enc
-}
 
-pub fn parse(opt: Args) -> anyhow::Result<Stats> {
-    #[allow(irrefutable_let_patterns)]
-    let Command::Parse {
-        workers,
-        input_file,
-        output_dir,
-        max_file_size,
-    } = opt.cmd
-    else {
-        unreachable!()
-    };
+    /// Directory of the files of a shard, or the output directory itself without `--shard-by-id`
+    fn shard_dir(&self, shard: usize) -> PathBuf {
+        match self.shard_by_id {
+            Some(_) => self.output_dir.join(format!("shard-{shard:03}")),
+            None => self.output_dir.clone(),
+        }
+    }
+
+    /// Create the next output file for the elements of a shard, together with its block index if enabled
+    fn create_output(&self, shard: usize) -> OutputFile {
+        let (filename, enc) = self.create_file(shard);
+        let ranges = self.file_ranges.as_ref().map(|_| FileRanges {
+            path: filename
+                .strip_prefix(&self.output_dir)
+                .unwrap()
+                .to_path_buf(),
+            ids: BTreeMap::new(),
+        });
+        let index = self.emit_index.then(|| {
+            let mut filename = filename.into_os_string();
+            filename.push(".gzi");
+            BlockIndex::new(File::create(filename).unwrap())
+        });
+        OutputFile {
+            enc,
+            index,
+            ranges,
+            compression: self.compression,
+            compression_level: self.compression_level,
+            deflate_strategy: self.deflate_strategy,
+        }
+    }
+
+    /// Complete an output file, and record its element id ranges for `--emit-file-index`
+    fn finish_output(&self, out: OutputFile) {
+        if let (Some(ranges), Some(file_ranges)) = (out.finish(), &self.file_ranges) {
+            file_ranges.lock().unwrap().push(ranges);
+        }
+    }
+
+    /// Write the element id ranges of all output files to `index.csv`, see `--emit-file-index`
+    fn write_file_index(&self) -> anyhow::Result<()> {
+        let Some(file_ranges) = &self.file_ranges else {
+            return Ok(());
+        };
+        let mut file_ranges = file_ranges.lock().unwrap();
+        file_ranges.sort_by(|a, b| a.path.cmp(&b.path));
+        let path = self.output_dir.join("index.csv");
+        info!(
+            "Writing the element id ranges of the output files to {}",
+            path.display()
+        );
+        let mut out = String::from("file,type,min_id,max_id\n");
+        for ranges in file_ranges.iter() {
+            for (elem, (min_id, max_id)) in &ranges.ids {
+                let elem = elem.to_possible_value().unwrap();
+                let file = ranges.path.display();
+                out.push_str(&format!("{file},{},{min_id},{max_id}\n", elem.get_name()));
+            }
+        }
+        std::fs::write(&path, out).with_context(|| format!("Unable to write {}", path.display()))
+    }
+
+    fn create_file(&self, shard: usize) -> (PathBuf, Encoder) {
+        let index = self.file_index.fetch_add(1, Ordering::Relaxed);
+        self.create_named(shard, &format!("osm-{index:06}"))
+    }
+
+    /// Create the next file for the deleted elements of a shard, see `write_delete`
+    fn create_deletes(&self, shard: usize) -> Encoder {
+        let index = self.delete_index.fetch_add(1, Ordering::Relaxed);
+        self.create_named(shard, &format!("deletes-{index:06}")).1
+    }
+
+    fn create_named(&self, shard: usize, name: &str) -> (PathBuf, Encoder) {
+        // SPARQL Update files are not Turtle, so they must not be mixed with the regular output
+        let ext = if self.upsert {
+            "ru"
+        } else {
+            self.format.extension()
+        };
+        let suffix = self.compression.suffix();
+        let filename = self.shard_dir(shard).join(format!("{name}.{ext}{suffix}"));
+        info!("Creating {:?}", filename.absolutize().unwrap());
+        let file = File::create(&filename).unwrap();
+        let mut enc = Encoder::new(
+            file,
+            self.compression,
+            self.compression_level,
+            self.deflate_strategy,
+        );
+        self.write_prefixes(&mut enc).unwrap();
+        (filename, enc)
+    }
+
+    /// Write the element in the output format, and return the size of its value
+    fn write_statement(
+        &self,
+        statement: &Statement,
+        out: &mut impl Write,
+    ) -> std::io::Result<usize> {
+        match (statement, &self.converter) {
+            (Statement::Create { elem, id, val, .. }, Some(converter)) => {
+                converter.write_element(out, &format!("{elem}:{id}"), val)
+            }
+            _ if self.upsert => statement.write_upsert_to(out),
+            _ => statement.write_to(out),
+        }
+    }
+
+    /// Write a deleted element. With `--upsert`, it is a request removing all statements of the subject,
+    /// otherwise an `osmm:deleted` statement about the subject.
+    fn write_delete(&self, elem: Element, id: i64, out: &mut impl Write) -> std::io::Result<()> {
+        let subject = format!("{elem}:{id}");
+        let value = format!("{} {}.", vocab::DELETED, XsdBoolean(true));
+        match &self.converter {
+            Some(converter) => converter.write_element(out, &subject, &value).map(drop),
+            None if self.upsert => writeln!(out, "DELETE WHERE {{ {subject} ?p ?o }};"),
+            None => writeln!(out, "{subject} {value}"),
+        }
+    }
+
+    /// All prefix declarations, including the `--key-namespace` and `--prefixes` ones
+    fn declarations(&self) -> Vec<String> {
+        let namespaces = self.key_namespaces.iter().map(KeyNamespace::declaration);
+        let declarations = prefixes(&self.osm_base_url)
+            .into_iter()
+            .chain(namespaces)
+            .collect();
+        self.prefixes.merge(declarations)
+    }
+
+    /// Declare the prefixes at the start of each file or stream, unless the format has no prefixes
+    fn write_prefixes(&self, out: &mut impl Write) -> std::io::Result<()> {
+        if self.format != OutputFormat::Turtle {
+            return Ok(());
+        }
+        for prefix in self.declarations() {
+            if self.upsert {
+                writeln!(out, "{prefix}")?;
+            } else {
+                writeln!(out, "@{prefix}.")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writer of an output file, compressed as chosen by `--compression`
+enum Encoder {
+    Gzip(GzEncoder<File>),
+    /// Gzip with a non-default `--deflate-strategy`
+    GzipStrategy(GzStrategyEncoder<File>),
+    Zstd(zstd::Encoder<'static, File>),
+    Plain(BufWriter<File>),
+}
+
+impl Encoder {
+    /// Create an encoder with the given level, or with the default level of the compression
+    fn new(
+        file: File,
+        compression: OutputCompression,
+        level: Option<u32>,
+        strategy: DeflateStrategy,
+    ) -> Self {
+        match compression {
+            OutputCompression::Gzip if strategy != DeflateStrategy::Default => {
+                let level = level.unwrap_or(Compression::default().level());
+                Self::GzipStrategy(GzStrategyEncoder::new(file, level, strategy).unwrap())
+            }
+            OutputCompression::Gzip => {
+                let level = level.map_or_else(Compression::default, Compression::new);
+                Self::Gzip(GzEncoder::new(file, level))
+            }
+            OutputCompression::Zstd => {
+                let level = level.map_or(zstd::DEFAULT_COMPRESSION_LEVEL, |v| v as i32);
+                Self::Zstd(zstd::Encoder::new(file, level).unwrap())
+            }
+            OutputCompression::None => Self::Plain(BufWriter::new(file)),
+        }
+    }
+
+    /// Complete the compressed stream, and return the underlying file
+    fn finish(self) -> std::io::Result<File> {
+        match self {
+            Self::Gzip(enc) => enc.finish(),
+            Self::GzipStrategy(enc) => enc.finish(),
+            Self::Zstd(enc) => enc.finish(),
+            Self::Plain(writer) => writer.into_inner().map_err(|err| err.into_error()),
+        }
+    }
+}
+
+impl Write for Encoder {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Gzip(enc) => enc.write(buf),
+            Self::GzipStrategy(enc) => enc.write(buf),
+            Self::Zstd(enc) => enc.write(buf),
+            Self::Plain(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Gzip(enc) => enc.flush(),
+            Self::GzipStrategy(enc) => enc.flush(),
+            Self::Zstd(enc) => enc.flush(),
+            Self::Plain(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Output file for the elements. With `--emit-index`, each batch of statements is written
+/// as a separate gzip member or zstd frame, and they are listed in the `.gzi` sidecar file.
+struct OutputFile {
+    enc: Encoder,
+    index: Option<BlockIndex>,
+    ranges: Option<FileRanges>,
+    compression: OutputCompression,
+    compression_level: Option<u32>,
+    deflate_strategy: DeflateStrategy,
+}
+
+impl OutputFile {
+    fn write_element(&mut self, elem: Element, id: i64) {
+        if let Some(index) = &mut self.index {
+            index.first.get_or_insert((elem, id));
+            index.last = Some((elem, id));
+        }
+        if let Some(ranges) = &mut self.ranges {
+            let range = ranges.ids.entry(elem).or_insert((id, id));
+            range.0 = range.0.min(id);
+            range.1 = range.1.max(id);
+        }
+    }
+
+    /// Start a new gzip member if the current one has any elements and the index is enabled
+    fn end_block(mut self) -> Self {
+        match &mut self.index {
+            Some(index) if index.first.is_some() => {
+                let mut file = self.enc.finish().unwrap();
+                index.add_block(file.stream_position().unwrap());
+                self.enc = Encoder::new(
+                    file,
+                    self.compression,
+                    self.compression_level,
+                    self.deflate_strategy,
+                );
+                self
+            }
+            _ => self,
+        }
+    }
+
+    /// Complete the file, and return its element id ranges if they are collected
+    fn finish(self) -> Option<FileRanges> {
+        let mut file = self.enc.finish().unwrap();
+        if let Some(mut index) = self.index {
+            index.add_block(file.stream_position().unwrap());
+            index.file.flush().unwrap();
+        }
+        self.ranges
+    }
+}
+
+/// Smallest and largest element ids of each type in an output file, see `--emit-file-index`
+struct FileRanges {
+    /// Path of the file relative to the output directory
+    path: PathBuf,
+    ids: BTreeMap<Element, (i64, i64)>,
+}
+
+/// Sidecar index of an output file, with one line per gzip member:
+/// `<offset>\t<first element>\t<last element>`, e.g. `1234\tosmnode:10\tosmway:20`.
+/// The offset is the position of the member in the compressed file, and the member ends where the next one starts.
+/// The first member also contains the prefix declarations.
+struct BlockIndex {
+    file: BufWriter<File>,
+    /// Offset of the current gzip member
+    offset: u64,
+    first: Option<(Element, i64)>,
+    last: Option<(Element, i64)>,
+}
+
+impl BlockIndex {
+    fn new(file: File) -> Self {
+        Self {
+            file: BufWriter::new(file),
+            offset: 0,
+            first: None,
+            last: None,
+        }
+    }
+
+    /// Record the current gzip member, and start a new one at the given offset
+    fn add_block(&mut self, next_offset: u64) {
+        if let (Some((first, first_id)), Some((last, last_id))) =
+            (self.first.take(), self.last.take())
+        {
+            let offset = self.offset;
+            writeln!(self.file, "{offset}\t{first}:{first_id}\t{last}:{last_id}").unwrap();
+        }
+        self.offset = next_offset;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn start_writer_thread(
+    files: OutputFiles,
+    max_file_size: usize,
+    flush_interval: Option<Duration>,
+    io_threads: NonZeroUsize,
+    sorted_batch_size: Option<NonZeroUsize>,
+    dry_run: bool,
+    socket: Option<Box<dyn Write + Send>>,
+    endpoints: Option<Endpoints>,
+    receiver: Receiver<Vec<Statement>>,
+) -> JoinHandle<WriterResult> {
+    let oldest_ts = AtomicI64::new(0);
+
+    Builder::new()
+        .name("gz_writer".into())
+        .spawn(move || {
+            let receiver = Mutex::new(match sorted_batch_size {
+                Some(batch_size) => sort_statements(receiver, batch_size),
+                None => receiver,
+            });
+            if dry_run {
+                discard_statements(&receiver, &oldest_ts);
+                return WriterResult {
+                    newest_ts: oldest_ts.load(Ordering::SeqCst),
+                    files,
+                    warnings: Warnings::default(),
+                    socket: None,
+                    socket_closed: false,
+                    endpoints: None,
+                };
+            }
+            if let Some(socket) = socket {
+                let (warnings, socket) = stream_statements(&files, socket, &receiver, &oldest_ts);
+                return WriterResult {
+                    newest_ts: oldest_ts.load(Ordering::SeqCst),
+                    files,
+                    warnings,
+                    socket_closed: socket.is_none(),
+                    socket,
+                    endpoints: None,
+                };
+            }
+            if let Some(endpoints) = endpoints {
+                send_statements(&files, &endpoints, &receiver, &oldest_ts);
+                return WriterResult {
+                    newest_ts: oldest_ts.load(Ordering::SeqCst),
+                    files,
+                    warnings: Warnings::default(),
+                    socket: None,
+                    socket_closed: false,
+                    endpoints: Some(endpoints),
+                };
+            }
+
+            // Each writer thread takes batches from the shared receiver, and writes them to its own file
+            let mut warnings = Warnings::default();
+            thread::scope(|scope| {
+                let writers: Vec<_> = (0..io_threads.get())
+                    .map(|i| {
+                        Builder::new()
+                            .name(format!("gz_writer #{i}"))
+                            .spawn_scoped(scope, || {
+                                write_statements(
+                                    &files,
+                                    &receiver,
+                                    max_file_size,
+                                    flush_interval,
+                                    &oldest_ts,
+                                )
+                            })
+                            .unwrap()
+                    })
+                    .collect();
+                for writer in writers {
+                    warnings.combine(writer.join().unwrap());
+                }
+            });
+
+            WriterResult {
+                newest_ts: oldest_ts.load(Ordering::SeqCst),
+                files,
+                warnings,
+                socket: None,
+                socket_closed: false,
+                endpoints: None,
+            }
+        })
+        .unwrap()
+}
+
+/// Receive all statements without writing them, only tracking the newest timestamp, see `--dry-run`
+fn discard_statements(receiver: &Mutex<Receiver<Vec<Statement>>>, oldest_ts: &AtomicI64) {
+    let receiver = receiver.lock().unwrap();
+    while let Ok(batch) = receiver.recv() {
+        for statement in batch {
+            if let Statement::Create { ts, .. } | Statement::Encoded { ts, .. } = statement {
+                oldest_ts.fetch_max(ts, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Write all statements to the socket instead of the output files, see `--socket`.
+/// If the reader closes the socket, the remaining statements are discarded, and no socket is returned.
+fn stream_statements(
+    files: &OutputFiles,
+    socket: Box<dyn Write + Send>,
+    receiver: &Mutex<Receiver<Vec<Statement>>>,
+    oldest_ts: &AtomicI64,
+) -> (Warnings, Option<Box<dyn Write + Send>>) {
+    let mut warnings = Warnings::default();
+    let mut out = Some(BufWriter::new(socket));
+    if let Err(err) = files.write_prefixes(out.as_mut().unwrap()) {
+        warn!("Socket was closed, discarding all statements: {err}");
+        out = None;
+    }
+    let receiver = receiver.lock().unwrap();
+    while let Ok(batch) = receiver.recv() {
+        // Keep receiving after the socket is closed, so that the parser threads can finish
+        let Some(stream) = &mut out else { continue };
+        for statement in batch {
+            let result = match statement {
+                Statement::Create { ts, .. } | Statement::Encoded { ts, .. } => {
+                    oldest_ts.fetch_max(ts, Ordering::Relaxed);
+                    files.write_statement(&statement, stream)
+                }
+                Statement::Skip | Statement::Checkpoint(_) => Ok(0),
+                Statement::Delete { elem, id } => {
+                    warn!("Delete {elem}:{id} is not supported");
+                    warnings.add(Warning::DeleteSkipped, 1);
+                    Ok(0)
+                }
+            };
+            if let Err(err) = result {
+                warn!("Socket was closed, discarding the remaining statements: {err}");
+                out = None;
+                break;
+            }
+        }
+    }
+    let socket = out.and_then(|v| v.into_inner().ok());
+    (warnings, socket)
+}
+
+/// Send each batch of statements as a SPARQL Update request to all endpoints, see `--sparql-endpoint`.
+/// Unlike `--socket`, the deleted elements are sent as requests removing all statements of the subject.
+fn send_statements(
+    files: &OutputFiles,
+    endpoints: &Endpoints,
+    receiver: &Mutex<Receiver<Vec<Statement>>>,
+    oldest_ts: &AtomicI64,
+) {
+    let receiver = receiver.lock().unwrap();
+    while let Ok(batch) = receiver.recv() {
+        // Each request declares the prefixes, as it is parsed on its own
+        let mut body = Vec::new();
+        files.write_prefixes(&mut body).unwrap();
+        let len = body.len();
+        for statement in batch {
+            match statement {
+                Statement::Create { ts, .. } | Statement::Encoded { ts, .. } => {
+                    oldest_ts.fetch_max(ts, Ordering::Relaxed);
+                    files.write_statement(&statement, &mut body).unwrap();
+                }
+                Statement::Delete { elem, id } => files.write_delete(elem, id, &mut body).unwrap(),
+                Statement::Skip | Statement::Checkpoint(_) => {}
+            }
+        }
+        if body.len() > len {
+            endpoints.send(body);
+        }
+    }
+}
+
+/// Connect to the Unix domain socket of the loader process, see `--socket`
+#[cfg(unix)]
+fn connect_socket(path: &Path) -> anyhow::Result<Box<dyn Write + Send>> {
+    let stream = std::os::unix::net::UnixStream::connect(path)
+        .with_context(|| format!("Unable to connect to socket {}", path.display()))?;
+    Ok(Box::new(stream))
+}
+
+#[cfg(not(unix))]
+fn connect_socket(_path: &Path) -> anyhow::Result<Box<dyn Write + Send>> {
+    bail!("--socket is only supported on Unix systems")
+}
+
+/// Wait for all statements, and return a receiver with the same statements sorted by element type and id
+fn sort_statements(
+    receiver: Receiver<Vec<Statement>>,
+    batch_size: NonZeroUsize,
+) -> Receiver<Vec<Statement>> {
+    let mut statements: Vec<_> = receiver
+        .into_iter()
+        .flatten()
+        .filter(|v| !matches!(v, Statement::Skip | Statement::Checkpoint(_)))
+        .collect();
+    info!("Sorting {} elements", statements.len());
+    // A stable sort keeps the input order of the same element from multiple input files
+    statements.sort_by_key(Statement::sort_key);
+
+    // Use the same batch size as the parser, so that `--emit-index` members have a similar size
+    let (sender, sorted) = channel();
+    let mut iter = statements.into_iter().peekable();
+    while iter.peek().is_some() {
+        sender
+            .send(iter.by_ref().take(batch_size.get()).collect())
+            .unwrap();
+    }
+    sorted
+}
+
+/// Returned by the writer thread once all statements are written
+struct WriterResult {
+    files: OutputFiles,
+    /// The latest timestamp of all written elements
+    newest_ts: i64,
+    warnings: Warnings,
+    /// Socket that the footer is written to instead of a file, see `--socket`
+    socket: Option<Box<dyn Write + Send>>,
+    /// Set if the socket reader stopped before all statements were written
+    socket_closed: bool,
+    /// Endpoints that the footer is sent to instead of a file, see `--sparql-endpoint`
+    endpoints: Option<Endpoints>,
+}
+
+impl WriterResult {
+    /// Create a separate file with the date of the last modification and the bounding box of the dataset
+    /// With `--socket`, the footer is written to the socket instead, unless it was closed.
+    /// With `--sparql-endpoint`, it is sent to all endpoints that have not failed.
+    fn write_footer(&mut self, date_modified: Option<i64>, bbox: Option<Bbox>, crs: Crs) {
+        if self.files.format == OutputFormat::Protobuf || self.socket_closed {
+            return;
+        }
+        let mut statements = Vec::new();
+        let ts = XsdDateTime(
+            date_modified.unwrap_or(self.newest_ts),
+            TimestampFormat::Iso,
+        );
+        statements.push((vocab::DATE_MODIFIED, ts.to_string()));
+        if let Some(bbox) = bbox {
+            let bbox = XsdBbox { bbox, crs };
+            statements.push((vocab::DATASET_BBOX, bbox.to_string()));
+        }
+
+        let footer = (|| {
+            let mut out = Vec::new();
+            if self.endpoints.is_some() {
+                self.files.write_prefixes(&mut out)?;
+            } else if self.files.converter.is_none() {
+                writeln!(out)?;
+            }
+            for (predicate, value) in statements {
+                if let Some(converter) = &self.files.converter {
+                    converter.write_element(
+                        &mut out,
+                        "osmroot:",
+                        &format!("{predicate} {value}."),
+                    )?;
+                } else if self.files.upsert {
+                    writeln!(out, "DELETE WHERE {{ osmroot: {predicate} ?o }};")?;
+                    writeln!(out, "INSERT DATA {{ osmroot: {predicate} {value}. }};")?;
+                } else {
+                    writeln!(out, "osmroot: {predicate} {value}.")?;
+                }
+            }
+            Ok::<_, std::io::Error>(out)
+        })()
+        .unwrap();
+
+        if let Some(endpoints) = &self.endpoints {
+            endpoints.send(footer);
+            return;
+        }
+        // The footer file must be finished explicitly to complete the compressed stream
+        let is_socket = self.socket.is_some();
+        let result = match self.socket.take() {
+            Some(mut socket) => socket.write_all(&footer).and_then(|()| socket.flush()),
+            None => {
+                let mut enc = self.files.create();
+                enc.write_all(&footer).and_then(|()| enc.finish().map(drop))
+            }
+        };
+        match result {
+            Err(err) if is_socket => {
+                warn!("Socket was closed before the footer was written: {err}");
+                self.socket_closed = true;
+            }
+            result => result.unwrap(),
+        }
+    }
+}
+
+fn write_statements(
+    files: &OutputFiles,
+    receiver: &Mutex<Receiver<Vec<Statement>>>,
+    max_file_size: usize,
+    flush_interval: Option<Duration>,
+    oldest_ts: &AtomicI64,
+) -> Warnings {
+    // One output file and its uncompressed size per shard
+    let mut encoders: Vec<Option<OutputFile>> = (0..files.shard_count()).map(|_| None).collect();
+    let mut sizes = vec![0_usize; encoders.len()];
+    // Deleted elements are written to separate files, so they can be applied before the new data is loaded
+    let mut deletes: Vec<Option<Encoder>> = (0..files.shard_count()).map(|_| None).collect();
+    let mut warnings = Warnings::default();
+    let mut next_flush = flush_interval.map(|v| Instant::now() + v);
+    loop {
+        // The lock must be released before processing the batch
+        let batch = recv_batch(&receiver.lock().unwrap(), next_flush);
+        let Some(batch) = batch else { break };
+        for statement in batch {
+            let written = match statement {
+                Statement::Create { elem, id, ts, .. }
+                | Statement::Encoded { elem, id, ts, .. } => {
+                    let shard = files.shard(id);
+                    let out = encoders[shard].get_or_insert_with(|| files.create_output(shard));
+                    let len = files.write_statement(&statement, &mut out.enc).unwrap();
+                    out.write_element(elem, id);
+                    Some((shard, ts, len))
+                }
+                Statement::Skip => None,
+                Statement::Delete { elem, id } if files.format == OutputFormat::Protobuf => {
+                    warn!("Delete {elem}:{id} is not supported by the protobuf format");
+                    warnings.add(Warning::DeleteSkipped, 1);
+                    None
+                }
+                Statement::Delete { elem, id } => {
+                    let shard = files.shard(id);
+                    let out = deletes[shard].get_or_insert_with(|| files.create_deletes(shard));
+                    files.write_delete(elem, id, out).unwrap();
+                    None
+                }
+                Statement::Checkpoint(mut state) => {
+                    // The saved file indexes are only valid once all the open files are complete
+                    for out in encoders.iter_mut().filter_map(Option::take) {
+                        files.finish_output(out);
+                    }
+                    sizes.fill(0);
+                    for out in deletes.iter_mut().filter_map(Option::take) {
+                        out.finish().unwrap();
+                    }
+                    state.file_index = files.file_index.load(Ordering::Relaxed);
+                    state.delete_index = files.delete_index.load(Ordering::Relaxed);
+                    state.newest_ts = state.newest_ts.max(oldest_ts.load(Ordering::Relaxed));
+                    state.save(&files.output_dir).unwrap();
+                    None
+                }
+            };
+            if let Some((shard, ts, len)) = written {
+                oldest_ts.fetch_max(ts, Ordering::Relaxed);
+                sizes[shard] += len;
+                if sizes[shard] > max_file_size {
+                    files.finish_output(encoders[shard].take().unwrap());
+                    sizes[shard] = 0;
+                }
+            }
+        }
+        for encoder in &mut encoders {
+            *encoder = encoder.take().map(OutputFile::end_block);
+        }
+        if let (Some(deadline), Some(interval)) = (next_flush, flush_interval) {
+            if Instant::now() >= deadline {
+                for out in encoders.iter_mut().flatten() {
+                    out.enc.flush().unwrap();
+                }
+                for out in deletes.iter_mut().flatten() {
+                    out.flush().unwrap();
+                }
+                next_flush = Some(Instant::now() + interval);
+            }
+        }
+    }
+    for out in encoders.into_iter().flatten() {
+        files.finish_output(out);
+    }
+    for out in deletes.into_iter().flatten() {
+        out.finish().unwrap();
+    }
+    warnings
+}
+
+/// Get the latest replication timestamp from the headers of all input files, in milliseconds
+fn header_timestamp(input_files: &[PathBuf]) -> anyhow::Result<i64> {
+    let mut result = i64::MIN;
+    for file in input_files {
+        let mut timestamp = None;
+        for blob in PbfReader::from_path(file)? {
+            if let BlobDecode::OsmHeader(header) = blob?.decode()? {
+                timestamp = header.osmosis_replication_timestamp();
+                break;
+            }
+        }
+        let Some(timestamp) = timestamp else {
+            bail!(
+                "{} has no replication timestamp in its header",
+                file.display()
+            );
+        };
+        result = result.max(timestamp * 1000);
+    }
+    Ok(result)
+}
+
+/// Get the latest timestamp of the elements converted with the default options, in milliseconds.
+/// Node locations are not cached, as they do not change the timestamps.
+fn elements_timestamp(input_files: &[PathBuf]) -> anyhow::Result<i64> {
+    let opts = ParserOpts::default();
+    let pre_pass = PrePass::default();
+    let cache = HashMapCache::new();
+    let stats = Mutex::new(Stats::default());
+    let mut result = 0;
+    for file in input_files {
+        let newest = PbfReader::from_path(file)?
+            .par_bridge()
+            .map(|blob| {
+                let mut newest = 0;
+                if let BlobDecode::OsmData(block) = blob?.decode()? {
+                    let mut parser =
+                        Parser::new(&stats, cache.get_accessor(), 1024, &opts, &pre_pass);
+                    parser.cache_locations = false;
+                    parser.parse_block(block, |batch| {
+                        for statement in batch {
+                            if let Statement::Create { ts, .. } = statement {
+                                newest = newest.max(ts);
+                            }
+                        }
+                    });
+                }
+                Ok(newest)
+            })
+            .try_reduce(|| 0, |a, b| Ok(a.max(b)))
+            .map_err(|err: osmpbf::Error| {
+                anyhow::anyhow!("Unable to read {}: {err}", file.display())
+            })?;
+        result = result.max(newest);
+    }
+    Ok(result)
+}
+
+/// Index of the next `osm-NNNNNN` file in a directory with the output of previous runs
+fn next_file_index(output_dir: &Path) -> anyhow::Result<u32> {
+    let mut result = 0;
+    for entry in std::fs::read_dir(output_dir)? {
+        if let Some(index) = file_index(&entry?.file_name(), "osm-") {
+            result = result.max(index + 1);
+        }
+    }
+    Ok(result)
+}
+
+/// Index of a `<prefix>NNNNNN` output file, including its `.gzi` block index, e.g. 3 for `osm-000003.ttl.gz`
+fn file_index(name: &OsStr, prefix: &str) -> Option<u32> {
+    name.to_str()?
+        .strip_prefix(prefix)?
+        .split('.')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Name of the file in the output directory with the progress of the run, see `--resume`
+const STATE_FILE: &str = ".osm2rdf-state";
+
+/// Progress of a run saved at its last checkpoint, see `--resume`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResumeState {
+    input_files: Vec<PathBuf>,
+    /// Number of blobs of all input files, in order, whose statements are in the saved files
+    blobs: u64,
+    /// Index of the next `osm-NNNNNN` file
+    file_index: u32,
+    /// Index of the next `deletes-NNNNNN` file
+    delete_index: u32,
+    /// The latest timestamp of the saved elements, for the footer
+    newest_ts: i64,
+    /// The bounding box of the saved nodes, for the footer
+    bbox: Option<Bbox>,
+}
+
+impl ResumeState {
+    fn load(output_dir: &Path) -> anyhow::Result<Self> {
+        let path = output_dir.join(STATE_FILE);
+        let content = std::fs::read_to_string(&path).with_context(|| {
+            format!(
+                "Unable to read {}, it is saved by --checkpoint-interval, --checkpoint-every, or --stop-after",
+                path.display()
+            )
+        })?;
+        let value =
+            json::parse(&content).with_context(|| format!("Unable to parse {}", path.display()))?;
+        let number = |key: &str| {
+            value[key]
+                .as_i64()
+                .with_context(|| format!("{} has no `{key}` number", path.display()))
+        };
+        let bbox = match value["bbox"]
+            .members()
+            .map(|v| v.as_f64())
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(v) if v.len() == 4 => Some(Bbox {
+                min_lon: v[0],
+                min_lat: v[1],
+                max_lon: v[2],
+                max_lat: v[3],
+            }),
+            _ => None,
+        };
+        Ok(Self {
+            input_files: value["input_files"]
+                .members()
+                .filter_map(|v| v.as_str().map(PathBuf::from))
+                .collect(),
+            blobs: number("blobs")? as u64,
+            file_index: number("file_index")? as u32,
+            delete_index: number("delete_index")? as u32,
+            newest_ts: number("newest_ts")?,
+            bbox,
+        })
+    }
+
+    /// Replace the state file, so that it is complete even if the process is killed while saving it
+    fn save(&self, output_dir: &Path) -> std::io::Result<()> {
+        let value = json::object! {
+            input_files: self.input_files.iter().map(|v| v.to_string_lossy().to_string()).collect::<Vec<_>>(),
+            blobs: self.blobs,
+            file_index: self.file_index,
+            delete_index: self.delete_index,
+            newest_ts: self.newest_ts,
+            bbox: self.bbox.map(|v| vec![v.min_lon, v.min_lat, v.max_lon, v.max_lat]),
+        };
+        let path = output_dir.join(STATE_FILE);
+        let temp = output_dir.join(format!("{STATE_FILE}.tmp"));
+        std::fs::write(&temp, value.pretty(2))?;
+        std::fs::rename(temp, path)
+    }
+
+    /// Remove the output files written after the saved checkpoint, which have incomplete or duplicate elements
+    fn remove_later_files(&self, dir: &Path) -> anyhow::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let is_later = matches!(file_index(&name, "osm-"), Some(v) if v >= self.file_index)
+                || matches!(file_index(&name, "deletes-"), Some(v) if v >= self.delete_index);
+            if is_later {
+                info!(
+                    "Removing {}, written after the last checkpoint",
+                    entry.path().display()
+                );
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How the progress of the main pass is saved and resumed, see `--resume`
+struct Checkpoints {
+    input_files: Vec<PathBuf>,
+    /// How often the progress is saved, see `--checkpoint-interval`
+    interval: Option<Duration>,
+    /// Number of blobs between the saved progress, see `--checkpoint-every`
+    every: Option<u64>,
+    /// Number of blobs to parse before stopping, see `--stop-after`
+    stop_after: Option<u64>,
+    /// The saved progress of the resumed run, whose blobs are skipped
+    resumed: Option<ResumeState>,
+}
+
+impl Checkpoints {
+    /// Number of blobs already converted by the resumed run
+    fn skip(&self) -> u64 {
+        self.resumed.as_ref().map_or(0, |v| v.blobs)
+    }
+
+    /// The progress after the given number of blobs, with the file indexes and the timestamp set by the writer
+    fn state(&self, blobs: u64, bbox: Option<Bbox>) -> ResumeState {
+        let resumed = self.resumed.clone().unwrap_or_default();
+        ResumeState {
+            input_files: self.input_files.clone(),
+            blobs,
+            newest_ts: resumed.newest_ts,
+            bbox: resumed.bbox.into_iter().chain(bbox).reduce(Bbox::union),
+            ..ResumeState::default()
+        }
+    }
+}
+
+/// Write only the footer file of an existing dataset, see the `footer` command.
+/// Returns the `schema:dateModified` value of the footer.
+pub fn footer(opt: Args) -> anyhow::Result<DateTime<Utc>> {
+    let Command::Footer {
+        date_source,
+        osm_base_url,
+        compression,
+        input_files,
+        output_dir,
+    } = opt.cmd
+    else {
+        unreachable!()
+    };
+    let date_modified = match date_source {
+        DateSource::Elements => elements_timestamp(&input_files)?,
+        DateSource::Header => header_timestamp(&input_files)?,
+    };
+    let file_index = next_file_index(&output_dir)?;
+    let mut writer = WriterResult {
+        files: OutputFiles {
+            output_dir,
+            file_index: AtomicU32::new(file_index),
+            delete_index: AtomicU32::new(0),
+            format: OutputFormat::Turtle,
+            compression,
+            compression_level: None,
+            deflate_strategy: DeflateStrategy::Default,
+            key_namespaces: Vec::new(),
+            emit_index: false,
+            osm_base_url,
+            upsert: false,
+            converter: None,
+            shard_by_id: None,
+            prefixes: PrefixFile::default(),
+            file_ranges: None,
+        },
+        newest_ts: date_modified,
+        warnings: Warnings::default(),
+        socket: None,
+        socket_closed: false,
+        endpoints: None,
+    };
+    writer.write_footer(Some(date_modified), None, Crs::default());
+    Ok(Utc.timestamp_millis_opt(date_modified).unwrap())
+}
+
+/// Wait for the next batch of statements, or return an empty batch once the flush deadline is reached.
+/// Returns `None` when all senders are gone.
+fn recv_batch(
+    receiver: &Receiver<Vec<Statement>>,
+    flush_deadline: Option<Instant>,
+) -> Option<Vec<Statement>> {
+    let Some(deadline) = flush_deadline else {
+        return receiver.recv().ok();
+    };
+    match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        Ok(batch) => Some(batch),
+        Err(RecvTimeoutError::Timeout) => Some(Vec::new()),
+        Err(RecvTimeoutError::Disconnected) => None,
+    }
+}
+
+/// Install a Ctrl-C handler that stops reading the input instead of aborting, and return the flag it sets.
+/// The elements parsed so far are still written, the output files are finished, and the footer is written
+/// with the latest timestamp of the written elements. The resulting dataset is valid but incomplete.
+/// A second Ctrl-C exits immediately, leaving the output files unfinished.
+pub fn handle_interrupts() -> anyhow::Result<&'static AtomicBool> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+        warn!("Interrupted, finishing the output files. Press Ctrl-C again to exit immediately");
+    })?;
+    Ok(&INTERRUPTED)
+}
+
+pub fn parse(opt: Args) -> anyhow::Result<Stats> {
+    parse_interruptible(opt, &AtomicBool::new(false))
+}
+
+/// Run the `parse` command, stopping early once `interrupted` is set, see `handle_interrupts`
+pub fn parse_interruptible(opt: Args, interrupted: &AtomicBool) -> anyhow::Result<Stats> {
+    let start = Instant::now();
+    let Command::Parse {
+        workers,
+        blob_chunk,
+        batch_size,
+        queue_depth,
+        input_files,
+        output_dir,
+        max_file_size,
+        flush_interval,
+        io_threads,
+        date_source,
+        emit_index,
+        emit_file_index,
+        sorted_output,
+        osm_base_url,
+        upsert,
+        compression,
+        compression_level,
+        deflate_strategy,
+        socket,
+        sparql_endpoint,
+        sparql_retries,
+        two_pass,
+        shard_by_id,
+        prefixes,
+        stats_json,
+        progress,
+        dry_run,
+        checkpoint_interval,
+        checkpoint_every,
+        stop_after,
+        resume,
+        opts,
+    } = opt.cmd
+    else {
+        unreachable!()
+    };
+    if dry_run && socket.is_some() {
+        bail!("--dry-run cannot be used with --socket");
+    }
+    if socket.is_some() && (io_threads.get() > 1 || emit_index) {
+        bail!("--socket cannot be used with multiple --io-threads or --emit-index");
+    }
+    if socket.is_some() && emit_file_index {
+        bail!("--socket cannot be used with --emit-file-index");
+    }
+    if socket.is_some() && shard_by_id.is_some() {
+        bail!("--socket cannot be used with --shard-by-id");
+    }
+    if socket.is_some() && opts.format == OutputFormat::JsonLd {
+        bail!("--socket cannot be used with --format jsonld, which needs a context file next to the output files");
+    }
+    if upsert && opts.format != OutputFormat::Turtle {
+        bail!("--upsert can only be used with the turtle output format");
+    }
+    if !sparql_endpoint.is_empty()
+        && (socket.is_some()
+            || dry_run
+            || shard_by_id.is_some()
+            || emit_index
+            || emit_file_index
+            || io_threads.get() > 1
+            || opts.format != OutputFormat::Turtle)
+    {
+        bail!("--sparql-endpoint cannot be used with --socket, --dry-run, --shard-by-id, --emit-index, --emit-file-index, multiple --io-threads, or the other output formats");
+    }
+    if compression == OutputCompression::None && compression_level.is_some() {
+        bail!("--compression-level cannot be used with --compression none");
+    }
+    if compression != OutputCompression::Gzip && deflate_strategy != DeflateStrategy::Default {
+        bail!("--deflate-strategy can only be used with --compression gzip");
+    }
+    if sorted_output && flush_interval.is_some() {
+        bail!("--sorted-output cannot be used with --flush-interval, nothing is written until the input is parsed");
+    }
+    if sorted_output && io_threads.get() > 1 {
+        bail!("--sorted-output cannot be used with multiple --io-threads");
+    }
+    for (idx, rename) in opts.tag_rename.iter().enumerate() {
+        if opts.tag_rename[..idx].iter().any(|v| v.from == rename.from) {
+            bail!(
+                "Key `{}` is renamed more than once by --tag-rename",
+                rename.from
+            );
+        }
+    }
+    if opts.omit_dangling_members && !opts.validate_members {
+        bail!("--omit-dangling-members requires --validate-members");
+    }
+    if let Some(prefixes) = &prefixes {
+        for ns in &opts.key_namespace {
+            if prefixes
+                .prefixes
+                .iter()
+                .any(|(name, _)| *name == ns.namespace)
+            {
+                bail!(
+                    "Prefix `{}` is used by both --prefixes and --key-namespace",
+                    ns.namespace
+                );
+            }
+        }
+    }
+    // The standard input can only be read once, and its header is checked while parsing
+    if input_files.iter().any(is_stdin) {
+        if input_files.iter().filter(|v| is_stdin(v)).count() > 1 {
+            bail!("The standard input `-` can only be used once");
+        }
+        if two_pass || PrePass::is_needed(&opts) || date_source == DateSource::Header {
+            bail!("--two-pass, --date-source header, and the options requiring a pre-pass, e.g. --validate-members, cannot be used with the standard input");
+        }
+        if progress {
+            bail!("--progress cannot be used with the standard input, its size is not known");
+        }
+    }
+    for file in input_files.iter().filter(|v| !is_stdin(v)) {
+        PbfReader::check_header(file)?;
+    }
+    // Any number of megabytes is a multiple of the memory page size, but it may not fit the address space
+    let Some(cache_page_size) = opt
+        .cache_page_size
+        .checked_mul(1024 * 1024)
+        .and_then(|v| usize::try_from(v).ok())
+    else {
+        bail!(
+            "--cache-page-size {} MB is too large for this platform",
+            opt.cache_page_size
+        );
+    };
+    let is_checkpointed = resume
+        || checkpoint_interval.is_some()
+        || checkpoint_every.is_some()
+        || stop_after.is_some();
+    if is_checkpointed
+        && (socket.is_some()
+            || !sparql_endpoint.is_empty()
+            || dry_run
+            || sorted_output
+            || emit_file_index
+            || io_threads.get() > 1
+            || input_files.iter().any(is_stdin))
+    {
+        bail!("--resume, --checkpoint-interval, --checkpoint-every, and --stop-after cannot be used with --socket, --sparql-endpoint, --dry-run, --sorted-output, --emit-file-index, multiple --io-threads, or the standard input");
+    }
+    if resume && !two_pass && opt.planet_cache.is_none() && opt.small_cache.is_none() {
+        bail!("--resume requires --two-pass, --planet-cache, or --small-cache for the node locations of the skipped blobs");
+    }
+    let resumed = if resume {
+        let state = ResumeState::load(&output_dir)?;
+        if state.input_files != input_files {
+            bail!("--resume requires the same input files as the saved run");
+        }
+        info!(
+            "Resuming after {} blobs, starting with file {}",
+            state.blobs, state.file_index
+        );
+        Some(state)
+    } else {
+        None
+    };
 
     let is_multithreaded = matches!(workers, Some(v) if v > 0);
     if let Some(v) = workers {
-        rayon::ThreadPoolBuilder::new()
+        // The global pool can only be configured once, e.g. not by a second run in the same process
+        if let Err(err) = rayon::ThreadPoolBuilder::new()
             .thread_name(|i| format!("parser #{i}"))
             .num_threads(v)
             .build_global()
-            .unwrap();
+        {
+            warn!("Unable to configure the worker threads, using the existing ones: {err}");
+        }
     }
-    let (sender, receiver) = channel();
-    let writer_thread = start_writer_thread(&output_dir, max_file_size * 1024 * 1024, receiver);
+    let date_modified = match date_source {
+        DateSource::Elements => None,
+        DateSource::Header => Some(header_timestamp(&input_files)?),
+    };
+    let (sender, receiver) = sync_channel(queue_depth.get());
+    let mut files = OutputFiles {
+        output_dir,
+        file_index: AtomicU32::new(resumed.as_ref().map_or(0, |v| v.file_index)),
+        delete_index: AtomicU32::new(resumed.as_ref().map_or(0, |v| v.delete_index)),
+        format: opts.format,
+        compression,
+        compression_level,
+        deflate_strategy,
+        key_namespaces: opts.key_namespace.clone(),
+        emit_index,
+        osm_base_url,
+        // The statements are sent to the endpoints as SPARQL Update requests
+        upsert: upsert || !sparql_endpoint.is_empty(),
+        converter: None,
+        shard_by_id,
+        prefixes: prefixes.unwrap_or_default(),
+        file_ranges: emit_file_index.then(Mutex::default),
+    };
+    files.converter = match opts.format {
+        OutputFormat::NTriples => Some(Box::new(NTriples::new(files.declarations()))),
+        OutputFormat::JsonLd => Some(Box::new(JsonLd::new(files.declarations()))),
+        OutputFormat::Turtle | OutputFormat::Protobuf => None,
+    };
+    if !dry_run {
+        // The JSON-LD elements refer to the context file next to them
+        let context = (opts.format == OutputFormat::JsonLd)
+            .then(|| JsonLd::new(files.declarations()).context().pretty(2));
+        for shard in 0..files.shard_count() {
+            let dir = files.shard_dir(shard);
+            std::fs::create_dir_all(&dir)?;
+            if let Some(state) = &resumed {
+                state.remove_later_files(&dir)?;
+            }
+            if let Some(context) = &context {
+                std::fs::write(dir.join(CONTEXT_FILE), context)?;
+            }
+        }
+    }
+    let writer_thread = start_writer_thread(
+        files,
+        max_file_size * 1024 * 1024,
+        flush_interval.map(Duration::from_secs),
+        io_threads,
+        sorted_output.then_some(batch_size),
+        dry_run,
+        socket.as_deref().map(connect_socket).transpose()?,
+        (!sparql_endpoint.is_empty()).then(|| Endpoints::start(&sparql_endpoint, sparql_retries)),
+        receiver,
+    );
 
-    let reader = BlobReader::from_path(input_file)?;
-    let stats = if let Some(filename) = &opt.planet_cache {
+    let checkpoints = is_checkpointed.then(|| Checkpoints {
+        input_files: input_files.clone(),
+        interval: checkpoint_interval.map(Duration::from_secs),
+        every: checkpoint_every,
+        stop_after,
+        resumed: resumed.clone(),
+    });
+    let progress = Progress::new(progress, &input_files)?;
+    let readers = input_files
+        .iter()
+        .map(PbfReader::from_path)
+        .collect::<Result<Vec<_>, _>>()?;
+    let pre_pass = if PrePass::is_needed(&opts) {
+        info!("Running a pre-pass over all input files");
+        let readers = input_files
+            .iter()
+            .map(PbfReader::from_path)
+            .collect::<Result<Vec<_>, _>>()?;
+        run_pre_pass(readers, is_multithreaded, &opts, interrupted)
+            .context("Unable to read the input files in the pre-pass")?
+    } else {
+        PrePass::default()
+    };
+    let first_pass = if two_pass {
+        let readers = input_files
+            .iter()
+            .map(PbfReader::from_path)
+            .collect::<Result<Vec<_>, _>>()?;
+        Some(readers)
+    } else {
+        None
+    };
+    let mut stats = if let Some(filename) = &opt.planet_cache {
         info!("Creating dense cache in {:?}", filename.display());
-        let cache = create_flat_cache(filename.clone())?;
-        run_with_cache(cache, sender, reader, is_multithreaded)
+        let cache = create_flat_cache(filename.clone(), cache_page_size)?;
+        run_with_cache(
+            cache,
+            sender,
+            readers,
+            first_pass,
+            is_multithreaded,
+            blob_chunk,
+            batch_size,
+            &opts,
+            &pre_pass,
+            &progress,
+            interrupted,
+            checkpoints.as_ref(),
+            false,
+        )?
     } else {
         let cache = if let Some(filename) = &opt.small_cache {
             if filename.exists() {
@@ -353,7 +2410,21 @@ pub fn parse(opt: Args) -> anyhow::Result<Stats> {
             HashMapCache::new()
         };
 
-        let stats = run_with_cache(cache.clone(), sender, reader, is_multithreaded);
+        let stats = run_with_cache(
+            cache.clone(),
+            sender,
+            readers,
+            first_pass,
+            is_multithreaded,
+            blob_chunk,
+            batch_size,
+            &opts,
+            &pre_pass,
+            &progress,
+            interrupted,
+            checkpoints.as_ref(),
+            true,
+        )?;
 
         if let Some(filename) = &opt.small_cache {
             info!("Saving sparse cache to {:?}", filename.display());
@@ -362,41 +2433,399 @@ pub fn parse(opt: Args) -> anyhow::Result<Stats> {
 
         stats
     };
+    if interrupted.load(Ordering::Relaxed) {
+        warn!("The run was interrupted, the output only has the elements parsed so far");
+        stats.warnings.add(Warning::Interrupted, 1);
+    }
 
-    writer_thread.join().unwrap();
+    // The statistics are also written if finishing the output fails
+    let result = (|| {
+        let mut writer = writer_thread.join().unwrap();
+        if dry_run {
+            let ts = XsdDateTime(
+                date_modified.unwrap_or(writer.newest_ts),
+                TimestampFormat::Iso,
+            );
+            info!(
+                "Dry run finished without writing any files, the dataset would be modified at {ts}"
+            );
+            return Ok(());
+        }
+        // The footer also covers the elements written by the resumed run
+        if let Some(state) = &resumed {
+            writer.newest_ts = writer.newest_ts.max(state.newest_ts);
+            stats.bbox = state.bbox.into_iter().chain(stats.bbox).reduce(Bbox::union);
+        }
+        writer.write_footer(date_modified, stats.bbox, opts.wkt_crs());
+        writer.files.write_file_index()?;
+        // Once all blobs are parsed, there is nothing left to resume
+        let state_file = writer.files.output_dir.join(STATE_FILE);
+        if !interrupted.load(Ordering::Relaxed) && state_file.exists() {
+            std::fs::remove_file(state_file)?;
+        }
+        stats.warnings.combine(writer.warnings);
+        if opts.emit_issues {
+            write_issues(&writer.files.output_dir, &mut stats.issues)?;
+        }
+        if writer.socket_closed {
+            bail!("The socket was closed before all statements were written");
+        }
+        if let Some(endpoints) = writer.endpoints.take() {
+            stats.endpoints = endpoints.finish();
+            let failed: Vec<_> = stats
+                .endpoints
+                .iter()
+                .filter(|v| v.error.is_some())
+                .map(|v| v.url.as_str())
+                .collect();
+            if !failed.is_empty() {
+                bail!(
+                    "{} of {} SPARQL endpoints did not receive all requests: {}",
+                    failed.len(),
+                    stats.endpoints.len(),
+                    failed.join(", ")
+                );
+            }
+        }
+        Ok(())
+    })();
+    if let Some(path) = &stats_json {
+        let mut json = stats.to_json(start.elapsed());
+        if let Err(err) = &result {
+            json["error"] = err.to_string().into();
+        }
+        info!("Writing run statistics to {}", path.display());
+        std::fs::write(path, json.pretty(2))
+            .with_context(|| format!("Unable to write {}", path.display()))?;
+    }
+    result?;
+    if let Some(id_list) = &opts.id_list {
+        let missing = id_list.missing(&stats.listed_found);
+        if !missing.is_empty() {
+            let ids: Vec<_> = missing
+                .iter()
+                .map(|(elem, id)| format!("{elem}:{id}"))
+                .collect();
+            warn!(
+                "{} of {} listed elements were not found: {}",
+                missing.len(),
+                id_list.len(),
+                ids.join(", ")
+            );
+            stats
+                .warnings
+                .add(Warning::ListedMissing, missing.len() as u64);
+        }
+    }
+    if !stats.warnings.is_empty() {
+        warn!("Warnings summary: {}", stats.warnings);
+    }
     Ok(stats)
 }
 
-fn run_with_cache<R: Read + Send, C: CacheStore + Clone + Send>(
+/// Write all collected issues sorted by element to the `issues.json` file
+fn write_issues(output_dir: &Path, issues: &mut [Issue]) -> anyhow::Result<()> {
+    issues.sort_by_key(|v| (v.elem, v.id, v.kind));
+    let issues: Vec<_> = issues
+        .iter()
+        .map(|v| {
+            let mut issue = json::object! {
+                element: format!("{}:{}", v.elem, v.id),
+                kind: v.kind.to_string(),
+            };
+            if let Some(value) = &v.value {
+                issue["value"] = value.as_str().into();
+            }
+            issue
+        })
+        .collect();
+    let path = output_dir.join("issues.json");
+    info!("Writing {} issues to {}", issues.len(), path.display());
+    std::fs::write(path, json::JsonValue::from(issues).pretty(2))?;
+    Ok(())
+}
+
+fn run_pre_pass(
+    readers: Vec<PbfReader>,
+    is_multithreaded: bool,
+    opts: &ParserOpts,
+    interrupted: &AtomicBool,
+) -> Result<PrePass, osmpbf::Error> {
+    let mut result = PrePass::default();
+    for reader in readers {
+        let reader = reader.take_while(|_| !interrupted.load(Ordering::Relaxed));
+        let pre_pass = if is_multithreaded {
+            reader
+                .par_bridge()
+                .map(|blob| PrePass::from_blob(opts, blob))
+                .try_reduce(PrePass::default, |a, b| Ok(a.combine(b)))?
+        } else {
+            reader
+                .map(|blob| PrePass::from_blob(opts, blob))
+                .try_fold(PrePass::default(), |a, b| {
+                    Ok::<_, osmpbf::Error>(a.combine(b?))
+                })?
+        };
+        result = result.combine(pre_pass);
+    }
+    result.retain_route_ways();
+    result.retain_present_members();
+    Ok(result)
+}
+
+/// Process all readers in order, sharing the same node cache,
+/// so that the nodes of one file can be used by the ways of the next one.
+/// With `first_pass` readers, all node locations are cached before any statements are generated.
+#[allow(clippy::too_many_arguments)]
+fn run_with_cache<C: CacheStore + Clone + Send>(
     mut cache: C,
-    mut sender: Sender<Vec<Statement>>,
-    reader: BlobReader<R>,
+    mut sender: SyncSender<Vec<Statement>>,
+    readers: Vec<PbfReader>,
+    first_pass: Option<Vec<PbfReader>>,
     is_multithreaded: bool,
-) -> Stats {
+    blob_chunk: NonZeroUsize,
+    batch_size: NonZeroUsize,
+    opts: &ParserOpts,
+    pre_pass: &PrePass,
+    progress: &Progress,
+    interrupted: &AtomicBool,
+    checkpoints: Option<&Checkpoints>,
+    report_cache_size: bool,
+) -> anyhow::Result<Stats> {
     let stats = Mutex::new(Stats::default());
-    if is_multithreaded {
-        reader
-            .par_bridge()
-            .for_each_with((cache, sender), |(dfc, sender), blob| {
-                run_block(&stats, dfc, sender, blob);
-            });
-    } else {
+    if !is_multithreaded {
         info!("Running in single-threaded mode");
-        for blob in reader {
-            run_block(&stats, &mut cache, &mut sender, blob);
+    }
+    // The dense cache logs its own growth, the in-memory cache size is reported periodically instead
+    let next_report = Mutex::new(Instant::now() + CACHE_REPORT_INTERVAL);
+    let report = |offset: Option<u64>| {
+        progress.add_blob(offset, &stats);
+        if report_cache_size {
+            report_cache_size_every(&stats, &next_report);
         }
+    };
+    let pass = if let Some(first_pass) = first_pass {
+        info!("Caching the node locations of all input files");
+        progress.start_pass("Caching nodes");
+        for_each_blob(
+            &mut cache,
+            &mut sender,
+            first_pass,
+            is_multithreaded,
+            blob_chunk,
+            progress,
+            interrupted,
+            None,
+            |dfc, sender, blob| {
+                let offset = blob_offset(&blob);
+                run_block(
+                    &stats,
+                    dfc,
+                    sender,
+                    blob,
+                    batch_size,
+                    opts,
+                    pre_pass,
+                    Pass::CacheNodes,
+                )?;
+                report(offset);
+                Ok(())
+            },
+            |_, _| {},
+        )?;
+        info!("Generating the statements");
+        Pass::Statements
+    } else {
+        Pass::Single
+    };
+    progress.start_pass("Parsing");
+    for_each_blob(
+        &mut cache,
+        &mut sender,
+        readers,
+        is_multithreaded,
+        blob_chunk,
+        progress,
+        interrupted,
+        checkpoints,
+        |dfc, sender, blob| {
+            let offset = blob_offset(&blob);
+            run_block(&stats, dfc, sender, blob, batch_size, opts, pre_pass, pass)?;
+            report(offset);
+            Ok(())
+        },
+        |sender, blobs| {
+            if let Some(checkpoints) = checkpoints {
+                let state = checkpoints.state(blobs, stats.lock().unwrap().bbox);
+                sender.send(vec![Statement::Checkpoint(state)]).unwrap();
+            }
+        },
+    )?;
+    let stats = stats.into_inner().unwrap();
+    progress.finish(&stats);
+    if report_cache_size {
+        log_cache_size(stats.cached_nodes);
     }
-    stats.into_inner().unwrap()
+    Ok(stats)
 }
 
+/// Process the blobs of all readers in order, or in parallel with multithreading.
+/// With `checkpoints`, the blobs of the resumed run are skipped, and `checkpoint` is called with the number
+/// of processed blobs once all of them are done, periodically and when the run is interrupted.
+#[allow(clippy::too_many_arguments)]
+fn for_each_blob<C: CacheStore + Clone + Send>(
+    cache: &mut C,
+    sender: &mut SyncSender<Vec<Statement>>,
+    readers: Vec<PbfReader>,
+    is_multithreaded: bool,
+    blob_chunk: NonZeroUsize,
+    progress: &Progress,
+    interrupted: &AtomicBool,
+    checkpoints: Option<&Checkpoints>,
+    process: impl Fn(
+            &mut C,
+            &mut SyncSender<Vec<Statement>>,
+            Result<Blob, osmpbf::Error>,
+        ) -> anyhow::Result<()>
+        + Send
+        + Sync,
+    checkpoint: impl Fn(&mut SyncSender<Vec<Statement>>, u64),
+) -> anyhow::Result<()> {
+    let skip = checkpoints.map_or(0, Checkpoints::skip);
+    let stop_at = checkpoints.and_then(|v| v.stop_after).map(|v| skip + v);
+    let interval = checkpoints.and_then(|v| v.interval);
+    let every = checkpoints.and_then(|v| v.every);
+    let chunk_len = if is_multithreaded {
+        blob_chunk.get()
+    } else {
+        1
+    };
+    // Number of blobs read from all readers, including the skipped ones
+    let mut position = 0;
+    for mut reader in readers {
+        if interrupted.load(Ordering::Relaxed) {
+            break;
+        }
+        progress.start_file();
+        if position < skip {
+            // The blobs converted by the resumed run are read, but not decoded
+            position += reader.by_ref().take((skip - position) as usize).count() as u64;
+        }
+        let mut is_done = false;
+        while !is_done {
+            let deadline = interval.map(|v| Instant::now() + v);
+            // The checkpoints are at the multiples of `--checkpoint-every`, counting the skipped blobs
+            let checkpoint_at = every.map(|v| (position / v + 1) * v);
+            // Group consecutive blobs, so that each parallel task processes several of them.
+            // Once interrupted, no more blobs are read, and the tasks already started are completed.
+            // The blobs are also no longer read at the next checkpoint, until all the read ones are done.
+            let next_chunk = || {
+                if interrupted.load(Ordering::Relaxed)
+                    || deadline.is_some_and(|v| Instant::now() >= v)
+                    || checkpoint_at.is_some_and(|v| position >= v)
+                {
+                    return None;
+                }
+                let mut len = chunk_len;
+                if let Some(stop_at) = stop_at {
+                    if position >= stop_at {
+                        info!("Stopping after {position} blobs, continue the run with --resume");
+                        interrupted.store(true, Ordering::Relaxed);
+                        return None;
+                    }
+                    len = len.min((stop_at - position) as usize);
+                }
+                if let Some(checkpoint_at) = checkpoint_at {
+                    len = len.min((checkpoint_at - position) as usize);
+                }
+                let chunk: Vec<_> = reader.by_ref().take(len).collect();
+                position += chunk.len() as u64;
+                is_done = chunk.is_empty();
+                (!is_done).then_some(chunk)
+            };
+            if is_multithreaded {
+                iter::from_fn(next_chunk).par_bridge().try_for_each_with(
+                    (cache.clone(), sender.clone()),
+                    |(dfc, sender), blobs| {
+                        blobs
+                            .into_iter()
+                            .try_for_each(|blob| process(dfc, sender, blob))
+                    },
+                )?;
+            } else {
+                for blob in iter::from_fn(next_chunk).flatten() {
+                    process(cache, sender, blob)?;
+                }
+            }
+            if interrupted.load(Ordering::Relaxed) {
+                break;
+            }
+            if !is_done {
+                checkpoint(sender, position);
+            }
+        }
+    }
+    // Nothing is read before an interruption in an earlier pass, so the resumed blobs are still done
+    if checkpoints.is_some() && interrupted.load(Ordering::Relaxed) {
+        checkpoint(sender, position.max(skip));
+    }
+    Ok(())
+}
+
+/// Position of the blob in its input file, see `--progress`
+fn blob_offset(blob: &Result<Blob, osmpbf::Error>) -> Option<u64> {
+    blob.as_ref().ok()?.offset().map(|v| v.0)
+}
+
+/// Log the in-memory node cache size, unless it was already logged by another thread within the interval
+fn report_cache_size_every(stats: &Mutex<Stats>, next_report: &Mutex<Instant>) {
+    let Ok(mut next) = next_report.try_lock() else {
+        return;
+    };
+    if Instant::now() < *next {
+        return;
+    }
+    *next = Instant::now() + CACHE_REPORT_INTERVAL;
+    log_cache_size(stats.lock().unwrap().cached_nodes);
+}
+
+fn log_cache_size(cached_nodes: u64) {
+    info!(
+        "Node cache has {cached_nodes} locations, using about {} of memory",
+        ByteSize(cached_nodes * CACHE_ENTRY_SIZE)
+    );
+}
+
+/// The work done for each block, see `--two-pass`
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pass {
+    /// Cache the node locations and generate the statements
+    Single,
+    /// Only cache the node locations
+    CacheNodes,
+    /// Generate the statements using the node locations cached by the `CacheNodes` pass
+    Statements,
+}
+
+#[allow(clippy::too_many_arguments)]
 fn run_block<C: CacheStore + Clone + Send>(
     stats: &Mutex<Stats>,
     dfc: &mut C,
-    sender: &mut Sender<Vec<Statement>>,
+    sender: &mut SyncSender<Vec<Statement>>,
     blob: Result<Blob, osmpbf::Error>,
-) {
-    if let BlobDecode::OsmData(block) = blob.unwrap().decode().unwrap() {
-        let mut parser = Parser::new(stats, dfc.get_accessor(), 1024);
-        parser.parse_block(block, |s| sender.send(s).unwrap());
+    batch_size: NonZeroUsize,
+    opts: &ParserOpts,
+    pre_pass: &PrePass,
+    pass: Pass,
+) -> Result<(), osmpbf::Error> {
+    if let BlobDecode::OsmData(block) = blob?.decode()? {
+        let mut parser = Parser::new(stats, dfc.get_accessor(), batch_size.get(), opts, pre_pass);
+        parser.cache_locations = pass != Pass::Statements;
+        if pass == Pass::CacheNodes {
+            parser.cache_block(block);
+        } else {
+            parser.parse_block(block, |s| sender.send(s).unwrap());
+        }
     }
+    Ok(())
 }
@@ -0,0 +1,158 @@
+use std::io::IsTerminal as _;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use bytesize::ByteSize;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use lazy_static::lazy_static;
+use log::{info, Log, Metadata, Record, SetLoggerError};
+
+use crate::utils::Stats;
+
+/// How often to log the progress when the standard error is not a terminal
+const PROGRESS_LOG_INTERVAL: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    /// Progress bars of the current run, hidden while the log lines are written
+    static ref BARS: MultiProgress = MultiProgress::new();
+}
+
+/// Logger writing each line above the progress bar instead of overwriting it
+struct SuspendingLogger(env_logger::Logger);
+
+impl Log for SuspendingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.0.matches(record) {
+            BARS.suspend(|| self.0.log(record));
+        }
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+/// Install the logger, keeping its output apart from the `--progress` bar
+pub fn init_logger(logger: env_logger::Logger) -> Result<(), SetLoggerError> {
+    log::set_max_level(logger.filter());
+    log::set_boxed_logger(Box::new(SuspendingLogger(logger)))
+}
+
+/// Progress of reading the input files, see `--progress`. Shows a progress bar with the processed blobs,
+/// elements, and bytes read, or logs the same values periodically if the standard error is not a terminal.
+#[derive(Default)]
+pub struct Progress {
+    is_enabled: bool,
+    bar: Option<ProgressBar>,
+    /// Sizes of all input files, in the processing order
+    file_sizes: Vec<u64>,
+    /// Index of the file being read plus one, or 0 before the first one
+    file_index: AtomicUsize,
+    /// Highest offset of a processed blob in the current file
+    offset: AtomicU64,
+    blobs: AtomicU64,
+    next_log: Mutex<Option<Instant>>,
+}
+
+impl Progress {
+    /// Create a disabled progress, or one measuring the reading of the input files
+    pub fn new(is_enabled: bool, input_files: &[PathBuf]) -> std::io::Result<Self> {
+        if !is_enabled {
+            return Ok(Self::default());
+        }
+        let file_sizes = input_files
+            .iter()
+            .map(|v| v.metadata().map(|v| v.len()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let bar = std::io::stderr().is_terminal().then(|| {
+            let style = ProgressStyle::with_template(
+                "{prefix} [{elapsed_precise}] {wide_bar} {bytes}/{total_bytes} (eta {eta}) {msg}",
+            )
+            .unwrap();
+            BARS.add(ProgressBar::new(file_sizes.iter().sum()).with_style(style))
+        });
+        Ok(Self {
+            is_enabled,
+            bar,
+            file_sizes,
+            ..Self::default()
+        })
+    }
+
+    /// Start reading the input files from the beginning, e.g. for the second pass of `--two-pass`
+    pub fn start_pass(&self, name: &str) {
+        self.file_index.store(0, Ordering::Relaxed);
+        self.offset.store(0, Ordering::Relaxed);
+        self.blobs.store(0, Ordering::Relaxed);
+        if let Some(bar) = &self.bar {
+            bar.set_prefix(name.to_string());
+            bar.reset();
+        }
+        *self.next_log.lock().unwrap() = Some(Instant::now() + PROGRESS_LOG_INTERVAL);
+    }
+
+    /// Start reading the next input file
+    pub fn start_file(&self) {
+        self.file_index.fetch_add(1, Ordering::Relaxed);
+        self.offset.store(0, Ordering::Relaxed);
+    }
+
+    /// Record a processed blob at the given offset of the current file
+    pub fn add_blob(&self, offset: Option<u64>, stats: &Mutex<Stats>) {
+        if !self.is_enabled {
+            return;
+        }
+        let blobs = self.blobs.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(offset) = offset {
+            self.offset.fetch_max(offset, Ordering::Relaxed);
+        }
+        if let Some(bar) = &self.bar {
+            let elements = stats.lock().unwrap().elements();
+            bar.set_position(self.position());
+            bar.set_message(format!("{blobs} blobs, {elements} elements"));
+        } else if let Ok(mut next_log) = self.next_log.try_lock() {
+            if next_log.is_some_and(|v| Instant::now() >= v) {
+                *next_log = Some(Instant::now() + PROGRESS_LOG_INTERVAL);
+                self.log(&stats.lock().unwrap(), self.position());
+            }
+        }
+    }
+
+    /// Remove the progress bar, and log the final totals
+    pub fn finish(&self, stats: &Stats) {
+        if !self.is_enabled {
+            return;
+        }
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+            BARS.remove(bar);
+        }
+        self.log(stats, self.file_sizes.iter().sum());
+    }
+
+    /// Number of bytes read from all input files
+    fn position(&self) -> u64 {
+        let index = self.file_index.load(Ordering::Relaxed).max(1) - 1;
+        let previous: u64 = self.file_sizes.iter().take(index).sum();
+        previous + self.offset.load(Ordering::Relaxed)
+    }
+
+    fn log(&self, stats: &Stats, position: u64) {
+        let added = stats.added_nodes + stats.added_ways + stats.added_rels;
+        let deleted = stats.deleted_nodes + stats.deleted_ways + stats.deleted_rels;
+        info!(
+            "Processed {} blobs, {} elements ({added} added, {} skipped, {deleted} deleted), read {} of {}",
+            self.blobs.load(Ordering::Relaxed),
+            stats.elements(),
+            stats.elements() - added - deleted,
+            ByteSize(position),
+            ByteSize(self.file_sizes.iter().sum()),
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! Protobuf messages for the `--format protobuf` output, see `proto/osm2rdf.proto` for the schema.
+
+use osmpbf::{RelMember, RelMemberType};
+use prost::{Enumeration, Message};
+
+use crate::utils::{self, ElementInfo};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Enumeration)]
+#[repr(i32)]
+pub enum ElementType {
+    Node = 0,
+    Way = 1,
+    Relation = 2,
+}
+
+impl From<&utils::Element> for ElementType {
+    fn from(elem: &utils::Element) -> Self {
+        match elem {
+            utils::Element::Node => ElementType::Node,
+            utils::Element::Way => ElementType::Way,
+            utils::Element::Relation => ElementType::Relation,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Element {
+    #[prost(enumeration = "ElementType", tag = "1")]
+    pub r#type: i32,
+    #[prost(int64, tag = "2")]
+    pub id: i64,
+    #[prost(message, repeated, tag = "3")]
+    pub tags: Vec<Tag>,
+    #[prost(message, optional, tag = "4")]
+    pub loc: Option<Point>,
+    #[prost(bool, optional, tag = "5")]
+    pub is_closed: Option<bool>,
+    #[prost(message, repeated, tag = "6")]
+    pub members: Vec<Member>,
+    #[prost(message, optional, tag = "7")]
+    pub info: Option<Info>,
+    #[prost(message, repeated, tag = "8")]
+    pub geometry: Vec<Point>,
+}
+
+impl Element {
+    pub fn new(elem: &utils::Element, id: i64, tags: Vec<Tag>, info: &ElementInfo) -> Self {
+        Self {
+            r#type: ElementType::from(elem) as i32,
+            id,
+            tags,
+            info: Some(Info {
+                version: info.version.unwrap_or_default(),
+                user: info.user.map(ToString::to_string),
+                timestamp: info.milli_timestamp.unwrap_or_default(),
+                changeset: info.changeset.unwrap_or_default(),
+            }),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Tag {
+    #[prost(string, tag = "1")]
+    pub key: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+}
+
+/// Convert tags to messages, skipping `created_by` just like the Turtle output
+pub fn tags<'t>(tags: impl Iterator<Item = (&'t str, &'t str)>) -> Vec<Tag> {
+    tags.filter(|(key, _)| *key != "created_by")
+        .map(|(key, value)| Tag {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+        .collect()
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Point {
+    #[prost(double, tag = "1")]
+    pub lat: f64,
+    #[prost(double, tag = "2")]
+    pub lon: f64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Member {
+    #[prost(enumeration = "ElementType", tag = "1")]
+    pub r#type: i32,
+    #[prost(int64, tag = "2")]
+    pub id: i64,
+    #[prost(string, tag = "3")]
+    pub role: String,
+}
+
+impl From<&RelMember<'_>> for Member {
+    fn from(mbr: &RelMember<'_>) -> Self {
+        let member_type = match mbr.member_type {
+            RelMemberType::Node => ElementType::Node,
+            RelMemberType::Way => ElementType::Way,
+            RelMemberType::Relation => ElementType::Relation,
+        };
+        Self {
+            r#type: member_type as i32,
+            id: mbr.member_id,
+            role: mbr.role().unwrap().to_string(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Info {
+    #[prost(int32, tag = "1")]
+    pub version: i32,
+    #[prost(string, optional, tag = "2")]
+    pub user: Option<String>,
+    #[prost(int64, tag = "3")]
+    pub timestamp: i64,
+    #[prost(int64, tag = "4")]
+    pub changeset: i64,
+}
@@ -0,0 +1,165 @@
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::Arc;
+use std::thread::{sleep, Builder, JoinHandle};
+use std::time::Duration;
+
+use anyhow::bail;
+use log::{info, warn};
+
+/// Number of requests waiting to be sent to each endpoint. A slower endpoint holds back the others
+/// once its queue is full, so that the statements are not buffered in memory without a limit.
+const QUEUE_DEPTH: usize = 4;
+
+/// Delay before the first retry of a failed request, doubled for each next one
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Time allowed to connect to an endpoint before the attempt fails
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Check that the `--sparql-endpoint` value is an HTTP URL
+pub fn parse_endpoint_url(value: &str) -> anyhow::Result<String> {
+    if !value.starts_with("http://") && !value.starts_with("https://") {
+        bail!("SPARQL endpoint `{value}` must start with http:// or https://")
+    }
+    Ok(value.to_string())
+}
+
+/// Requests sent to one `--sparql-endpoint`, reported at the end of the run
+#[derive(Debug, Clone, Default)]
+pub struct EndpointStats {
+    pub url: String,
+    /// Number of requests accepted by the endpoint
+    pub requests: u64,
+    /// Number of failed attempts that were retried
+    pub retries: u64,
+    /// Reason the endpoint was given up, after which it received no more requests
+    pub error: Option<String>,
+}
+
+impl EndpointStats {
+    pub fn to_json(&self) -> json::JsonValue {
+        json::object! {
+            url: self.url.clone(),
+            requests: self.requests,
+            retries: self.retries,
+            error: self.error.clone(),
+        }
+    }
+}
+
+/// SPARQL Update endpoints receiving the same requests, see `--sparql-endpoint`.
+/// Each endpoint has its own thread, so a retried request only delays the other endpoints
+/// once their queue is full. An endpoint failing all retries receives no more requests.
+pub struct Endpoints {
+    senders: Vec<SyncSender<Arc<Vec<u8>>>>,
+    threads: Vec<JoinHandle<EndpointStats>>,
+}
+
+impl Endpoints {
+    /// Start a sending thread for each endpoint URL, retrying each failed request up to `retries` times
+    pub fn start(urls: &[String], retries: u32) -> Self {
+        let (senders, threads) = urls
+            .iter()
+            .enumerate()
+            .map(|(i, url)| {
+                let (sender, receiver) = sync_channel::<Arc<Vec<u8>>>(QUEUE_DEPTH);
+                let url = url.clone();
+                let thread = Builder::new()
+                    .name(format!("sparql #{i}"))
+                    .spawn(move || {
+                        let agent = ureq::AgentBuilder::new()
+                            .timeout_connect(CONNECT_TIMEOUT)
+                            .build();
+                        let mut stats = EndpointStats {
+                            url,
+                            ..EndpointStats::default()
+                        };
+                        // Once the endpoint fails, the receiver is dropped, and no more requests are queued
+                        for body in receiver {
+                            if let Err(err) = post(&agent, &body, retries, &mut stats) {
+                                warn!(
+                                    "Giving up SPARQL endpoint {}, it receives no more requests: {err}",
+                                    stats.url
+                                );
+                                stats.error = Some(err);
+                                break;
+                            }
+                        }
+                        stats
+                    })
+                    .unwrap();
+                (sender, thread)
+            })
+            .unzip();
+        Self { senders, threads }
+    }
+
+    /// Queue a request for all endpoints that have not failed yet
+    pub fn send(&self, body: Vec<u8>) {
+        let body = Arc::new(body);
+        for sender in &self.senders {
+            // A failed endpoint no longer receives, its requests are discarded
+            let _ = sender.send(body.clone());
+        }
+    }
+
+    /// Wait until all queued requests are sent, and log the requests sent to each endpoint
+    pub fn finish(self) -> Vec<EndpointStats> {
+        drop(self.senders);
+        let stats: Vec<_> = self
+            .threads
+            .into_iter()
+            .map(|v| v.join().unwrap())
+            .collect();
+        for endpoint in &stats {
+            let EndpointStats {
+                url,
+                requests,
+                retries,
+                error,
+            } = endpoint;
+            match error {
+                None => info!("Sent {requests} requests to {url}, with {retries} retries"),
+                Some(err) => warn!("Sent {requests} requests to {url} before it failed: {err}"),
+            }
+        }
+        stats
+    }
+}
+
+/// Post a SPARQL Update request, retrying the connection errors and the server errors.
+/// Other errors, e.g. a request rejected by the endpoint, are not retried.
+fn post(
+    agent: &ureq::Agent,
+    body: &[u8],
+    retries: u32,
+    stats: &mut EndpointStats,
+) -> Result<(), String> {
+    let mut delay = RETRY_DELAY;
+    let mut attempts = 0;
+    loop {
+        let result = agent
+            .post(&stats.url)
+            .set("Content-Type", "application/sparql-update; charset=utf-8")
+            .send_bytes(body);
+        let err = match result {
+            Ok(_) => {
+                stats.requests += 1;
+                return Ok(());
+            }
+            Err(ureq::Error::Status(status, response)) if status < 500 => {
+                let text = response.into_string().unwrap_or_default();
+                return Err(format!("status {status}: {}", text.trim()));
+            }
+            Err(err) => err,
+        };
+        if attempts == retries {
+            return Err(err.to_string());
+        }
+        warn!("Retrying the request to {} in {delay:?}: {err}", stats.url);
+        attempts += 1;
+        stats.retries += 1;
+        sleep(delay);
+        delay *= 2;
+    }
+}
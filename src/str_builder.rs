@@ -1,14 +1,18 @@
 use std::fmt::{Debug, Display, Write as _};
 use std::ops::{Deref, DerefMut};
 
-use chrono::{TimeZone as _, Utc};
+use chrono::{NaiveDate, TimeZone as _, Utc};
 use json::JsonValue;
 use lazy_static::lazy_static;
 use osmpbf::{RelMember, RelMemberType};
 use percent_encoding::utf8_percent_encode;
 use regex::Regex;
 
-use crate::utils::{Element, ElementInfo, PERCENT_ENC_SET};
+use crate::geometry::to_web_mercator;
+use crate::tag_schema::{TagType, DEFAULT_SCHEMA};
+use crate::turtle::{Serializer, Turtle};
+use crate::utils::{Bbox, Element, ElementInfo, IssueKind, PERCENT_ENC_SET};
+use crate::{vocab, Crs, ParserOpts, Section, TimestampFormat};
 
 lazy_static! {
     /// Total length of the maximum "valid" local name is 60 (58 + first + last char)
@@ -18,11 +22,28 @@ lazy_static! {
     pub static ref RE_WIKIDATA_VALUE: Regex = Regex::new(r"^Q[1-9][0-9]{0,18}$").unwrap();
     pub static ref RE_WIKIDATA_MULTI_VALUE: Regex = Regex::new(r"^Q[1-9][0-9]{0,18}(\s*;\s*Q[1-9][0-9]{0,18})+$").unwrap();
     pub static ref RE_WIKIPEDIA_VALUE: Regex = Regex::new(r"^([-a-z]+):(.+)$").unwrap();
+    /// Commons page title, without the characters that are invalid in MediaWiki titles or are not encoded in IRIs
+    pub static ref RE_COMMONS_VALUE: Regex = Regex::new(r#"^(Category|File):([^#<>\[\]|{}"\\^`]+)$"#).unwrap();
+    pub static ref RE_INTEGER_VALUE: Regex = Regex::new(r"^-?(0|[1-9][0-9]{0,17})$").unwrap();
+    pub static ref RE_DECIMAL_VALUE: Regex = Regex::new(r"^-?[0-9]+(\.[0-9]+)?$").unwrap();
+    pub static ref RE_DATE_VALUE: Regex = Regex::new(r"^[0-9]{4}-[0-9]{2}-[0-9]{2}$").unwrap();
+    pub static ref RE_LANGUAGE: Regex = Regex::new(r"^[a-z]{2,3}(-[a-zA-Z0-9]{1,8})*$").unwrap();
+    pub static ref RE_MONTH_DAY_VALUE: Regex = Regex::new(r"^(?:--)?([0-9]{2})-([0-9]{2})$").unwrap();
+    /// International phone number without separators, at most 15 digits including the country code
+    pub static ref RE_PHONE_VALUE: Regex = Regex::new(r"^\+[1-9][0-9]{6,14}$").unwrap();
+    pub static ref RE_EMAIL_VALUE: Regex = Regex::new(r"^[^@\s]+@([^@\s.]+\.)+[^@\s.]{2,}$").unwrap();
 }
 
-#[repr(transparent)]
 pub struct StringBuf {
     pub buf: String,
+    /// Start offsets of the statement sections in the order they were added, see `--predicate-order`
+    sections: Vec<(Section, usize)>,
+    /// Problems found in the tags, only collected with `--emit-issues`
+    pub issues: Vec<(IssueKind, String)>,
+    /// Output format of the statements, Turtle by default
+    serializer: Box<dyn Serializer>,
+    /// Offset of the first statement, after anything written by `begin_subject`
+    statements_start: usize,
 }
 
 impl Deref for StringBuf {
@@ -80,71 +101,310 @@ impl Display for StringBuf {
 
 impl Default for StringBuf {
     fn default() -> Self {
+        Self::new(Box::new(Turtle))
+    }
+}
+
+impl StringBuf {
+    pub fn new(serializer: Box<dyn Serializer>) -> Self {
         Self {
             buf: String::with_capacity(100000),
+            sections: Vec::new(),
+            issues: Vec::new(),
+            serializer,
+            statements_start: 0,
         }
     }
-}
 
-impl StringBuf {
+    /// Start the statements of an element, before any of them is added
+    pub fn begin_subject(&mut self, elem: Element, id: i64) {
+        self.serializer.begin_subject(&mut self.buf, elem, id);
+        self.statements_start = self.buf.len();
+    }
+
+    /// Check if no statements were added yet
     pub fn is_empty(&self) -> bool {
-        self.buf.is_empty()
+        self.buf.len() == self.statements_start
+    }
+
+    /// The statements added so far, without anything written by `begin_subject`
+    fn statements(&self) -> &str {
+        &self.buf[self.statements_start..]
+    }
+
+    /// Mark the start of the statements of the given section
+    pub fn start_section(&mut self, section: Section) {
+        if self.sections.last().map(|v| v.0) != Some(section) {
+            self.sections.push((section, self.buf.len()));
+        }
     }
 
     pub fn add_value(&mut self, predicate: impl Display, value: impl XsdValue) {
-        writeln!(self, r#"{predicate} {value};"#).unwrap();
+        self.serializer
+            .predicate_value(&mut self.buf, &predicate, &value);
     }
 
+    /// Add all tags as statements, and return the number of keys that were recorded as `osmm:badkey` or dropped
     pub fn add_tags<'t, TTags: Iterator<Item = (&'t str, &'t str)> + ExactSizeIterator>(
         &mut self,
         tags: TTags,
-    ) {
+        opts: &ParserOpts,
+    ) -> u64 {
+        let mut bad_keys = 0;
+        let mut raw_tags = opts.emit_raw_tags_json.then(JsonValue::new_object);
+        self.start_section(Section::Tags);
         for (key, val) in tags {
-            if key == "created_by" {
+            if let Some(raw_tags) = &mut raw_tags {
+                raw_tags[key] = val.into();
+            }
+            if !opts.only_keys.is_empty() && !opts.only_keys.iter().any(|v| v == key) {
                 continue;
             }
+            if key == "created_by" || !opts.tag_filter.is_allowed(key) {
+                continue;
+            }
+            let key = match opts.tag_rename.iter().find(|v| v.from == key) {
+                Some(rename) => rename.to.as_str(),
+                None => key,
+            };
             if !RE_SIMPLE_LOCAL_NAME.is_match(key) {
-                // Record any unusual tag name in a "osmm:badkey" statement
-                self.add_value("osmm:badkey", XsdStr(key));
+                // Record any unusual tag name in a "osmm:badkey" statement, unless they are dropped
+                if !opts.drop_bad_keys {
+                    self.add_value(vocab::BAD_KEY, XsdStr(key));
+                }
+                if opts.emit_issues {
+                    self.issues.push((IssueKind::BadKey, key.to_string()));
+                }
+                bad_keys += 1;
                 continue;
             }
 
-            let prop = XsdRaw("osmt", key);
-            if key.contains("wikidata") {
+            let prop = tag_predicate(key, opts);
+            let schema = opts.tag_schema.as_ref().unwrap_or(&DEFAULT_SCHEMA);
+            let datatype = schema.datatype(key);
+            if !self.add_typed_value(&prop, datatype, key, val) {
+                self.add_value(prop, XsdStr(val));
+                if opts.flag_bad_wiki {
+                    match datatype {
+                        TagType::WikidataRef => self.add_value(vocab::BAD_WIKIDATA, XsdStr(val)),
+                        TagType::Wikipedia => self.add_value(vocab::BAD_WIKIPEDIA, XsdStr(val)),
+                        _ => {}
+                    }
+                }
+            }
+            if opts.validate_contacts && !self.add_contact(key, val) && opts.emit_issues {
+                self.issues
+                    .push((IssueKind::InvalidContact, val.to_string()));
+            }
+        }
+        if let Some(raw_tags) = raw_tags.filter(|_| !self.is_empty()) {
+            self.add_value(vocab::RAW_TAGS, XsdJson(&raw_tags));
+        }
+        bad_keys
+    }
+
+    /// Add the normalized values of the phone and email tags, and flag the tag key if any value is invalid.
+    /// Multiple values are separated with `;`. Returns false if any value is invalid.
+    fn add_contact(&mut self, key: &str, val: &str) -> bool {
+        let (prop, normalize): (_, fn(&str) -> Option<String>) = match key {
+            "phone" | "contact:phone" => (vocab::TELEPHONE, normalize_phone),
+            "email" | "contact:email" => (vocab::EMAIL, normalize_email),
+            _ => return true,
+        };
+        let mut is_valid = true;
+        for value in val.split(';') {
+            match normalize(value.trim()) {
+                Some(value) => self.add_value(prop, XsdStr(&value)),
+                None => is_valid = false,
+            }
+        }
+        if !is_valid {
+            self.add_value(vocab::CONTACT_WARNING, XsdStr(key));
+        }
+        is_valid
+    }
+
+    /// Add the tag value using the given datatype, or return false if the value does not fit it
+    fn add_typed_value(&mut self, prop: &XsdRaw, datatype: TagType, key: &str, val: &str) -> bool {
+        match datatype {
+            TagType::String => return false,
+            TagType::LangString => {
+                let lang = key.rsplit_once(':').map(|(_, lang)| lang);
+                match lang.filter(|v| RE_LANGUAGE.is_match(v)) {
+                    Some(lang) => self.add_value(prop, XsdLangString(val, lang)),
+                    None => return false,
+                }
+            }
+            TagType::Integer => match val.parse() {
+                Ok(v) if RE_INTEGER_VALUE.is_match(val) => self.add_value(prop, XsdInteger(v)),
+                _ => return false,
+            },
+            TagType::Decimal => {
+                if !RE_DECIMAL_VALUE.is_match(val) {
+                    return false;
+                }
+                self.add_value(prop, XsdTyped(val, "decimal"));
+            }
+            TagType::Boolean => match val {
+                "yes" | "true" => self.add_value(prop, XsdBoolean(true)),
+                "no" | "false" => self.add_value(prop, XsdBoolean(false)),
+                _ => return false,
+            },
+            TagType::Date => {
+                let is_valid = NaiveDate::parse_from_str(val, "%Y-%m-%d").is_ok();
+                if !is_valid || !RE_DATE_VALUE.is_match(val) {
+                    return false;
+                }
+                self.add_value(prop, XsdTyped(val, "date"));
+            }
+            TagType::MonthDay => match XsdGMonthDay::parse(val) {
+                Some(month_day) => self.add_value(prop, month_day),
+                None => return false,
+            },
+            TagType::WikidataRef => {
                 if RE_WIKIDATA_VALUE.is_match(val) {
                     self.add_value(prop, XsdRaw("wd", val));
-                    continue;
                 } else if RE_WIKIDATA_MULTI_VALUE.is_match(val) {
                     let vals = || val.split(';').map(|v| XsdRaw("wd", v.trim()));
                     self.add_value(prop, XsdIter(vals));
-                    continue;
-                }
-            } else if key.contains("wikipedia") {
-                if let Some(v) = RE_WIKIPEDIA_VALUE.captures(val) {
-                    let lang = v.get(1).unwrap().as_str();
-                    let title = v.get(2).unwrap().as_str();
-                    let title = title.replace(' ', "_");
-                    let title = &utf8_percent_encode(&title, PERCENT_ENC_SET);
-                    self.add_value(prop, XsdWikipedia { lang, title });
-                    continue;
+                } else {
+                    return false;
                 }
             }
-            self.add_value(prop, XsdStr(val));
+            TagType::Wikipedia => {
+                // Semicolon lists are only linked if every entry is valid
+                let pages = val.split(';').map(|v| XsdWikipedia::parse(v.trim()));
+                let Some(pages) = pages.collect::<Option<Vec<_>>>() else {
+                    return false;
+                };
+                self.add_value(prop, XsdIter(|| pages.iter()));
+            }
+            TagType::WikimediaCommons => {
+                let pages = val.split(';').map(|v| XsdCommons::parse(v.trim()));
+                let Some(pages) = pages.collect::<Option<Vec<_>>>() else {
+                    return false;
+                };
+                self.add_value(prop, XsdIter(|| pages.iter()));
+            }
         }
+        true
     }
 
-    pub fn finalize(mut self, info: ElementInfo) -> StringBuf {
-        self.add_value("osmm:version", XsdInteger(info.version as i64));
-        if let Some(user) = info.user {
-            self.add_value("osmm:user", XsdStr(user));
+    pub fn finalize(mut self, info: ElementInfo, opts: &ParserOpts) -> StringBuf {
+        self.start_section(Section::Metadata);
+        if opts.emit_hash {
+            let hash = XsdHash::new(self.statements(), info.version.unwrap_or_default());
+            self.add_value(vocab::HASH, hash);
+        }
+        // Missing metadata fields are not emitted at all
+        if let Some(version) = info.version {
+            if !opts.no_metadata {
+                self.add_value(vocab::VERSION, XsdInteger(i64::from(version)));
+            }
+            // Versions start at 1, anything else is a problem upstream, see `Parser::check_version`
+            if version < 1 {
+                self.add_value(vocab::VERSION_WARNING, XsdStr("invalid"));
+            }
+            if opts.emit_new_flag && version == 1 {
+                self.add_value(vocab::IS_NEW, XsdBoolean(true));
+            }
+        }
+        if !opts.no_metadata {
+            self.add_metadata(&info, opts);
+        }
+        if self.is_empty() {
+            // Nothing to terminate, such elements are skipped by the parser
+            return self;
+        }
+        if !opts.predicate_order.is_empty() {
+            self.reorder_sections(&opts.predicate_order);
         }
-        self.add_value("osmm:timestamp", XsdDateTime(info.milli_timestamp));
-        self.add_value("osmm:changeset", XsdInteger(info.changeset));
-        self.pop(); // remove trailing "\n"
-        self.pop(); // remove trailing ";"
-        self.push_str(".\n");
+        self.serializer.end_subject(&mut self.buf);
         self
     }
+
+    /// Add the user, timestamp, and changeset statements
+    fn add_metadata(&mut self, info: &ElementInfo, opts: &ParserOpts) {
+        // Empty user names are also anonymous, as the PBF format stores them as an empty string
+        let user = info
+            .user
+            .filter(|v| !opts.mark_anonymous_users || !v.is_empty());
+        if let Some(user) = user {
+            self.add_value(vocab::USER, XsdStr(user));
+        } else if opts.mark_anonymous_users {
+            self.add_value(vocab::USER, XsdRaw("osmm", "anonymous"));
+        }
+        if let Some(ts) = info.milli_timestamp {
+            let ts = XsdDateTime(ts, opts.timestamp_format);
+            self.add_value(vocab::TIMESTAMP, ts);
+        }
+        if let Some(changeset) = info.changeset {
+            self.add_value(vocab::CHANGESET, XsdInteger(changeset));
+        }
+    }
+
+    fn reorder_sections(&mut self, order: &[Section]) {
+        let rank = |section| {
+            order
+                .iter()
+                .position(|v| *v == section)
+                .unwrap_or(order.len())
+        };
+        let mut ranges: Vec<_> = self
+            .sections
+            .iter()
+            .enumerate()
+            .map(|(idx, &(section, start))| {
+                let end = self.sections.get(idx + 1).map_or(self.buf.len(), |v| v.1);
+                (rank(section), start..end)
+            })
+            .collect();
+        // Sorting is stable, so the statements of the same rank keep their order
+        ranges.sort_by_key(|v| v.0);
+        let mut buf = String::with_capacity(self.buf.capacity());
+        buf.push_str(&self.buf[..self.statements_start]);
+        for (_, range) in ranges {
+            buf.push_str(&self.buf[range]);
+        }
+        self.buf = buf;
+    }
+}
+
+/// Get the predicate for the tag key, using the first matching `--key-namespace` rule.
+/// The key must match `RE_SIMPLE_LOCAL_NAME`, so the prefixed name is valid Turtle without any escaping.
+fn tag_predicate<'a>(key: &'a str, opts: &'a ParserOpts) -> XsdRaw<'a> {
+    for ns in &opts.key_namespace {
+        let name = key
+            .strip_prefix(ns.key_prefix.as_str())
+            .and_then(|v| v.strip_prefix(':'));
+        if let Some(name) = name.filter(|v| RE_SIMPLE_LOCAL_NAME.is_match(v)) {
+            return XsdRaw(&ns.namespace, name);
+        }
+    }
+    XsdRaw("osmt", key)
+}
+
+/// Normalize a phone number to the E.164 form, e.g. `+1 (555) 123-4567` to `+15551234567`.
+/// Numbers without the country code cannot be normalized and are treated as invalid.
+pub fn normalize_phone(value: &str) -> Option<String> {
+    let mut result: String = value
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')' | '/'))
+        .collect();
+    if let Some(number) = result.strip_prefix("00") {
+        result = format!("+{number}");
+    }
+    RE_PHONE_VALUE.is_match(&result).then_some(result)
+}
+
+/// Normalize an email address by removing the `mailto:` prefix and lowercasing the domain
+pub fn normalize_email(value: &str) -> Option<String> {
+    let value = value.strip_prefix("mailto:").unwrap_or(value);
+    if !RE_EMAIL_VALUE.is_match(value) {
+        return None;
+    }
+    let (local, domain) = value.rsplit_once('@')?;
+    Some(format!("{local}@{}", domain.to_lowercase()))
 }
 
 pub trait XsdValue: Display {}
@@ -152,26 +412,143 @@ pub trait XsdValue: Display {}
 pub struct XsdPoint {
     pub lat: f64,
     pub lon: f64,
+    /// Number of digits after the decimal point, or `None` to keep full precision
+    pub precision: Option<u8>,
+    pub crs: Crs,
 }
 impl XsdValue for XsdPoint {}
 impl Display for XsdPoint {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (x, y) = project(self.crs, self.lon, self.lat);
         write!(
             f,
-            r#""Point({lon} {lat})"^^geo:wktLiteral"#,
-            lon = self.lon,
-            lat = self.lat,
+            r#""{crs}Point({x} {y})"^^geo:wktLiteral"#,
+            crs = crs_prefix(self.crs),
+            x = round(x, self.precision),
+            y = round(y, self.precision),
         )
     }
 }
 
-pub struct XsdWikipedia<'a, T: Display> {
+/// A collection of line strings, each given as a list of `(lat, lon)` pairs
+pub struct XsdLineCollection<'a> {
+    pub lines: &'a [Vec<(f64, f64)>],
+    /// Number of digits after the decimal point, or `None` to keep full precision
+    pub precision: Option<u8>,
+    pub crs: Crs,
+}
+impl XsdValue for XsdLineCollection<'_> {}
+impl Display for XsdLineCollection<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, r#""{}GEOMETRYCOLLECTION("#, crs_prefix(self.crs))?;
+        for (i, line) in self.lines.iter().enumerate() {
+            let sep = if i == 0 { "" } else { ", " };
+            f.write_str(sep)?;
+            write_line_string(f, line, self.precision, self.crs)?;
+        }
+        f.write_str(r#")"^^geo:wktLiteral"#)
+    }
+}
+
+/// WKT line string of (lat, lon) points
+pub struct XsdLineString<'a> {
+    pub line: &'a [(f64, f64)],
+    /// Number of digits after the decimal point, or `None` to keep full precision
+    pub precision: Option<u8>,
+    pub crs: Crs,
+}
+impl XsdValue for XsdLineString<'_> {}
+impl Display for XsdLineString<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, r#""{}"#, crs_prefix(self.crs))?;
+        write_line_string(f, self.line, self.precision, self.crs)?;
+        f.write_str(r#""^^geo:wktLiteral"#)
+    }
+}
+
+fn write_line_string(
+    f: &mut std::fmt::Formatter<'_>,
+    line: &[(f64, f64)],
+    precision: Option<u8>,
+    crs: Crs,
+) -> std::fmt::Result {
+    f.write_str("LINESTRING(")?;
+    for (i, &(lat, lon)) in line.iter().enumerate() {
+        let (x, y) = project(crs, lon, lat);
+        let sep = if i == 0 { "" } else { ", " };
+        let (x, y) = (round(x, precision), round(y, precision));
+        write!(f, "{sep}{x} {y}")?;
+    }
+    f.write_char(')')
+}
+
+/// Bounding box as a WKT polygon
+pub struct XsdBbox {
+    pub bbox: Bbox,
+    pub crs: Crs,
+}
+impl XsdValue for XsdBbox {}
+impl Display for XsdBbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (x1, y1) = project(self.crs, self.bbox.min_lon, self.bbox.min_lat);
+        let (x2, y2) = project(self.crs, self.bbox.max_lon, self.bbox.max_lat);
+        write!(
+            f,
+            r#""{crs}POLYGON(({x1} {y1}, {x2} {y1}, {x2} {y2}, {x1} {y2}, {x1} {y1}))"^^geo:wktLiteral"#,
+            crs = crs_prefix(self.crs),
+        )
+    }
+}
+
+/// WKT prefix declaring the coordinate reference system, empty for the default WGS 84
+fn crs_prefix(crs: Crs) -> &'static str {
+    match crs {
+        Crs::Wgs84 => "",
+        Crs::Crs84 => "<http://www.opengis.net/def/crs/OGC/1.3/CRS84> ",
+        Crs::WebMercator => "<http://www.opengis.net/def/crs/EPSG/0/3857> ",
+    }
+}
+
+/// Convert the location to the `(x, y)` coordinates of the given CRS
+fn project(crs: Crs, lon: f64, lat: f64) -> (f64, f64) {
+    match crs {
+        Crs::Wgs84 | Crs::Crs84 => (lon, lat),
+        Crs::WebMercator => to_web_mercator(lon, lat),
+    }
+}
+
+/// Round the value to the given number of decimal digits
+pub fn round(value: f64, precision: Option<u8>) -> f64 {
+    match precision {
+        Some(digits) => {
+            let scale = 10_f64.powi(i32::from(digits));
+            (value * scale).round() / scale
+        }
+        None => value,
+    }
+}
+
+/// Wikipedia article, e.g. `<https://en.wikipedia.org/wiki/Eiffel_Tower>`
+pub struct XsdWikipedia<'a> {
     lang: &'a str,
-    title: &'a T,
+    /// Percent-encoded article title
+    title: String,
 }
 
-impl<T: Display> XsdValue for XsdWikipedia<'_, T> {}
-impl<T: Display> Display for XsdWikipedia<'_, T> {
+impl<'a> XsdWikipedia<'a> {
+    /// Parse a `lang:title` tag value
+    pub fn parse(value: &'a str) -> Option<Self> {
+        let v = RE_WIKIPEDIA_VALUE.captures(value)?;
+        let title = v.get(2).unwrap().as_str().replace(' ', "_");
+        Some(Self {
+            lang: v.get(1).unwrap().as_str(),
+            title: utf8_percent_encode(&title, PERCENT_ENC_SET).to_string(),
+        })
+    }
+}
+
+impl XsdValue for XsdWikipedia<'_> {}
+impl Display for XsdWikipedia<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
@@ -182,7 +559,58 @@ impl<T: Display> Display for XsdWikipedia<'_, T> {
     }
 }
 
-pub struct XsdInteger(i64);
+/// Wikimedia Commons page, e.g. `<https://commons.wikimedia.org/wiki/Category:Eiffel_Tower>`
+pub struct XsdCommons<'a> {
+    namespace: &'a str,
+    /// Percent-encoded page title without the namespace
+    title: String,
+}
+
+impl<'a> XsdCommons<'a> {
+    /// Parse a `Category:...` or `File:...` tag value
+    pub fn parse(value: &'a str) -> Option<Self> {
+        let v = RE_COMMONS_VALUE.captures(value)?;
+        let title = v.get(2).unwrap().as_str().replace(' ', "_");
+        Some(Self {
+            namespace: v.get(1).unwrap().as_str(),
+            title: utf8_percent_encode(&title, PERCENT_ENC_SET).to_string(),
+        })
+    }
+}
+
+impl XsdValue for XsdCommons<'_> {}
+impl Display for XsdCommons<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "<https://commons.wikimedia.org/wiki/{}:{}>",
+            self.namespace, self.title,
+        )
+    }
+}
+
+/// A stable 64-bit FNV-1a hash of all statements generated so far for an element,
+/// followed by the decimal element version. Metadata other than the version is not included.
+pub struct XsdHash(u64);
+impl XsdHash {
+    pub fn new(statements: &str, version: i32) -> Self {
+        let hash = statements
+            .bytes()
+            .chain(version.to_string().bytes())
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            });
+        Self(hash)
+    }
+}
+impl XsdValue for XsdHash {}
+impl Display for XsdHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, r#""{:016x}""#, self.0)
+    }
+}
+
+pub struct XsdInteger(pub i64);
 impl XsdValue for XsdInteger {}
 impl Display for XsdInteger {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -199,14 +627,62 @@ impl Display for XsdBoolean {
     }
 }
 
-pub struct XsdDateTime(pub i64);
+/// Timestamp in milliseconds since the Unix epoch, formatted as chosen by `--timestamp-format`
+pub struct XsdDateTime(pub i64, pub TimestampFormat);
 impl XsdValue for XsdDateTime {}
 impl Display for XsdDateTime {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        // "{0:%Y-%m-%dT%H:%M:%S}Z"^^xsd:dateTime
         let ms = self.0;
-        let ts = Utc.timestamp_opt(ms / 1000, (ms % 1000) as u32).unwrap();
-        write!(f, r#""{ts}"^^xsd:dateTime"#)
+        match self.1 {
+            TimestampFormat::Iso => {
+                // "{0:%Y-%m-%dT%H:%M:%S}Z"^^xsd:dateTime
+                let ts = Utc.timestamp_opt(ms / 1000, (ms % 1000) as u32).unwrap();
+                write!(f, r#""{ts}"^^xsd:dateTime"#)
+            }
+            TimestampFormat::EpochMillis => write!(f, "{}", XsdInteger(ms)),
+            TimestampFormat::EpochSeconds => write!(f, "{}", XsdInteger(ms.div_euclid(1000))),
+        }
+    }
+}
+
+/// Literal with an explicit `xsd:` datatype, e.g. `"1.5"^^xsd:decimal`. The value must not need escaping.
+pub struct XsdTyped<'a>(pub &'a str, pub &'a str);
+impl XsdValue for XsdTyped<'_> {}
+impl Display for XsdTyped<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, r#""{}"^^xsd:{}"#, self.0, self.1)
+    }
+}
+
+/// String with a language tag, e.g. `"Paris"@fr`
+pub struct XsdLangString<'a>(pub &'a str, pub &'a str);
+impl XsdValue for XsdLangString<'_> {}
+impl Display for XsdLangString<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}", XsdStr(self.0), self.1)
+    }
+}
+
+/// Recurring month and day without a year, e.g. `--12-25`
+pub struct XsdGMonthDay {
+    pub month: u32,
+    pub day: u32,
+}
+impl XsdGMonthDay {
+    /// Parse `--MM-DD` or `MM-DD` values. February 29th is allowed because the year is unknown.
+    pub fn parse(value: &str) -> Option<Self> {
+        let v = RE_MONTH_DAY_VALUE.captures(value)?;
+        let month = v.get(1).unwrap().as_str().parse().ok()?;
+        let day = v.get(2).unwrap().as_str().parse().ok()?;
+        // Use a leap year to validate the day of the month
+        NaiveDate::from_ymd_opt(2000, month, day)?;
+        Some(Self { month, day })
+    }
+}
+impl XsdValue for XsdGMonthDay {}
+impl Display for XsdGMonthDay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, r#""--{:02}-{:02}"^^xsd:gMonthDay"#, self.month, self.day)
     }
 }
 
@@ -218,6 +694,18 @@ impl Display for XsdStr<'_> {
     }
 }
 
+/// JSON text literal, e.g. `"{\"highway\":\"primary\"}"^^rdf:JSON`. The datatype is written as a full IRI,
+/// because the `rdf` prefix is not declared in the output files.
+pub struct XsdJson<'a>(pub &'a JsonValue);
+impl XsdValue for XsdJson<'_> {}
+impl Display for XsdJson<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = self.0.dump();
+        let datatype = "<http://www.w3.org/1999/02/22-rdf-syntax-ns#JSON>";
+        write!(f, "{}^^{datatype}", XsdStr(&text))
+    }
+}
+
 pub struct XsdRaw<'a>(&'a str, pub &'a str);
 impl XsdValue for XsdRaw<'_> {}
 impl Display for XsdRaw<'_> {
@@ -253,6 +741,29 @@ impl Display for XsdRelMember<'_> {
     }
 }
 
+/// Blank node describing a relation member at the given position, see `--emit-member-order`
+pub struct XsdMember<'a> {
+    pub member: &'a RelMember<'a>,
+    pub index: usize,
+}
+impl XsdValue for XsdMember<'_> {}
+impl Display for XsdMember<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{} {}; {} {}",
+            vocab::MEMBER_INDEX,
+            XsdInteger(self.index as i64),
+            vocab::MEMBER_REF,
+            XsdRelMember(self.member),
+        )?;
+        match self.member.role() {
+            Ok(role) if !role.is_empty() => write!(f, "; {} {}]", vocab::MEMBER_ROLE, XsdStr(role)),
+            _ => f.write_str("]"),
+        }
+    }
+}
+
 pub struct XsdIter<F>(F);
 impl<F: Fn() -> I, I: Iterator<Item = V>, V: Display> XsdValue for XsdIter<F> {}
 impl<F: Fn() -> I, I: Iterator<Item = V>, V: Display> Display for XsdIter<F> {
@@ -0,0 +1,111 @@
+use std::path::Path;
+
+use anyhow::{bail, Context as _};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// Rules reproducing the built-in tag value handling. Used unless `--tag-schema` is given.
+pub const DEFAULT_TAG_SCHEMA: &str = "\
+# Tag schema: each line maps a key pattern to a value datatype, the first matching rule is used.
+# A `*` in the pattern matches any number of characters. Keys without a matching rule are strings.
+*wikidata*: wikidata-ref
+*wikipedia*: wikipedia
+*wikimedia_commons*: wikimedia-commons
+date: month-day
+*_date: month-day
+*:date: month-day
+";
+
+lazy_static! {
+    pub static ref DEFAULT_SCHEMA: TagSchema = TagSchema::parse(DEFAULT_TAG_SCHEMA).unwrap();
+}
+
+/// How a tag value is serialized. Values that do not fit the datatype are emitted as plain strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagType {
+    /// Plain string literal
+    String,
+    /// Language-tagged string, with the language taken from the last `:` part of the key, e.g. `name:en`
+    LangString,
+    /// `xsd:integer`
+    Integer,
+    /// `xsd:decimal`
+    Decimal,
+    /// `xsd:boolean` for `yes`/`no` and `true`/`false`
+    Boolean,
+    /// `xsd:date` for `YYYY-MM-DD` values
+    Date,
+    /// `xsd:gMonthDay` for `--MM-DD` and `MM-DD` values
+    MonthDay,
+    /// Wikidata entity reference such as `wd:Q42`, or a `;`-separated list of them
+    WikidataRef,
+    /// Wikipedia article link for the `lang:title` values
+    Wikipedia,
+    /// Wikimedia Commons page link for the `Category:...` and `File:...` values, or a `;`-separated list of them
+    WikimediaCommons,
+}
+
+impl TagType {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "string" => Self::String,
+            "langString" => Self::LangString,
+            "integer" => Self::Integer,
+            "decimal" => Self::Decimal,
+            "boolean" => Self::Boolean,
+            "date" => Self::Date,
+            "month-day" => Self::MonthDay,
+            "wikidata-ref" => Self::WikidataRef,
+            "wikipedia" => Self::Wikipedia,
+            "wikimedia-commons" => Self::WikimediaCommons,
+            _ => return None,
+        })
+    }
+}
+
+/// Ordered list of rules choosing the datatype of each tag value by its key
+#[derive(Debug, Clone)]
+pub struct TagSchema {
+    rules: Vec<(Regex, TagType)>,
+}
+
+impl TagSchema {
+    /// Parse the schema from lines like `height: decimal`. Empty lines and `#` comments are ignored.
+    pub fn parse(schema: &str) -> anyhow::Result<Self> {
+        let mut rules = Vec::new();
+        for (idx, line) in schema.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((pattern, datatype)) = line.rsplit_once(':') else {
+                bail!("Line {}: expected `key: datatype`, got `{line}`", idx + 1);
+            };
+            let (pattern, datatype) = (pattern.trim(), datatype.trim());
+            let Some(datatype) = TagType::from_name(datatype) else {
+                bail!("Line {}: unknown datatype `{datatype}`", idx + 1);
+            };
+            let pattern = pattern
+                .split('*')
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join(".*");
+            rules.push((Regex::new(&format!("^{pattern}$"))?, datatype));
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        let schema = std::fs::read_to_string(Path::new(path))
+            .with_context(|| format!("Unable to read tag schema {path}"))?;
+        Self::parse(&schema).with_context(|| format!("Invalid tag schema {path}"))
+    }
+
+    /// Get the datatype of the first rule matching the key
+    pub fn datatype(&self, key: &str) -> TagType {
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(key))
+            .map_or(TagType::String, |(_, datatype)| *datatype)
+    }
+}
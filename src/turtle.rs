@@ -0,0 +1,213 @@
+use std::fmt::{Display, Write as _};
+use std::io::{self, Write};
+
+use crate::utils::Element;
+
+/// Converter of the Turtle statements generated for each element into another text format,
+/// see `--format ntriples` and `--format jsonld`
+pub trait Converter: Send + Sync {
+    /// Write the statements of a single subject, and return the number of bytes written.
+    /// The statements are a Turtle predicate-object list ending with `.`, as generated by `Turtle`.
+    fn write_element(
+        &self,
+        out: &mut dyn Write,
+        subject: &str,
+        statements: &str,
+    ) -> io::Result<usize>;
+}
+
+/// Writer of the statements of an element in an output format, called by `StringBuf`
+/// as the parser adds the statements. Each element has its own serializer.
+pub trait Serializer: Send {
+    /// Start the statements of the element, before any of them is added
+    fn begin_subject(&mut self, out: &mut String, elem: Element, id: i64);
+    /// Add a single statement about the element
+    fn predicate_value(&mut self, out: &mut String, predicate: &dyn Display, value: &dyn Display);
+    /// Complete the statements of the element, once at least one of them was added
+    fn end_subject(&mut self, out: &mut String);
+}
+
+/// Turtle predicate-object list with one statement per line, ending with `.`.
+/// The subject is not part of it: the writer adds it in front of the list, see `Statement::write_to`,
+/// so the sorting, sharding, and `--emit-hash` of the elements only depend on their statements.
+pub struct Turtle;
+
+impl Serializer for Turtle {
+    fn begin_subject(&mut self, _out: &mut String, _elem: Element, _id: i64) {}
+
+    fn predicate_value(&mut self, out: &mut String, predicate: &dyn Display, value: &dyn Display) {
+        writeln!(out, "{predicate} {value};").unwrap();
+    }
+
+    fn end_subject(&mut self, out: &mut String) {
+        out.pop(); // remove trailing "\n"
+        out.pop(); // remove trailing ";"
+        out.push_str(".\n");
+    }
+}
+
+/// A single term of the generated statements
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Term<'a> {
+    /// IRI without the angle brackets
+    Iri(&'a str),
+    /// Prefixed name, e.g. `osmnode:123`
+    Name(&'a str),
+    /// The `a` keyword used as the `rdf:type` predicate
+    Type,
+    /// Literal with the quotes and escapes as written, e.g. `"Café"`
+    Literal {
+        text: &'a str,
+        /// Datatype, written as a prefixed name or an IRI
+        datatype: Option<&'a str>,
+        /// Language tag, e.g. `fr`
+        lang: Option<&'a str>,
+    },
+}
+
+/// Object of a statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Object<'a> {
+    Term(Term<'a>),
+    /// Blank node with its own statements, e.g. `[osmm:index "0"^^xsd:integer; osmm:ref osmway:1]`
+    BlankNode(Vec<(Term<'a>, Object<'a>)>),
+}
+
+/// Parse a predicate-object list into the (predicate, object) pairs, in the order they are written.
+/// Objects separated by `,` are returned as separate pairs with the same predicate.
+pub fn parse_statements(statements: &str) -> Result<Vec<(Term<'_>, Object<'_>)>, String> {
+    let (result, rest) = parse_list(statements)?;
+    match rest {
+        Some(_) => Err("unexpected `]`".to_string()),
+        None => Ok(result),
+    }
+}
+
+/// Parse a predicate-object list, stopping at the end of the text or at the `]` closing a blank node.
+/// Returns the text after the closing `]`, or `None` at the end of the text.
+#[allow(clippy::type_complexity)]
+fn parse_list(statements: &str) -> Result<(Vec<(Term<'_>, Object<'_>)>, Option<&str>), String> {
+    let mut result = Vec::new();
+    let mut predicate = None;
+    let mut rest = statements;
+    loop {
+        rest = rest.trim_start();
+        match rest.chars().next() {
+            None => break,
+            // The next predicate
+            Some(';') => predicate = None,
+            // Another object of the same predicate, or the end of the statements
+            Some(',' | '.') => {}
+            // The end of a blank node, the text after it is returned to the enclosing list
+            Some(']') => return Ok((result, Some(&rest[1..]))),
+            // A blank node object, e.g. `osmm:member [osmm:index "0"^^xsd:integer; osmm:ref osmway:1]`
+            Some('[') => {
+                let Some(p) = predicate else {
+                    return Err("blank node used as a predicate".to_string());
+                };
+                let (node, tail) = parse_list(&rest[1..])?;
+                rest = tail.ok_or("unterminated blank node")?;
+                result.push((p, Object::BlankNode(node)));
+                continue;
+            }
+            Some(_) => {
+                let (term, tail) = next_term(rest)?;
+                rest = tail;
+                match predicate {
+                    None => predicate = Some(term),
+                    Some(p) => result.push((p, Object::Term(term))),
+                }
+                continue;
+            }
+        }
+        rest = &rest[1..];
+    }
+    Ok((result, None))
+}
+
+/// Parse an IRI, a literal, or a prefixed name, and return it with the remaining text
+fn next_term(value: &str) -> Result<(Term<'_>, &str), String> {
+    if let Some(iri) = value.strip_prefix('<') {
+        let end = iri.find('>').ok_or("unterminated IRI")?;
+        return Ok((Term::Iri(&iri[..end]), &iri[end + 1..]));
+    }
+    if value.starts_with('"') {
+        let mut end = None;
+        let mut is_escaped = false;
+        for (idx, ch) in value.char_indices().skip(1) {
+            match ch {
+                _ if is_escaped => is_escaped = false,
+                '\\' => is_escaped = true,
+                '"' => {
+                    end = Some(idx + 1);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let end = end.ok_or("unterminated literal")?;
+        let (text, mut tail) = value.split_at(end);
+        let (mut datatype, mut lang) = (None, None);
+        if let Some(rest) = tail.strip_prefix("^^") {
+            let len = match rest.strip_prefix('<') {
+                Some(iri) => iri.find('>').ok_or("unterminated IRI")? + 2,
+                None => name_len(rest),
+            };
+            datatype = Some(&rest[..len]);
+            tail = &rest[len..];
+        } else if let Some(rest) = tail.strip_prefix('@') {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+                .unwrap_or(rest.len());
+            lang = Some(&rest[..len]);
+            tail = &rest[len..];
+        }
+        return Ok((
+            Term::Literal {
+                text,
+                datatype,
+                lang,
+            },
+            tail,
+        ));
+    }
+
+    let (name, tail) = value.split_at(name_len(value));
+    if name == "a" {
+        return Ok((Term::Type, tail));
+    }
+    Ok((Term::Name(name), tail))
+}
+
+/// Length of the prefixed name at the start of the text
+fn name_len(value: &str) -> usize {
+    let end = value
+        .find(|c: char| c.is_whitespace() || matches!(c, ';' | ',' | ']'))
+        .unwrap_or(value.len());
+    // Local names cannot end with a dot, so it terminates the statements
+    if value[..end].ends_with('.') {
+        end - 1
+    } else {
+        end
+    }
+}
+
+/// Split a prefixed name like `osmnode:123` into the prefix name and the local name
+pub fn split_name(name: &str) -> Result<(&str, &str), String> {
+    name.split_once(':')
+        .ok_or_else(|| format!("`{name}` is not a prefixed name"))
+}
+
+/// Parse the prefix declarations in the `prefix name: <iri>` form into the name and IRI pairs
+pub fn parse_declarations<T: AsRef<str>>(
+    declarations: impl IntoIterator<Item = T>,
+) -> Vec<(String, String)> {
+    declarations
+        .into_iter()
+        .filter_map(|v| {
+            let (name, iri) = v.as_ref().strip_prefix("prefix ")?.split_once(": ")?;
+            let iri = iri.strip_prefix('<')?.strip_suffix('>')?;
+            Some((name.to_string(), iri.to_string()))
+        })
+        .collect()
+}
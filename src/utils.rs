@@ -1,8 +1,14 @@
+use std::collections::{BTreeMap, HashSet};
 use std::fmt::{Debug, Display};
+use std::time::Duration;
 
-use osmpbf::{DenseNodeInfo, Info};
+use json::JsonValue;
+
+use osmpbf::{DenseNodeInfo, Info, RelMemberType};
 use percent_encoding::{AsciiSet, CONTROLS};
 
+use crate::sparql::EndpointStats;
+
 pub const PERCENT_ENC_SET: &AsciiSet = &CONTROLS
     .add(b';')
     .add(b'@')
@@ -24,26 +30,269 @@ pub struct Stats {
     pub added_rels: u64,
     pub added_ways: u64,
     pub skipped_nodes: u64,
+    pub skipped_ways: u64,
+    pub skipped_rels: u64,
+    pub skipped_old: u64,
+    pub skipped_unlisted: u64,
+    /// Elements of the types excluded by `--types`
+    pub skipped_types: u64,
+    /// Ways and relations without tags, see `--skip-tagless-ways` and `--skip-tagless-relations`
+    pub skipped_tagless: u64,
+    /// Elements outside of the `--bbox` box
+    pub skipped_outside_bbox: u64,
+    /// Number of node locations stored in the cache, including the ones stored more than once
+    pub cached_nodes: u64,
     pub deleted_nodes: u64,
     pub deleted_rels: u64,
     pub deleted_ways: u64,
     pub blocks: u64,
+    pub warnings: Warnings,
+    /// Bounding box of all emitted element locations
+    pub bbox: Option<Bbox>,
+    /// Elements from the `--id-list` file that were found in the input
+    pub listed_found: HashSet<(Element, i64)>,
+    /// Data problems of the individual elements, only collected with `--emit-issues`
+    pub issues: Vec<Issue>,
+    /// Requests sent to each `--sparql-endpoint`
+    pub endpoints: Vec<EndpointStats>,
 }
 
 impl Stats {
+    /// Total number of elements seen, whether they were added, skipped, or deleted
+    pub fn elements(&self) -> u64 {
+        self.added_nodes
+            + self.added_ways
+            + self.added_rels
+            + self.skipped_nodes
+            + self.skipped_ways
+            + self.skipped_rels
+            + self.skipped_old
+            + self.skipped_unlisted
+            + self.skipped_types
+            + self.skipped_tagless
+            + self.skipped_outside_bbox
+            + self.deleted_nodes
+            + self.deleted_ways
+            + self.deleted_rels
+    }
+
+    /// Summary of the run written to the `--stats-json` file, including the totals and the elapsed time
+    pub fn to_json(&self, elapsed: Duration) -> JsonValue {
+        let added = self.added_nodes + self.added_ways + self.added_rels;
+        let deleted = self.deleted_nodes + self.deleted_ways + self.deleted_rels;
+        let mut warnings = JsonValue::new_object();
+        for (kind, count) in &self.warnings.0 {
+            warnings[kind.to_string().replace(' ', "_")] = (*count).into();
+        }
+        let bbox = self.bbox.map(|v| {
+            json::object! {
+                min_lat: v.min_lat,
+                min_lon: v.min_lon,
+                max_lat: v.max_lat,
+                max_lon: v.max_lon,
+            }
+        });
+        let mut json = json::object! {
+            added_nodes: self.added_nodes,
+            added_ways: self.added_ways,
+            added_rels: self.added_rels,
+            skipped_nodes: self.skipped_nodes,
+            skipped_ways: self.skipped_ways,
+            skipped_rels: self.skipped_rels,
+            skipped_old: self.skipped_old,
+            skipped_unlisted: self.skipped_unlisted,
+            skipped_types: self.skipped_types,
+            skipped_tagless: self.skipped_tagless,
+            skipped_outside_bbox: self.skipped_outside_bbox,
+            deleted_nodes: self.deleted_nodes,
+            deleted_ways: self.deleted_ways,
+            deleted_rels: self.deleted_rels,
+            cached_nodes: self.cached_nodes,
+            blocks: self.blocks,
+            elements: self.elements(),
+            added_total: added,
+            skipped_total: self.elements() - added - deleted,
+            deleted_total: deleted,
+            issues: self.issues.len(),
+            warnings: warnings,
+            bbox: bbox,
+            elapsed_secs: elapsed.as_secs_f64(),
+        };
+        if !self.endpoints.is_empty() {
+            let endpoints: Vec<_> = self.endpoints.iter().map(EndpointStats::to_json).collect();
+            json["sparql_endpoints"] = endpoints.into();
+        }
+        json
+    }
+
+    pub(crate) fn add_point(&mut self, lat: f64, lon: f64) {
+        let point = Bbox {
+            min_lat: lat,
+            min_lon: lon,
+            max_lat: lat,
+            max_lon: lon,
+        };
+        self.bbox = Some(self.bbox.map_or(point, |v| v.union(point)));
+    }
+
     pub(crate) fn combine(&mut self, other: Stats) {
         self.added_nodes += other.added_nodes;
         self.added_rels += other.added_rels;
         self.added_ways += other.added_ways;
         self.skipped_nodes += other.skipped_nodes;
+        self.skipped_ways += other.skipped_ways;
+        self.skipped_rels += other.skipped_rels;
+        self.skipped_old += other.skipped_old;
+        self.skipped_unlisted += other.skipped_unlisted;
+        self.skipped_types += other.skipped_types;
+        self.skipped_tagless += other.skipped_tagless;
+        self.skipped_outside_bbox += other.skipped_outside_bbox;
+        self.cached_nodes += other.cached_nodes;
         self.deleted_nodes += other.deleted_nodes;
         self.deleted_rels += other.deleted_rels;
         self.deleted_ways += other.deleted_ways;
         self.blocks += 1;
+        self.warnings.combine(other.warnings);
+        self.listed_found.extend(other.listed_found);
+        self.issues.extend(other.issues);
+        if let Some(bbox) = other.bbox {
+            self.bbox = Some(self.bbox.map_or(bbox, |v| v.union(bbox)));
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bbox {
+    pub min_lat: f64,
+    pub min_lon: f64,
+    pub max_lat: f64,
+    pub max_lon: f64,
+}
+
+impl Bbox {
+    pub fn union(self, other: Bbox) -> Bbox {
+        Bbox {
+            min_lat: self.min_lat.min(other.min_lat),
+            min_lon: self.min_lon.min(other.min_lon),
+            max_lat: self.max_lat.max(other.max_lat),
+            max_lon: self.max_lon.max(other.max_lon),
+        }
+    }
+
+    /// Check if the point is inside the box, including its edges
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        (self.min_lat..=self.max_lat).contains(&lat) && (self.min_lon..=self.max_lon).contains(&lon)
+    }
+}
+
+/// Kinds of data problems listed in the `issues.json` file, see `--emit-issues`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IssueKind {
+    BadKey,
+    InvalidContact,
+    GeometryError,
+    CentroidOutsideBbox,
+    NullIsland,
+    InvalidVersion,
+}
+
+impl Display for IssueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IssueKind::BadKey => write!(f, "bad_key"),
+            IssueKind::InvalidContact => write!(f, "invalid_contact"),
+            IssueKind::GeometryError => write!(f, "geometry_error"),
+            IssueKind::CentroidOutsideBbox => write!(f, "centroid_outside_bbox"),
+            IssueKind::NullIsland => write!(f, "null_island"),
+            IssueKind::InvalidVersion => write!(f, "invalid_version"),
+        }
+    }
+}
+
+/// Data problem of a single element, see `--emit-issues`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Issue {
+    pub elem: Element,
+    pub id: i64,
+    pub kind: IssueKind,
+    /// The original value that caused the issue, e.g. the bad tag key
+    pub value: Option<String>,
+}
+
+/// Kinds of data problems that are counted during the run and summarized at the end
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Warning {
+    DeleteSkipped,
+    GeometryError,
+    BadKey,
+    CentroidOutsideBbox,
+    TooFewNodes,
+    NullIsland,
+    ListedMissing,
+    InvalidVersion,
+    NegativeId,
+    Interrupted,
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::DeleteSkipped => write!(f, "deletes skipped"),
+            Warning::GeometryError => write!(f, "geometry errors"),
+            Warning::BadKey => write!(f, "bad keys"),
+            Warning::CentroidOutsideBbox => write!(f, "centroids outside bbox"),
+            Warning::TooFewNodes => write!(f, "ways with too few nodes"),
+            Warning::NullIsland => write!(f, "nodes at null island"),
+            Warning::ListedMissing => write!(f, "listed elements not found"),
+            Warning::InvalidVersion => write!(f, "invalid versions"),
+            Warning::NegativeId => write!(f, "negative ids"),
+            Warning::Interrupted => write!(f, "interrupted runs"),
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct Warnings(BTreeMap<Warning, u64>);
+
+impl Warnings {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, kind: Warning) -> u64 {
+        self.0.get(&kind).copied().unwrap_or_default()
+    }
+
+    pub fn add(&mut self, kind: Warning, count: u64) {
+        if count > 0 {
+            *self.0.entry(kind).or_default() += count;
+        }
+    }
+
+    pub fn combine(&mut self, other: Warnings) {
+        for (kind, count) in other.0 {
+            self.add(kind, count);
+        }
     }
 }
 
-#[derive(Debug)]
+impl Display for Warnings {
+    /// Summary of all warnings, e.g. "1423 deletes skipped, 57 geometry errors, 12 bad keys"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no warnings");
+        }
+        for (idx, (kind, count)) in self.0.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{count} {kind}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Element {
     Node,
     Way,
@@ -60,22 +309,34 @@ impl Display for Element {
     }
 }
 
+impl From<&RelMemberType> for Element {
+    fn from(value: &RelMemberType) -> Self {
+        match value {
+            RelMemberType::Node => Element::Node,
+            RelMemberType::Way => Element::Way,
+            RelMemberType::Relation => Element::Relation,
+        }
+    }
+}
+
+/// Metadata of an element. Any of the fields may be missing, e.g. in extracts stripped of the metadata.
+#[derive(Default)]
 pub struct ElementInfo<'a> {
     pub is_deleted: bool,
-    pub version: i32,
+    pub version: Option<i32>,
     pub user: Option<&'a str>,
-    pub milli_timestamp: i64,
-    pub changeset: i64,
+    pub milli_timestamp: Option<i64>,
+    pub changeset: Option<i64>,
 }
 
 impl<'a> From<Info<'a>> for ElementInfo<'a> {
     fn from(info: Info<'a>) -> Self {
         Self {
             is_deleted: info.deleted(),
-            version: info.version().unwrap(),
-            user: info.user().map(|v| v.unwrap()),
-            milli_timestamp: info.milli_timestamp().unwrap(),
-            changeset: info.changeset().unwrap(),
+            version: info.version(),
+            user: info.user().and_then(Result::ok),
+            milli_timestamp: info.milli_timestamp(),
+            changeset: info.changeset(),
         }
     }
 }
@@ -84,10 +345,10 @@ impl<'a> From<&DenseNodeInfo<'a>> for ElementInfo<'a> {
     fn from(info: &DenseNodeInfo<'a>) -> Self {
         Self {
             is_deleted: info.deleted(),
-            version: info.version(),
+            version: Some(info.version()),
             user: info.user().ok(),
-            milli_timestamp: info.milli_timestamp(),
-            changeset: info.changeset(),
+            milli_timestamp: Some(info.milli_timestamp()),
+            changeset: Some(info.changeset()),
         }
     }
 }
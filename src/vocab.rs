@@ -0,0 +1,194 @@
+use std::fmt::{Display, Write as _};
+
+use json::JsonValue;
+
+use crate::parser::{prefixes, OSM_BASE_URL};
+use crate::str_builder::{XsdStr, XsdValue};
+use crate::SchemaFormat;
+
+/// Predicate that may appear in the generated output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Predicate {
+    /// Prefixed name, e.g. `osmm:loc`
+    pub name: &'static str,
+    /// Prefixed name of the value datatype, or `rdfs:Resource` for IRI values
+    pub datatype: &'static str,
+    pub description: &'static str,
+}
+
+impl Display for Predicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+macro_rules! predicates {
+    ($($id:ident: $name:literal, $datatype:literal, $description:literal;)*) => {
+        $(
+            #[doc = $description]
+            pub const $id: Predicate = Predicate {
+                name: $name,
+                datatype: $datatype,
+                description: $description,
+            };
+        )*
+
+        /// All predicates with a fixed name, in the order they are described by the `schema` command
+        pub static PREDICATES: &[Predicate] = &[$($id),*];
+    };
+}
+
+predicates! {
+    TYPE: "osmm:type", "xsd:string", "Element type: `n` for nodes, `w` for ways, `r` for relations.";
+    LOC: "osmm:loc", "geo:wktLiteral", "Node location, or the centroid of a way.";
+    GEOHASH: "osmm:geohash", "xsd:string", "Geohash of the node location or the way centroid.";
+    LOC_ERROR: "osmm:loc:error", "xsd:string", "Reason why the way geometry could not be computed.";
+    LOC_PARTIAL: "osmm:loc:partial", "xsd:boolean", "Set when some route members are missing from the route geometry.";
+    LOC_WARNING: "osmm:locWarning", "xsd:string", "Suspicious node location, e.g. `null_island`.";
+    GEOMETRY_WARNING: "osmm:geometryWarning", "xsd:string", "Suspicious way geometry, e.g. `centroid_outside_bbox` or `too_few_nodes`.";
+    HAS_GEOMETRY: "osmm:hasGeometry", "xsd:boolean", "Set to false for the elements without a location.";
+    GEOMETRY: "osmm:geometry", "geo:wktLiteral", "Line string of the way nodes.";
+    ROUTE_GEOMETRY: "osmm:routeGeometry", "geo:wktLiteral", "Line strings of the member ways of a route relation.";
+    LABEL_LOC: "osmm:labelLoc", "geo:wktLiteral", "Location of the `label` member node of a relation.";
+    ADMIN_CENTRE_LOC: "osmm:adminCentreLoc", "geo:wktLiteral", "Location of the `admin_centre` member node of a relation.";
+    IS_CLOSED: "osmm:isClosed", "xsd:boolean", "Whether the first and the last way nodes are at the same location.";
+    NODE_COUNT: "osmm:nodeCount", "xsd:integer", "Number of node refs of a way, see `--emit-metrics`.";
+    LENGTH: "osmm:length", "xsd:decimal", "Great-circle length of a way in meters, see `--emit-metrics`.";
+    WAY_MEMBERSHIP_COUNT: "osmm:wayMembershipCount", "xsd:integer", "Number of ways using the node.";
+    TELEPHONE: "schema:telephone", "xsd:string", "Phone number from the `phone` or `contact:phone` tag in the E.164 form.";
+    EMAIL: "schema:email", "xsd:string", "Email address from the `email` or `contact:email` tag.";
+    CONTACT_WARNING: "osmm:contactWarning", "xsd:string", "Key of a contact tag with an invalid phone number or email.";
+    HAS: "osmm:has", "rdfs:Resource", "Relation member. The role is stored using the member IRI as the predicate.";
+    MEMBER: "osmm:member", "rdfs:Resource", "Blank node with the position, IRI, and role of a relation member, see `--emit-member-order`.";
+    MEMBER_INDEX: "osmm:index", "xsd:integer", "Position of a relation member in the member list, starting at 0.";
+    MEMBER_REF: "osmm:ref", "rdfs:Resource", "IRI of a relation member.";
+    MEMBER_ROLE: "osmm:role", "xsd:string", "Role of a relation member, omitted when empty.";
+    ADMIN_LEVEL: "osmm:adminLevel", "xsd:integer", "Numeric `admin_level` tag of a `type=boundary` relation.";
+    DANGLING_MEMBERS: "osmm:danglingMembers", "xsd:integer", "Number of relation members missing from the input, see `--validate-members`.";
+    ROLE_COUNT: "osmm:roleCount", "xsd:string", "Number of relation members with each role, e.g. `outer=3;inner=1`.";
+    BAD_KEY: "osmm:badkey", "xsd:string", "Tag key that cannot be used as a predicate name.";
+    BAD_WIKIDATA: "osmm:badWikidata", "xsd:string", "Value of a Wikidata tag that is not a valid item id.";
+    BAD_WIKIPEDIA: "osmm:badWikipedia", "xsd:string", "Value of a Wikipedia tag without the `lang:title` form.";
+    RAW_TAGS: "osmm:tags", "rdf:JSON", "All tags of the element as a JSON object, see `--emit-raw-tags-json`.";
+    HASH: "osmm:hash", "xsd:string", "Stable fingerprint of the element statements and version.";
+    VERSION: "osmm:version", "xsd:integer", "Element version.";
+    VERSION_WARNING: "osmm:versionWarning", "xsd:string", "Set to `invalid` when the element version is less than 1.";
+    IS_NEW: "osmm:isNew", "xsd:boolean", "Set to true for the elements with version 1, see `--emit-new-flag`.";
+    USER: "osmm:user", "xsd:string", "Name of the user who made the last change, or `osmm:anonymous` for anonymous edits.";
+    TIMESTAMP: "osmm:timestamp", "xsd:dateTime", "Time of the last change, or an `xsd:integer` with `--timestamp-format epoch-*`.";
+    CHANGESET: "osmm:changeset", "xsd:integer", "Changeset of the last change.";
+    DELETED: "osmm:deleted", "xsd:boolean", "Set on the elements deleted in a history or change file, written to the `deletes-NNNNNN` files.";
+    DATE_MODIFIED: "schema:dateModified", "xsd:dateTime", "Time of the latest change in the dataset, stated about `osmroot:`.";
+    DATASET_BBOX: "osmm:datasetBbox", "geo:wktLiteral", "Bounding box of all element locations, stated about `osmroot:`.";
+}
+
+/// Class of the elements, stated with `a` when using `--emit-rdf-type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Class {
+    /// Prefixed name, e.g. `osmm:Boundary`
+    pub name: &'static str,
+    /// Value of the relation `type` tag mapped to this class, if any
+    pub relation_type: Option<&'static str>,
+    pub description: &'static str,
+}
+
+impl XsdValue for Class {}
+impl Display for Class {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+macro_rules! classes {
+    ($($id:ident: $name:literal, $relation_type:expr, $description:literal;)*) => {
+        $(
+            #[doc = $description]
+            pub const $id: Class = Class {
+                name: $name,
+                relation_type: $relation_type,
+                description: $description,
+            };
+        )*
+
+        /// All element classes, in the order they are described by the `schema` command
+        pub static CLASSES: &[Class] = &[$($id),*];
+    };
+}
+
+classes! {
+    NODE: "osmm:Node", None, "OSM node.";
+    WAY: "osmm:Way", None, "OSM way.";
+    RELATION: "osmm:Relation", None, "OSM relation, also used for the relation types without a more specific class.";
+    MULTIPOLYGON: "osmm:Multipolygon", Some("multipolygon"), "Relation tagged with `type=multipolygon`.";
+    BOUNDARY: "osmm:Boundary", Some("boundary"), "Relation tagged with `type=boundary`.";
+    ROUTE: "osmm:Route", Some("route"), "Relation tagged with `type=route`.";
+    ROUTE_MASTER: "osmm:RouteMaster", Some("route_master"), "Relation tagged with `type=route_master`.";
+    RESTRICTION: "osmm:Restriction", Some("restriction"), "Relation tagged with `type=restriction`.";
+    PUBLIC_TRANSPORT: "osmm:PublicTransport", Some("public_transport"), "Relation tagged with `type=public_transport`.";
+    ASSOCIATED_STREET: "osmm:AssociatedStreet", Some("associatedStreet"), "Relation tagged with `type=associatedStreet`.";
+    SITE: "osmm:Site", Some("site"), "Relation tagged with `type=site`.";
+    WATERWAY: "osmm:Waterway", Some("waterway"), "Relation tagged with `type=waterway`.";
+}
+
+/// Get the class of a relation from the value of its `type` tag
+pub fn relation_class(relation_type: Option<&str>) -> Class {
+    relation_type
+        .and_then(|t| CLASSES.iter().find(|v| v.relation_type == Some(t)))
+        .copied()
+        .unwrap_or(RELATION)
+}
+
+/// Describe all predicates in the given format. The Turtle format also declares the element classes.
+pub fn describe(format: SchemaFormat) -> String {
+    match format {
+        SchemaFormat::Json => {
+            let predicates: Vec<_> = PREDICATES
+                .iter()
+                .map(|v| {
+                    json::object! {
+                        name: v.name,
+                        datatype: v.datatype,
+                        description: v.description,
+                    }
+                })
+                .collect();
+            let mut result = JsonValue::from(predicates).pretty(2);
+            result.push('\n');
+            result
+        }
+        SchemaFormat::Turtle => {
+            let mut result = String::new();
+            for prefix in prefixes(OSM_BASE_URL) {
+                writeln!(result, "@{prefix}.").unwrap();
+            }
+            writeln!(
+                result,
+                "@prefix rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#>."
+            )
+            .unwrap();
+            writeln!(
+                result,
+                "@prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#>."
+            )
+            .unwrap();
+            for v in PREDICATES {
+                let description = XsdStr(v.description);
+                write!(
+                    result,
+                    "\n{v}\n  a rdf:Property;\n  rdfs:range {};\n  rdfs:comment {description}.\n",
+                    v.datatype
+                )
+                .unwrap();
+            }
+            for v in CLASSES {
+                let description = XsdStr(v.description);
+                write!(result, "\n{v}\n  a rdfs:Class;\n").unwrap();
+                if v.relation_type.is_some() {
+                    writeln!(result, "  rdfs:subClassOf {RELATION};").unwrap();
+                }
+                writeln!(result, "  rdfs:comment {description}.").unwrap();
+            }
+            result
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use geo::{LineString, Point, Polygon};
+use osm2rdf::geometry::{centroid, geohash, is_within_bbox, polygon_centroid};
+use osm2rdf::CentroidMode;
+
+#[test]
+fn high_latitude_centroid() {
+    // A ring around the north pole at 80°N
+    let ring = LineString::from(vec![
+        (0.0, 80.0),
+        (90.0, 80.0),
+        (180.0, 80.0),
+        (-90.0, 80.0),
+        (0.0, 80.0),
+    ]);
+    let planar = centroid(&ring, CentroidMode::Planar).unwrap();
+    assert_eq!(planar.y(), 80.0);
+    let geodesic = centroid(&ring, CentroidMode::Geodesic).unwrap();
+    assert!(geodesic.y() > 89.999, "{geodesic:?}");
+
+    // Small features near the equator are nearly identical in both modes
+    let line = LineString::from(vec![(1.0, 1.0), (1.1, 1.0), (1.2, 1.1)]);
+    let planar = centroid(&line, CentroidMode::Planar).unwrap();
+    let geodesic = centroid(&line, CentroidMode::Geodesic).unwrap();
+    assert!((planar.x() - geodesic.x()).abs() < 1e-4);
+    assert!((planar.y() - geodesic.y()).abs() < 1e-4);
+
+    let single = LineString::from(vec![(7.0, 50.0)]);
+    assert_eq!(
+        centroid(&single, CentroidMode::Geodesic),
+        Some(Point::new(7.0, 50.0))
+    );
+}
+
+#[test]
+fn centroid_within_bbox() {
+    let line = LineString::from(vec![(10.0, 60.0), (10.5, 60.0), (11.0, 60.0)]);
+    for mode in [CentroidMode::Planar, CentroidMode::Geodesic] {
+        let point = centroid(&line, mode).unwrap();
+        assert!(is_within_bbox(&line, point), "{mode:?} {point:?}");
+    }
+
+    // One node with a bad location on the other side of the globe pulls the geodesic centroid over the pole
+    let line = LineString::from(vec![(10.0, 60.0), (10.5, 60.0), (-170.0, 60.0)]);
+    let point = centroid(&line, CentroidMode::Geodesic).unwrap();
+    assert!(!is_within_bbox(&line, point), "{point:?}");
+
+    assert!(!is_within_bbox(&line, Point::new(0.0, 0.0)));
+}
+
+#[test]
+fn area_centroid() {
+    // Right triangle: the boundary centroid is pulled towards the long hypotenuse
+    let ring = LineString::from(vec![(0.0, 0.0), (3.0, 0.0), (0.0, 4.0), (0.0, 0.0)]);
+    let boundary = centroid(&ring, CentroidMode::Planar).unwrap();
+    assert_eq!(boundary, Point::new(1.0, 1.5));
+    let area = polygon_centroid(&Polygon::new(ring, vec![]), CentroidMode::Planar).unwrap();
+    assert!((area.x() - 1.0).abs() < 1e-12, "{area:?}");
+    assert!((area.y() - 4.0 / 3.0).abs() < 1e-12, "{area:?}");
+
+    // Small polygons are nearly identical in both modes, regardless of the ring orientation
+    let ccw = vec![(0.0, 0.0), (0.003, 0.0), (0.0, 0.004), (0.0, 0.0)];
+    let cw: Vec<_> = ccw.iter().rev().copied().collect();
+    for ring in [ccw, cw] {
+        let polygon = Polygon::new(LineString::from(ring), vec![]);
+        let geodesic = polygon_centroid(&polygon, CentroidMode::Geodesic).unwrap();
+        assert!((geodesic.x() - 0.001).abs() < 1e-9, "{geodesic:?}");
+        assert!((geodesic.y() - 0.004 / 3.0).abs() < 1e-9, "{geodesic:?}");
+    }
+
+    // Polygons without an area use the boundary centroid
+    let flat = LineString::from(vec![(1.0, 1.0), (2.0, 1.0), (1.0, 1.0), (1.0, 1.0)]);
+    let polygon = Polygon::new(flat.clone(), vec![]);
+    for mode in [CentroidMode::Planar, CentroidMode::Geodesic] {
+        let point = polygon_centroid(&polygon, mode).unwrap();
+        assert!((point.x() - 1.5).abs() < 1e-4, "{mode:?} {point:?}");
+        assert!((point.y() - 1.0).abs() < 1e-4, "{mode:?} {point:?}");
+    }
+}
+
+#[test]
+fn geohash_values() {
+    assert_eq!(geohash(57.64911, 10.40744, 11), "u4pruydqqvj");
+    assert_eq!(geohash(42.6, -5.6, 5), "ezs42");
+    assert_eq!(geohash(-25.382708, -49.265506, 8), "6gkzwgjz");
+    assert_eq!(geohash(0.0, 0.0, 4), "s000");
+    assert_eq!(geohash(-90.0, -180.0, 3), "000");
+    // Shorter geohashes are prefixes of the longer ones
+    assert!(geohash(57.64911, 10.40744, 12).starts_with(&geohash(57.64911, 10.40744, 6)));
+}
@@ -0,0 +1,56 @@
+use std::io::{Read, Write};
+
+use flate2::read::GzDecoder;
+use osm2rdf::gzip::GzStrategyEncoder;
+use osm2rdf::DeflateStrategy;
+
+fn sample() -> String {
+    (0..2000)
+        .map(|id| {
+            format!(
+                "osmnode:{id}\nosmt:name \"Node {id}\";\nosmm:type \"n\";\nosmm:version \"{}\"^^xsd:integer.\n\n",
+                id % 7
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn round_trip() {
+    let data = sample();
+    for strategy in [
+        DeflateStrategy::Default,
+        DeflateStrategy::Filtered,
+        DeflateStrategy::Rle,
+    ] {
+        for level in [0, 1, 6, 9] {
+            let mut enc = GzStrategyEncoder::new(Vec::new(), level, strategy).unwrap();
+            let (first, second) = data.split_at(data.len() / 3);
+            enc.write_all(first.as_bytes()).unwrap();
+            // A flushed stream is still a single valid gzip member
+            enc.flush().unwrap();
+            enc.write_all(second.as_bytes()).unwrap();
+            let compressed = enc.finish().unwrap();
+            if level > 0 {
+                assert!(compressed.len() < data.len(), "{strategy:?} {level}");
+            }
+
+            let mut result = String::new();
+            GzDecoder::new(compressed.as_slice())
+                .read_to_string(&mut result)
+                .unwrap();
+            assert_eq!(result, data, "{strategy:?} {level}");
+        }
+    }
+}
+
+#[test]
+fn empty_stream() {
+    let enc = GzStrategyEncoder::new(Vec::new(), 6, DeflateStrategy::Rle).unwrap();
+    let compressed = enc.finish().unwrap();
+    let mut result = Vec::new();
+    GzDecoder::new(compressed.as_slice())
+        .read_to_end(&mut result)
+        .unwrap();
+    assert!(result.is_empty());
+}
@@ -0,0 +1,149 @@
+use json::JsonValue;
+use osm2rdf::jsonld::JsonLd;
+use osm2rdf::ntriples::NTriples;
+use osm2rdf::turtle::Converter;
+
+const PREFIXES: [&str; 6] = [
+    "prefix xsd: <http://www.w3.org/2001/XMLSchema#>",
+    "prefix wd: <http://www.wikidata.org/entity/>",
+    "prefix osmroot: <https://www.openstreetmap.org>",
+    "prefix osmnode: <https://www.openstreetmap.org/node/>",
+    "prefix osmt: <https://wiki.openstreetmap.org/wiki/Key:>",
+    "prefix osmm: <https://www.openstreetmap.org/meta/>",
+];
+
+const STATEMENTS: &str = concat!(
+    "osmt:name \"Café; \\\"A, B.\\\"\";\n",
+    "osmt:name:fr \"Café\"@fr;\n",
+    "osmt:wikidata wd:Q1,wd:Q2;\n",
+    "osmt:wikipedia <https://en.wikipedia.org/wiki/A.B>;\n",
+    "a osmm:Node;\n",
+    "osmm:member [osmm:index \"0\"^^xsd:integer; osmm:ref osmnode:2; osmm:role \"a]b\"];\n",
+    "osmm:member [osmm:ref osmnode:3];\n",
+    "osmm:tags \"{\\\"name\\\":\\\"Café\\\"}\"^^<http://www.w3.org/1999/02/22-rdf-syntax-ns#JSON>;\n",
+    "osmm:version \"1\"^^xsd:integer.\n",
+);
+
+fn convert(converter: &impl Converter, subject: &str, statements: &str) -> String {
+    let mut out = Vec::new();
+    let len = converter
+        .write_element(&mut out, subject, statements)
+        .unwrap();
+    assert_eq!(len, out.len());
+    String::from_utf8(out).unwrap()
+}
+
+/// Expand a compact IRI using the context, or keep an absolute IRI as is
+fn expand(context: &JsonValue, name: &str) -> String {
+    if name.starts_with("http") {
+        return format!("<{name}>");
+    }
+    let (prefix, local) = name.split_once(':').unwrap();
+    let iri = context["@context"][prefix].as_str().unwrap();
+    format!("<{iri}{local}>")
+}
+
+/// Convert a JSON-LD node object back into N-Triples, labeling the blank nodes the same way as `NTriples`
+fn to_ntriples(
+    context: &JsonValue,
+    subject: &str,
+    node: &JsonValue,
+    blank_nodes: &mut usize,
+    result: &mut Vec<String>,
+) {
+    for (key, values) in node.entries() {
+        if key == "@context" || key == "@id" {
+            continue;
+        }
+        let predicate = match key {
+            "@type" => "<http://www.w3.org/1999/02/22-rdf-syntax-ns#type>".to_string(),
+            _ => expand(context, key),
+        };
+        let values: Vec<_> = match values {
+            JsonValue::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let object = if key == "@type" {
+                expand(context, value.as_str().unwrap())
+            } else if value.has_key("@id") {
+                expand(context, value["@id"].as_str().unwrap())
+            } else if value.has_key("@value") {
+                let text = json::stringify(value["@value"].clone());
+                if value.has_key("@type") {
+                    format!(
+                        "{text}^^{}",
+                        expand(context, value["@type"].as_str().unwrap())
+                    )
+                } else {
+                    format!("{text}@{}", value["@language"])
+                }
+            } else if value.is_string() {
+                json::stringify(value.clone())
+            } else {
+                let label = format!("_:osmnode_1_{blank_nodes}");
+                *blank_nodes += 1;
+                result.push(format!("{subject} {predicate} {label} ."));
+                to_ntriples(context, &label, value, blank_nodes, result);
+                continue;
+            };
+            result.push(format!("{subject} {predicate} {object} ."));
+        }
+    }
+}
+
+#[test]
+fn round_trip() {
+    let jsonld = JsonLd::new(PREFIXES);
+    let context = jsonld.context();
+    let result = convert(&jsonld, "osmnode:1", STATEMENTS);
+    assert_eq!(result.lines().count(), 1);
+    let element = json::parse(&result).unwrap();
+    assert_eq!(element["@context"], "context.jsonld");
+    assert_eq!(element["@id"], "osmnode:1");
+    assert_eq!(element["@type"], "osmm:Node");
+    assert_eq!(element["osmt:name"], "Café; \"A, B.\"");
+    assert_eq!(
+        element["osmt:wikidata"],
+        json::array![{"@id": "wd:Q1"}, {"@id": "wd:Q2"}]
+    );
+    assert_eq!(
+        element["osmm:version"],
+        json::object! {"@value": "1", "@type": "xsd:integer"}
+    );
+    assert_eq!(element["osmm:member"].len(), 2);
+
+    let mut triples = Vec::new();
+    to_ntriples(
+        &context,
+        "<https://www.openstreetmap.org/node/1>",
+        &element,
+        &mut 0,
+        &mut triples,
+    );
+    let expected = convert(&NTriples::new(PREFIXES), "osmnode:1", STATEMENTS);
+    let mut expected: Vec<_> = expected.lines().collect();
+    triples.sort();
+    expected.sort();
+    assert_eq!(triples, expected);
+}
+
+#[test]
+fn context() {
+    let jsonld = JsonLd::new(PREFIXES);
+    let context = jsonld.context();
+    assert_eq!(
+        context["@context"]["osmnode"],
+        "https://www.openstreetmap.org/node/"
+    );
+    // Prefixes without a trailing delimiter cannot be used in compact IRIs, so they are expanded
+    assert!(!context["@context"].has_key("osmroot"));
+    let result = convert(&jsonld, "osmroot:", "osmm:version \"1\"^^xsd:integer.");
+    let element = json::parse(&result).unwrap();
+    assert_eq!(element["@id"], "https://www.openstreetmap.org");
+
+    let mut out = Vec::new();
+    assert!(jsonld
+        .write_element(&mut out, "osmnode:1", "foo:bar \"baz\".")
+        .is_err());
+}
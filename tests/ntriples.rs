@@ -0,0 +1,99 @@
+use osm2rdf::ntriples::NTriples;
+use osm2rdf::turtle::Converter as _;
+
+fn convert(statements: &str) -> String {
+    let ntriples = NTriples::new([
+        "prefix xsd: <http://www.w3.org/2001/XMLSchema#>",
+        "prefix wd: <http://www.wikidata.org/entity/>",
+        "prefix osmnode: <https://www.openstreetmap.org/node/>",
+        "prefix osmt: <https://wiki.openstreetmap.org/wiki/Key:>",
+        "prefix osmm: <https://www.openstreetmap.org/meta/>",
+    ]);
+    let mut out = Vec::new();
+    let len = ntriples
+        .write_element(&mut out, "osmnode:1", statements)
+        .unwrap();
+    assert_eq!(len, out.len());
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn expand_statements() {
+    let result = convert(concat!(
+        "osmt:name \"Caf\\u00e9; \\\"A, B.\\\"\";\n",
+        "osmt:name:fr \"Café\"@fr;\n",
+        "osmt:wikidata wd:Q1,wd:Q2;\n",
+        "osmt:wikipedia <https://en.wikipedia.org/wiki/A.B>;\n",
+        "a osmm:Node;\n",
+        "osmm:version \"1\"^^xsd:integer.\n",
+    ));
+    let s = "<https://www.openstreetmap.org/node/1>";
+    let t = "<https://wiki.openstreetmap.org/wiki/Key:";
+    let expected = [
+        format!("{s} {t}name> \"Caf\\u00e9; \\\"A, B.\\\"\" ."),
+        format!("{s} {t}name:fr> \"Café\"@fr ."),
+        format!("{s} {t}wikidata> <http://www.wikidata.org/entity/Q1> ."),
+        format!("{s} {t}wikidata> <http://www.wikidata.org/entity/Q2> ."),
+        format!("{s} {t}wikipedia> <https://en.wikipedia.org/wiki/A.B> ."),
+        format!(
+            "{s} <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <https://www.openstreetmap.org/meta/Node> ."
+        ),
+        format!(
+            "{s} <https://www.openstreetmap.org/meta/version> \"1\"^^<http://www.w3.org/2001/XMLSchema#integer> ."
+        ),
+    ];
+    assert_eq!(result.lines().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn prefixed_keys() {
+    let result = convert("osmt:addr:housenumber \"12\";\nosmt:ref:FR:FANTOIR \"751010001A\".\n");
+    let s = "<https://www.openstreetmap.org/node/1>";
+    let t = "<https://wiki.openstreetmap.org/wiki/Key:";
+    let expected = [
+        format!("{s} {t}addr:housenumber> \"12\" ."),
+        format!("{s} {t}ref:FR:FANTOIR> \"751010001A\" ."),
+    ];
+    assert_eq!(result.lines().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn blank_nodes() {
+    let result = convert(concat!(
+        "osmm:member [osmm:index \"0\"^^xsd:integer; osmm:ref osmnode:2; osmm:role \"a]b\"];\n",
+        "osmm:member [osmm:ref osmnode:3];\n",
+        "osmm:version \"1\"^^xsd:integer.\n",
+    ));
+    let s = "<https://www.openstreetmap.org/node/1>";
+    let m = "<https://www.openstreetmap.org/meta/";
+    let expected = [
+        format!("{s} {m}member> _:osmnode_1_0 ."),
+        format!("_:osmnode_1_0 {m}index> \"0\"^^<http://www.w3.org/2001/XMLSchema#integer> ."),
+        format!("_:osmnode_1_0 {m}ref> <https://www.openstreetmap.org/node/2> ."),
+        format!("_:osmnode_1_0 {m}role> \"a]b\" ."),
+        format!("{s} {m}member> _:osmnode_1_1 ."),
+        format!("_:osmnode_1_1 {m}ref> <https://www.openstreetmap.org/node/3> ."),
+        format!("{s} {m}version> \"1\"^^<http://www.w3.org/2001/XMLSchema#integer> ."),
+    ];
+    assert_eq!(result.lines().collect::<Vec<_>>(), expected);
+
+    let ntriples = NTriples::new(["prefix osmm: <https://www.openstreetmap.org/meta/>"]);
+    for statements in [
+        "osmm:member [osmm:index osmm:a.",
+        "osmm:a osmm:b].",
+        "[osmm:a osmm:b].",
+    ] {
+        assert!(ntriples
+            .write_element(&mut Vec::new(), "osmm:x", statements)
+            .is_err());
+    }
+}
+
+#[test]
+fn unknown_prefix() {
+    let ntriples = NTriples::new(["prefix osmnode: <https://www.openstreetmap.org/node/>"]);
+    let mut out = Vec::new();
+    assert!(ntriples
+        .write_element(&mut out, "osmnode:1", "foo:bar \"baz\".")
+        .is_err());
+}
@@ -1,27 +1,37 @@
+use std::collections::BTreeSet;
 use std::fs::File;
-use std::io::Read;
+use std::io::{BufRead as _, BufReader, Cursor, Read, Write as _};
+use std::net::TcpListener;
 use std::panic::catch_unwind;
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use clap::Parser as _;
 use flate2::read::GzDecoder;
 use insta::glob;
-use osm2rdf::parser::Parser;
-use osm2rdf::utils::Stats;
-use osm2rdf::{parser, Args};
+use osm2rdf::bench::Codec;
+use osm2rdf::input::PbfReader;
+use osm2rdf::parser::{Parser, PrePass};
+use osm2rdf::proto::{Element, ElementType};
+use osm2rdf::utils::{Stats, Warning};
+use osm2rdf::{bench, check, compact, parser, vocab, Args, ParserOpts, PrefixFile, SchemaFormat};
 use osmnodecache::{CacheStore, HashMapCache};
 use osmpbf::{BlobDecode, BlobReader};
+use prost::Message as _;
 use temp_dir::TempDir;
 
 #[test]
 fn decode_osm_pbf_files() {
     glob!("../tests/fixtures", "**/*.os*.pbf", |file| {
-        if let Err(_) = catch_unwind(|| {
+        if catch_unwind(|| {
             let reader = BlobReader::from_path(file).unwrap();
             let cache = HashMapCache::new();
             let stats = Mutex::new(Stats::default());
-            let mut parser = Parser::new(&stats, cache.get_accessor(), 100);
+            let opts = ParserOpts::default();
+            let pre_pass = PrePass::default();
+            let mut parser = Parser::new(&stats, cache.get_accessor(), 100, &opts, &pre_pass);
 
             let mut result = Vec::new();
             for blob in reader {
@@ -30,7 +40,9 @@ fn decode_osm_pbf_files() {
                 };
             }
             insta::assert_debug_snapshot!(result);
-        }) {
+        })
+        .is_err()
+        {
             panic!("Error while parsing file {}", file.display());
         }
     });
@@ -47,7 +59,7 @@ fn generate_ttl() {
         .join("fixtures/osm2rdf/dense_test1.osm.pbf");
 
     // Parse a test file, generating output files in the temp directory
-    parser::parse(Args::parse_from(&[
+    parser::parse(Args::parse_from([
         "osm2rdf",
         "parse",
         test_file.to_str().unwrap(),
@@ -65,3 +77,2146 @@ fn generate_ttl() {
         insta::assert_display_snapshot!(ttl_file_content);
     });
 }
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(file!())
+        .parent()
+        .unwrap()
+        .join("fixtures")
+        .join(name)
+}
+
+/// Run the `parse` command in a new temp directory, and return the directory with the generated files
+fn parse_to_dir(global_args: &[&str], inputs: &[&Path], parse_args: &[&str]) -> TempDir {
+    let temp_dir = TempDir::new().unwrap();
+    let mut args = vec!["osm2rdf"];
+    args.extend(global_args);
+    args.push("parse");
+    args.extend(inputs.iter().map(|v| v.to_str().unwrap()));
+    args.push(temp_dir.path().to_str().unwrap());
+    args.extend(parse_args);
+    parser::parse(Args::parse_from(args)).unwrap();
+    temp_dir
+}
+
+/// Get all files in a directory, sorted by name
+fn list_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|v| v.unwrap().path())
+        .collect();
+    files.sort();
+    files
+}
+
+/// Run the `parse` command in a new temp directory,
+/// and return the content of all generated files concatenated in the order of their names.
+fn run_parse(global_args: &[&str], inputs: &[&Path], parse_args: &[&str]) -> String {
+    let temp_dir = parse_to_dir(global_args, inputs, parse_args);
+    let mut result = String::new();
+    for file in list_files(temp_dir.path()) {
+        GzDecoder::new(File::open(file).unwrap())
+            .read_to_string(&mut result)
+            .unwrap();
+    }
+    result
+}
+
+/// Get all lines of a single element from the generated output, e.g. `osmway:20`
+fn element<'a>(output: &'a str, subject: &str) -> &'a str {
+    let start = output
+        .find(&format!("\n{subject}\n"))
+        .unwrap_or_else(|| panic!("{subject} not found in output"));
+    let value = &output[start + 1..];
+    let value = value.split("\n\n").next().unwrap();
+    // The last element of a file is directly followed by the prefixes of the next file
+    value.split("\n@prefix ").next().unwrap()
+}
+
+#[test]
+fn small_cache_across_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let cache = temp_dir.path().join("nodes.cache");
+    let cache = cache.to_str().unwrap();
+    let data = fixture("libosmium/examples_t_location_cache_data.osm.pbf");
+    let way = fixture("libosmium/examples_t_location_cache_way.osm.pbf");
+
+    // Overlapping files parsed in a single run share the node cache
+    let combined = run_parse(&[], &[&data, &way], &[]);
+    let expected = element(&combined, "osmway:20");
+    assert!(!expected.contains("Point(0 0)"));
+
+    // The same files parsed in two runs, keeping the node cache in between
+    run_parse(&["--small-cache", cache], &[&data], &[]);
+    let resumed = run_parse(&["--small-cache", cache], &[&way], &[]);
+    assert_eq!(element(&resumed, "osmway:20"), expected);
+}
+
+#[test]
+fn warnings_summary() {
+    let temp_dir = TempDir::new().unwrap();
+    let stats = parser::parse(Args::parse_from([
+        "osm2rdf",
+        "parse",
+        fixture("osm2rdf/dense_delete.osh.pbf").to_str().unwrap(),
+        fixture("osm2rdf/dense_test1.osm.pbf").to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+    ]))
+    .unwrap();
+    assert_eq!(stats.warnings.get(Warning::DeleteSkipped), 0);
+    assert_eq!(stats.warnings.to_string(), "1 bad keys");
+
+    // The protobuf format cannot represent the deleted elements
+    let temp_dir = TempDir::new().unwrap();
+    let stats = parser::parse(Args::parse_from([
+        "osm2rdf",
+        "parse",
+        fixture("osm2rdf/dense_delete.osh.pbf").to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+        "--format",
+        "protobuf",
+    ]))
+    .unwrap();
+    assert_eq!(stats.warnings.to_string(), "3 deletes skipped");
+}
+
+#[test]
+fn deletes() {
+    let file = fixture("osm2rdf/dense_delete.osh.pbf");
+    let temp_dir = parse_to_dir(&[], &[&file], &[]);
+    let files = list_files(temp_dir.path());
+    let names: Vec<_> = files.iter().map(|v| v.file_name().unwrap()).collect();
+    assert_eq!(names, ["deletes-000000.ttl.gz", "osm-000000.ttl.gz"]);
+    let mut result = String::new();
+    GzDecoder::new(File::open(&files[0]).unwrap())
+        .read_to_string(&mut result)
+        .unwrap();
+    assert!(result.starts_with("@prefix "));
+    for subject in ["osmnode:1", "osmway:2", "osmrel:3"] {
+        let line = format!("\n{subject} osmm:deleted \"true\"^^xsd:boolean.\n");
+        assert!(result.contains(&line), "{result}");
+    }
+
+    let result = run_parse(&[], &[&file], &["--upsert"]);
+    assert!(result.contains("\nDELETE WHERE { osmway:2 ?p ?o };\n"));
+    let result = run_parse(&[], &[&file], &["--format", "ntriples"]);
+    assert!(result.contains(concat!(
+        "<https://www.openstreetmap.org/way/2> <https://www.openstreetmap.org/meta/deleted> ",
+        "\"true\"^^<http://www.w3.org/2001/XMLSchema#boolean> .\n"
+    )));
+}
+
+#[test]
+fn drop_bad_keys() {
+    let temp_dir = TempDir::new().unwrap();
+    let stats = parser::parse(Args::parse_from([
+        "osm2rdf",
+        "parse",
+        fixture("osm2rdf/dense_test1.osm.pbf").to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+        "--drop-bad-keys",
+    ]))
+    .unwrap();
+    assert_eq!(stats.warnings.get(Warning::BadKey), 1);
+    let mut result = String::new();
+    for file in list_files(temp_dir.path()) {
+        GzDecoder::new(File::open(file).unwrap())
+            .read_to_string(&mut result)
+            .unwrap();
+    }
+    let node = element(&result, "osmnode:14");
+    assert!(!node.contains("osmm:badkey"));
+    assert!(node.contains("osmt:test \"node\";"));
+}
+
+#[test]
+fn no_type_predicate() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    assert!(run_parse(&[], &[&file], &[]).contains("osmm:type"));
+    let result = run_parse(&[], &[&file], &["--no-type-predicate"]);
+    assert!(!result.contains("osmm:type"));
+    assert!(result.contains("osmt:highway"));
+}
+
+#[test]
+fn modified_since() {
+    let old_way = fixture("libosmium/examples_t_pub_names_pub-way.osm.pbf");
+    let new_way = fixture("libosmium/examples_t_amenity_list_area.osm.pbf");
+    let files: &[&Path] = &[&old_way, &new_way];
+    let result = run_parse(&[], files, &["--modified-since", "2015-01-01"]);
+    assert!(!result.contains("osmway:40948654"));
+    assert!(!result.contains("\nosmnode:"));
+    // Nodes are too old, but they are still used for the way geometry
+    assert!(!element(&result, "osmway:274425514").contains("Point(0 0)"));
+
+    let result = run_parse(&[], files, &["--modified-since", "2009-09-17T09:27:41Z"]);
+    assert!(result.contains("osmway:40948654"));
+}
+
+#[test]
+fn membership_count() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--emit-membership-count"]);
+    // Node 11 is shared by ways 20 and 21
+    let node = element(&result, "osmnode:11");
+    assert!(node.contains("osmm:wayMembershipCount \"2\"^^xsd:integer;"));
+    assert!(!result.contains("osmnode:10\n"));
+    assert!(!element(&result, "osmnode:14").contains("osmm:wayMembershipCount"));
+}
+
+#[test]
+fn skip_multipolygon_ways() {
+    let file = fixture("libosmium/examples_t_read_data.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--skip-multipolygon-ways"]);
+    // Member ways of the multipolygon have their own tags, so they are kept
+    assert!(element(&result, "osmway:701800").contains("osmt:test:id \"701\";"));
+    assert!(element(&result, "osmway:701801").contains("osmt:test:id \"701\";"));
+    assert!(element(&result, "osmrel:701900").contains("osmm:has osmway:701800;"));
+}
+
+#[test]
+fn has_geometry() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--emit-has-geometry"]);
+    let relation = element(&result, "osmrel:30");
+    assert!(relation.contains("osmm:hasGeometry \"false\"^^xsd:boolean;"));
+    let way = element(&result, "osmway:20");
+    assert!(way.contains("osmm:loc "));
+    assert!(!way.contains("osmm:hasGeometry"));
+    assert!(!way.contains("osmm:loc:error"));
+
+    // Relations with a computed location have a geometry
+    let file = fixture("osm2rdf/dense_boundary.osm.pbf");
+    let args = ["--emit-has-geometry", "--emit-special-points"];
+    let result = run_parse(&[], &[&file], &args);
+    assert!(!element(&result, "osmrel:20").contains("osmm:hasGeometry"));
+    let file = fixture("osm2rdf/dense_route.osm.pbf");
+    let args = ["--emit-has-geometry", "--route-geometry"];
+    let result = run_parse(&[], &[&file], &args);
+    assert!(!element(&result, "osmrel:20").contains("osmm:hasGeometry"));
+
+    let result = run_parse(&[], &[&file], &[]);
+    assert!(!result.contains("osmm:hasGeometry"));
+}
+
+#[test]
+fn degenerate_ways() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = fixture("osm2rdf/nodense_degenerate.osm.pbf");
+    let stats = parser::parse(Args::parse_from([
+        "osm2rdf",
+        "parse",
+        file.to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+    ]))
+    .unwrap();
+    assert_eq!(stats.warnings.get(Warning::GeometryError), 1);
+    assert_eq!(stats.warnings.get(Warning::TooFewNodes), 0);
+
+    let result = run_parse(&[], &[&file], &["--emit-has-geometry"]);
+    let way = element(&result, "osmway:10");
+    assert!(!way.contains("osmm:hasGeometry"));
+    assert!(way.contains("osmm:loc:error \"no_nodes\";"));
+    assert!(!way.contains("osmm:isClosed"));
+    // Single-node ways are located at their node by default
+    let way = element(&result, "osmway:11");
+    assert!(way.contains("osmm:loc "));
+    assert!(!way.contains("osmm:hasGeometry"));
+    assert!(!way.contains("osmm:geometryWarning"));
+    assert!(!way.contains("osmm:isClosed"));
+
+    let args = ["--emit-has-geometry", "--min-way-nodes", "2"];
+    let result = run_parse(&[], &[&file], &args);
+    let way = element(&result, "osmway:11");
+    assert!(way.contains("osmm:hasGeometry \"false\"^^xsd:boolean;"));
+    assert!(way.contains("osmm:geometryWarning \"too_few_nodes\""));
+    assert!(!way.contains("osmm:loc"));
+    assert!(!way.contains("osmm:isClosed"));
+}
+
+#[test]
+fn min_way_nodes() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = fixture("osm2rdf/nodense_degenerate.osm.pbf");
+    let stats = parser::parse(Args::parse_from([
+        "osm2rdf",
+        "parse",
+        file.to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+        "--min-way-nodes",
+        "2",
+    ]))
+    .unwrap();
+    assert_eq!(stats.warnings.get(Warning::TooFewNodes), 1);
+
+    // A single-node way is below the limit of 2, a closed way with four nodes is not
+    let result = run_parse(&[], &[&file], &["--min-way-nodes", "2"]);
+    let way = element(&result, "osmway:11");
+    assert!(way.contains("osmm:geometryWarning \"too_few_nodes\""));
+    assert!(!way.contains("osmm:loc"));
+    let closed = fixture("osm2rdf/dense_closed.osm.pbf");
+    let result = run_parse(&[], &[&closed], &["--min-way-nodes", "2"]);
+    let way = element(&result, "osmway:10");
+    assert!(way.contains("osmm:isClosed \"true\"^^xsd:boolean;"));
+    assert!(way.contains("osmm:loc "));
+    assert!(!way.contains("osmm:geometryWarning"));
+
+    let result = run_parse(&[], &[&closed], &["--min-way-nodes", "5"]);
+    let way = element(&result, "osmway:10");
+    assert!(way.contains("osmm:geometryWarning \"too_few_nodes\""));
+    assert!(!way.contains("osmm:isClosed"));
+    assert!(!way.contains("osmm:loc"));
+}
+
+#[test]
+fn route_geometry() {
+    let file = fixture("osm2rdf/dense_route.osm.pbf");
+    let result = run_parse(
+        &[],
+        &[&file],
+        &["--route-geometry", "--coord-precision", "3"],
+    );
+    // Way 12 is not in the file, the other ways are kept in the member order
+    let bus = element(&result, "osmrel:20");
+    assert!(bus.contains(
+        r#"osmm:routeGeometry "GEOMETRYCOLLECTION(LINESTRING(1 0, 2 0), LINESTRING(0 0, 1 0))"^^geo:wktLiteral;"#
+    ));
+    assert!(bus.contains("osmm:loc:partial \"true\"^^xsd:boolean;"));
+    let hiking = element(&result, "osmrel:21");
+    assert!(hiking.contains(r#""GEOMETRYCOLLECTION(LINESTRING(0 0, 1 0))"^^geo:wktLiteral;"#));
+    assert!(!hiking.contains("osmm:loc:partial"));
+    assert!(!element(&result, "osmway:10").contains("osmm:routeGeometry"));
+
+    let result = run_parse(&[], &[&file], &[]);
+    assert!(!result.contains("osmm:routeGeometry"));
+
+    // Nodes 3-5 are not in the file, so way 11 has no line left
+    let file = fixture("osm2rdf/dense_partial_route.osm.pbf");
+    let args = [
+        "--route-geometry",
+        "--emit-has-geometry",
+        "--coord-precision",
+        "3",
+    ];
+    let result = run_parse(&[], &[&file], &args);
+    let bus = element(&result, "osmrel:20");
+    assert!(bus.contains(r#""GEOMETRYCOLLECTION(LINESTRING(0 0, 1 0))"^^geo:wktLiteral;"#));
+    assert!(bus.contains("osmm:loc:partial \"true\"^^xsd:boolean;"));
+    let hiking = element(&result, "osmrel:21");
+    assert!(!hiking.contains("osmm:routeGeometry"));
+    assert!(hiking.contains("osmm:loc:partial \"true\"^^xsd:boolean;"));
+    assert!(hiking.contains("osmm:hasGeometry \"false\"^^xsd:boolean;"));
+}
+
+#[test]
+fn special_points() {
+    let file = fixture("osm2rdf/dense_boundary.osm.pbf");
+    let args = ["--emit-special-points", "--coord-precision", "3"];
+    let result = run_parse(&[], &[&file], &args);
+    let relation = element(&result, "osmrel:20");
+    assert!(relation.contains("osmm:adminCentreLoc \"Point(2 1)\"^^geo:wktLiteral;"));
+    assert!(relation.contains("osmm:labelLoc \"Point(2.5 1.5)\"^^geo:wktLiteral;"));
+
+    let result = run_parse(&[], &[&file], &[]);
+    assert!(!result.contains("osmm:labelLoc"));
+    assert!(!result.contains("osmm:adminCentreLoc"));
+}
+
+#[test]
+fn admin_level() {
+    let file = fixture("osm2rdf/dense_boundary.osm.pbf");
+    let result = run_parse(&[], &[&file], &[]);
+    let relation = element(&result, "osmrel:20");
+    assert!(relation.contains("osmt:admin_level \"4\";\nosmm:adminLevel \"4\"^^xsd:integer;\n"));
+    // Only boundary relations get the numeric level
+    let file = fixture("osm2rdf/dense_route.osm.pbf");
+    assert!(!run_parse(&[], &[&file], &[]).contains("osmm:adminLevel"));
+}
+
+#[test]
+fn emit_index() {
+    let files = [
+        fixture("osm2rdf/dense_test1.osm.pbf"),
+        fixture("osm2rdf/dense_route.osm.pbf"),
+    ];
+    let inputs: Vec<_> = files.iter().map(PathBuf::as_path).collect();
+    let dir = parse_to_dir(&[], &inputs, &["--emit-index"]);
+    let data = std::fs::read(dir.path().join("osm-000000.ttl.gz")).unwrap();
+    let index = std::fs::read_to_string(dir.path().join("osm-000000.ttl.gz.gzi")).unwrap();
+    let blocks: Vec<Vec<_>> = index.lines().map(|v| v.split('\t').collect()).collect();
+    // Each input blob with elements is written as a separate gzip member, with its first and last element.
+    // The nodes of dense_route have no tags, so its nodes blob has no member.
+    let ids: Vec<_> = blocks.iter().map(|v| &v[1..]).collect();
+    assert_eq!(
+        ids,
+        [
+            ["osmnode:14", "osmnode:14"],
+            ["osmway:20", "osmway:21"],
+            ["osmrel:30", "osmrel:32"],
+            ["osmway:10", "osmway:11"],
+            ["osmrel:20", "osmrel:21"],
+        ]
+    );
+    assert_eq!(blocks[0][0], "0");
+    for block in blocks {
+        let offset: usize = block[0].parse().unwrap();
+        let mut member = String::new();
+        GzDecoder::new(&data[offset..])
+            .read_to_string(&mut member)
+            .unwrap();
+        assert!(member.contains(&format!("\n{}\n", block[1])));
+        assert!(member.contains(&format!("\n{}\n", block[2])));
+    }
+    assert!(!dir.path().join("osm-000001.ttl.gz.gzi").exists());
+}
+
+#[test]
+fn only_keys() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--only-keys", "highway,wikidata"]);
+    let node = element(&result, "osmnode:14");
+    assert!(node.contains("osmt:wikidata wd:Q12345;"));
+    assert!(!node.contains("osmt:test "));
+    assert!(!node.contains("osmm:badkey"));
+    assert!(element(&result, "osmway:20").contains("osmt:highway \"primary\";"));
+    // None of the relations have any of the keys
+    assert!(!result.contains("\nosmrel:"));
+}
+
+#[test]
+fn is_closed() {
+    let file = fixture("osm2rdf/nodense_closed.osm.pbf");
+    let result = run_parse(&[], &[&file], &[]);
+    // Way 10 ends with its first node, and way 11 ends with a different node at the same location
+    for subject in ["osmway:10", "osmway:11"] {
+        assert!(element(&result, subject).contains("osmm:isClosed \"true\"^^xsd:boolean;"));
+    }
+    let file = fixture("osm2rdf/nodense_test1.osm.pbf");
+    let result = run_parse(&[], &[&file], &[]);
+    assert!(element(&result, "osmway:20").contains("osmm:isClosed \"false\"^^xsd:boolean;"));
+    assert!(!element(&result, "osmrel:31").contains("osmm:isClosed"));
+}
+
+#[test]
+fn is_closed_for() {
+    let closed = fixture("osm2rdf/nodense_closed.osm.pbf");
+    let open = fixture("osm2rdf/nodense_test1.osm.pbf");
+    let run = |setting: &str| {
+        let args = ["--is-closed-for", setting];
+        let closed = run_parse(&[], &[&closed], &args);
+        let open = run_parse(&[], &[&open], &args);
+        (
+            element(&closed, "osmway:10").contains("osmm:isClosed \"true\"^^xsd:boolean;"),
+            element(&open, "osmway:20").contains("osmm:isClosed \"false\"^^xsd:boolean;"),
+            closed.contains("osmm:isClosed") || open.contains("osmm:isClosed"),
+        )
+    };
+    assert_eq!(run("all"), (true, true, true));
+    // Both closed ways have four nodes, so they are areas
+    assert_eq!(run("areas"), (true, false, true));
+    assert_eq!(run("none"), (false, false, false));
+}
+
+#[test]
+fn id_list() {
+    let temp_dir = TempDir::new().unwrap();
+    let list = temp_dir.path().join("ids.txt");
+    std::fs::write(&list, "# QA report\nnode/14\nway/21\n\nrelation/99\n").unwrap();
+    let out_dir = temp_dir.path().join("out");
+    std::fs::create_dir(&out_dir).unwrap();
+    let stats = parser::parse(Args::parse_from([
+        "osm2rdf",
+        "parse",
+        fixture("osm2rdf/dense_test1.osm.pbf").to_str().unwrap(),
+        out_dir.to_str().unwrap(),
+        "--id-list",
+        list.to_str().unwrap(),
+    ]))
+    .unwrap();
+    assert_eq!(
+        (stats.added_nodes, stats.added_ways, stats.added_rels),
+        (1, 1, 0)
+    );
+    assert_eq!(stats.warnings.get(Warning::ListedMissing), 1);
+
+    let result = run_parse(
+        &[],
+        &[&fixture("osm2rdf/dense_test1.osm.pbf")],
+        &["--id-list", list.to_str().unwrap()],
+    );
+    element(&result, "osmnode:14");
+    // The way geometry uses the cached locations of the unlisted nodes
+    assert!(element(&result, "osmway:21").contains("osmm:loc "));
+    assert!(!result.contains("osmway:20\n"));
+    assert!(!result.contains("osmrel:30\n"));
+}
+
+#[test]
+fn types() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--types", "way"]);
+    assert!(!result.contains("osmnode:14\n"));
+    assert!(!result.contains("osmrel:30\n"));
+    // The excluded nodes are still cached for the way geometry
+    assert!(element(&result, "osmway:20").contains("osmm:loc "));
+    assert!(element(&result, "osmway:21").contains("osmm:loc "));
+
+    let result = run_parse(&[], &[&file], &["--types", "node,relation"]);
+    element(&result, "osmnode:14");
+    element(&result, "osmrel:30");
+    assert!(!result.contains("osmway:20\n"));
+}
+
+#[test]
+fn sorted_output() {
+    let route = fixture("osm2rdf/dense_route.osm.pbf");
+    let test1 = fixture("osm2rdf/dense_test1.osm.pbf");
+    let inputs: [&Path; 2] = [&route, &test1];
+    let subjects = |output: &str| -> Vec<(usize, i64)> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let (prefix, id) = line.split_once(':')?;
+                let rank = ["osmnode", "osmway", "osmrel"]
+                    .iter()
+                    .position(|v| *v == prefix)?;
+                Some((rank, id.parse().ok()?))
+            })
+            .collect()
+    };
+    let unsorted = run_parse(&[], &inputs, &[]);
+    let sorted = run_parse(&[], &inputs, &["--sorted-output"]);
+    let mut expected = subjects(&unsorted);
+    assert!(!expected.windows(2).all(|v| v[0] <= v[1]));
+    expected.sort();
+    assert_eq!(subjects(&sorted), expected);
+
+    let mut unsorted_lines: Vec<_> = unsorted.lines().collect();
+    let mut sorted_lines: Vec<_> = sorted.lines().collect();
+    unsorted_lines.sort();
+    sorted_lines.sort();
+    assert_eq!(sorted_lines, unsorted_lines);
+
+    let temp_dir = TempDir::new().unwrap();
+    let args = Args::parse_from([
+        "osm2rdf",
+        "parse",
+        "--sorted-output",
+        "--flush-interval",
+        "10",
+        inputs[0].to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+    ]);
+    assert!(parser::parse(args).is_err());
+}
+
+#[test]
+fn emit_rdf_type() {
+    let result = run_parse(
+        &[],
+        &[&fixture("osm2rdf/dense_test1.osm.pbf")],
+        &["--emit-rdf-type"],
+    );
+    let node = element(&result, "osmnode:14");
+    assert!(node.contains("\na osmm:Node;"), "{node}");
+    // Both the short type and the class are emitted
+    assert!(node.contains("\nosmm:type \"n\";"), "{node}");
+    assert!(element(&result, "osmway:20").contains("\na osmm:Way;"));
+    // `type=test` has no dedicated class
+    assert!(element(&result, "osmrel:30").contains("\na osmm:Relation;"));
+    assert!(element(&result, "osmrel:31").contains("\na osmm:Restriction;"));
+
+    let result = run_parse(
+        &[],
+        &[&fixture("osm2rdf/dense_boundary.osm.pbf")],
+        &["--emit-rdf-type"],
+    );
+    assert!(element(&result, "osmrel:20").contains("\na osmm:Boundary;"));
+    let result = run_parse(
+        &[],
+        &[&fixture("osm2rdf/dense_route.osm.pbf")],
+        &["--emit-rdf-type"],
+    );
+    assert!(element(&result, "osmrel:20").contains("\na osmm:Route;"));
+    assert!(!run_parse(&[], &[&fixture("osm2rdf/dense_route.osm.pbf")], &[]).contains("\na osmm:"));
+    let result = run_parse(
+        &[],
+        &[&fixture("osm2rdf/dense_route.osm.pbf")],
+        &["--emit-rdf-type", "--no-type-predicate"],
+    );
+    assert!(element(&result, "osmrel:20").contains("\na osmm:Route;"));
+    assert!(!result.contains("osmm:type"));
+
+    // The shorthands are the same as the type styles
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let both = run_parse(&[], &[&file], &["--type-style", "both"]);
+    let node = element(&both, "osmnode:14");
+    assert!(node.contains("\na osmm:Node;"), "{node}");
+    assert!(node.contains("\nosmm:type \"n\";"), "{node}");
+    assert_eq!(both, run_parse(&[], &[&file], &["--emit-rdf-type"]));
+    let class = run_parse(&[], &[&file], &["--type-style", "class"]);
+    assert!(element(&class, "osmway:20").contains("\na osmm:Way;"));
+    assert!(!class.contains("osmm:type"));
+    let args = ["--emit-rdf-type", "--no-type-predicate"];
+    assert_eq!(class, run_parse(&[], &[&file], &args));
+    let short = run_parse(&[], &[&file], &["--type-style", "short"]);
+    assert_eq!(short, run_parse(&[], &[&file], &[]));
+
+    assert_eq!(
+        vocab::relation_class(Some("multipolygon")),
+        vocab::MULTIPOLYGON
+    );
+    assert_eq!(vocab::relation_class(None), vocab::RELATION);
+    let turtle = vocab::describe(SchemaFormat::Turtle);
+    assert!(turtle.contains("\nosmm:Boundary\n  a rdfs:Class;\n  rdfs:subClassOf osmm:Relation;\n"));
+    assert!(turtle.contains("\nosmm:Node\n  a rdfs:Class;\n  rdfs:comment "));
+}
+
+#[test]
+fn osm_base_url() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let result = run_parse(
+        &[],
+        &[&file],
+        &["--osm-base-url", "https://osm.example.org/"],
+    );
+    for prefix in [
+        "@prefix osmroot: <https://osm.example.org>.",
+        "@prefix osmnode: <https://osm.example.org/node/>.",
+        "@prefix osmway: <https://osm.example.org/way/>.",
+        "@prefix osmrel: <https://osm.example.org/relation/>.",
+    ] {
+        assert!(result.contains(prefix), "{prefix}");
+    }
+    // Only the vocabulary IRIs still point to openstreetmap.org
+    for line in result.lines().filter(|v| v.contains("openstreetmap.org")) {
+        assert!(
+            line.starts_with("@prefix osmt: ") || line.starts_with("@prefix osmm: "),
+            "{line}"
+        );
+    }
+    element(&result, "osmway:20");
+
+    let result = run_parse(&[], &[&file], &[]);
+    assert!(result.contains("@prefix osmnode: <https://www.openstreetmap.org/node/>."));
+}
+
+#[test]
+fn cached_nodes() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    for args in [&[][..], &["--types", "way"]] {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cmd = vec![
+            "osm2rdf",
+            "parse",
+            file.to_str().unwrap(),
+            temp_dir.path().to_str().unwrap(),
+        ];
+        cmd.extend(args);
+        let stats = parser::parse(Args::parse_from(cmd)).unwrap();
+        assert_eq!(stats.cached_nodes, 5);
+    }
+}
+
+#[test]
+fn emit_issues() {
+    let issues = |file: &str, args: &[&str]| {
+        let temp_dir = parse_to_dir(&[], &[&fixture(file)], args);
+        let path = temp_dir.path().join("issues.json");
+        json::parse(&std::fs::read_to_string(path).unwrap()).unwrap()
+    };
+
+    let result = issues("osm2rdf/dense_test1.osm.pbf", &["--emit-issues"]);
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0]["element"], "osmnode:14");
+    assert_eq!(result[0]["kind"], "bad_key");
+    assert_eq!(result[0]["value"], "my !@#$ '\"bad&key");
+
+    let result = issues("osm2rdf/dense_degenerate.osm.pbf", &["--emit-issues"]);
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0]["element"], "osmway:10");
+    assert_eq!(result[0]["kind"], "geometry_error");
+    assert_eq!(result[0]["value"], "no_nodes");
+
+    let args = ["--emit-issues", "--flag-null-island"];
+    let result = issues("osm2rdf/nodense_null_island.osm.pbf", &args);
+    let elements: Vec<_> = result.members().map(|v| v["element"].to_string()).collect();
+    assert!(elements.contains(&"osmnode:1".to_string()), "{elements:?}");
+    assert!(!elements.contains(&"osmnode:2".to_string()), "{elements:?}");
+    assert!(result
+        .members()
+        .all(|v| v["kind"] == "null_island" && v["value"].is_null()));
+
+    let temp_dir = parse_to_dir(&[], &[&fixture("osm2rdf/dense_test1.osm.pbf")], &[]);
+    assert!(!temp_dir.path().join("issues.json").exists());
+}
+
+#[test]
+fn upsert() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let temp_dir = parse_to_dir(&[], &[&file], &["--upsert"]);
+    let files = list_files(temp_dir.path());
+    assert!(files
+        .iter()
+        .all(|v| v.to_str().unwrap().ends_with(".ru.gz")));
+
+    let result = run_parse(&[], &[&file], &["--upsert"]);
+    assert!(result.starts_with("prefix wd: <http://www.wikidata.org/entity/>\n"));
+    assert!(!result.contains("@prefix"));
+    let delete = result
+        .find("\nDELETE WHERE { osmway:20 ?p ?o };\n")
+        .unwrap();
+    let insert = result.find("INSERT DATA {\nosmway:20\n").unwrap();
+    assert!(delete < insert);
+    // The deletion immediately precedes the insertion of the same subject
+    assert_eq!(
+        &result[delete..insert],
+        "\nDELETE WHERE { osmway:20 ?p ?o };\n"
+    );
+    let expected = run_parse(&[], &[&file], &[]);
+    let statements = element(&expected, "osmway:20");
+    assert!(result[insert..].starts_with(&format!("INSERT DATA {{\n{statements}\n}};\n")));
+    assert!(result.contains("DELETE WHERE { osmroot: schema:dateModified ?o };\n"));
+
+    let temp_dir = TempDir::new().unwrap();
+    let args = Args::parse_from([
+        "osm2rdf",
+        "parse",
+        "--upsert",
+        "--format",
+        "protobuf",
+        file.to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+    ]);
+    assert!(parser::parse(args).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn socket() {
+    use std::os::unix::net::UnixListener;
+
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("osm2rdf.sock");
+    let listener = UnixListener::bind(&path).unwrap();
+    let reader = std::thread::spawn(move || {
+        let mut result = String::new();
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.read_to_string(&mut result).unwrap();
+        result
+    });
+    let out_dir = temp_dir.path().join("out");
+    std::fs::create_dir(&out_dir).unwrap();
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    parser::parse(Args::parse_from([
+        "osm2rdf",
+        "parse",
+        "--socket",
+        path.to_str().unwrap(),
+        file.to_str().unwrap(),
+        out_dir.to_str().unwrap(),
+    ]))
+    .unwrap();
+    let result = reader.join().unwrap();
+
+    assert!(list_files(&out_dir).is_empty());
+    assert!(result.starts_with("@prefix wd: "));
+    assert_eq!(result.matches("@prefix osmnode: ").count(), 1);
+    let expected = run_parse(&[], &[&file], &[]);
+    assert_eq!(
+        element(&result, "osmway:20"),
+        element(&expected, "osmway:20")
+    );
+    assert!(result.contains("\nosmroot: schema:dateModified "));
+}
+
+/// Reply to the HTTP requests on a local port with the given statuses in order, and then with 200.
+/// Returns the URL of the endpoint, and the bodies of all requests it received.
+fn mock_endpoint(statuses: &[u16]) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/update", listener.local_addr().unwrap());
+    let bodies = Arc::new(Mutex::new(Vec::new()));
+    let statuses = Arc::new(Mutex::new(
+        statuses.iter().rev().copied().collect::<Vec<_>>(),
+    ));
+    let result = bodies.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let (bodies, statuses) = (bodies.clone(), statuses.clone());
+            std::thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                loop {
+                    // The request line and the headers end with an empty line
+                    let mut len = 0;
+                    let mut line = String::new();
+                    while line != "\r\n" {
+                        line.clear();
+                        if !matches!(reader.read_line(&mut line), Ok(n) if n > 0) {
+                            return;
+                        }
+                        if let Some(v) = line.to_lowercase().strip_prefix("content-length:") {
+                            len = v.trim().parse().unwrap();
+                        }
+                    }
+                    let mut body = vec![0; len];
+                    reader.read_exact(&mut body).unwrap();
+                    bodies
+                        .lock()
+                        .unwrap()
+                        .push(String::from_utf8(body).unwrap());
+                    let status = statuses.lock().unwrap().pop().unwrap_or(200);
+                    write!(
+                        stream,
+                        "HTTP/1.1 {status} Mock\r\nContent-Length: 0\r\n\r\n"
+                    )
+                    .unwrap();
+                }
+            });
+        }
+    });
+    (url, result)
+}
+
+#[test]
+fn sparql_endpoint() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let temp_dir = TempDir::new().unwrap();
+    let run = |urls: &[&str]| {
+        let mut args = vec!["osm2rdf", "parse", "--sparql-retries", "1"];
+        for url in urls {
+            args.extend(["--sparql-endpoint", url]);
+        }
+        args.push(file.to_str().unwrap());
+        args.push(temp_dir.path().to_str().unwrap());
+        parser::parse(Args::parse_from(args))
+    };
+
+    // The first request to the flaky endpoint fails once, and is sent again
+    let (flaky, flaky_bodies) = mock_endpoint(&[503]);
+    let (url, bodies) = mock_endpoint(&[]);
+    let stats = run(&[&flaky, &url]).unwrap();
+    assert!(list_files(temp_dir.path()).is_empty());
+    let bodies = bodies.lock().unwrap().clone();
+    let flaky_bodies = flaky_bodies.lock().unwrap().clone();
+    assert_eq!(flaky_bodies[0], flaky_bodies[1]);
+    assert_eq!(flaky_bodies[1..], bodies);
+    assert_eq!(stats.endpoints.len(), 2);
+    assert_eq!(stats.endpoints[0].url, flaky);
+    assert_eq!(stats.endpoints[0].retries, 1);
+    assert_eq!(stats.endpoints[1].retries, 0);
+    assert!(stats
+        .endpoints
+        .iter()
+        .all(|v| v.requests == bodies.len() as u64 && v.error.is_none()));
+
+    // Each request declares the prefixes, and the footer is the last one
+    assert!(bodies.iter().all(|v| v.starts_with("prefix wd: ")));
+    let expected = run_parse(&[], &[&file], &[]);
+    let statements = element(&expected, "osmway:20");
+    let insert =
+        format!("\nDELETE WHERE {{ osmway:20 ?p ?o }};\nINSERT DATA {{\n{statements}\n}};\n");
+    assert!(bodies.concat().contains(&insert));
+    let footer = bodies.last().unwrap();
+    assert!(footer.contains("\nDELETE WHERE { osmroot: schema:dateModified ?o };\n"));
+    assert!(!footer.contains("\nosmway:"));
+
+    // The other endpoints receive all requests when one of them is down
+    let (url, bodies) = mock_endpoint(&[]);
+    let down = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}/update", listener.local_addr().unwrap())
+    };
+    let err = run(&[&down, &url]).unwrap_err();
+    assert!(err.to_string().contains(&format!(
+        "1 of 2 SPARQL endpoints did not receive all requests: {down}"
+    )));
+    assert!(bodies
+        .lock()
+        .unwrap()
+        .last()
+        .unwrap()
+        .contains("schema:dateModified"));
+
+    // A rejected request is not retried
+    let (url, bodies) = mock_endpoint(&[400]);
+    assert!(run(&[&url]).is_err());
+    assert_eq!(bodies.lock().unwrap().len(), 1);
+
+    let args = Args::parse_from([
+        "osm2rdf",
+        "parse",
+        "--sparql-endpoint",
+        &url,
+        "--format",
+        "ntriples",
+        file.to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+    ]);
+    assert!(parser::parse(args).is_err());
+    let args = [
+        "osm2rdf",
+        "parse",
+        "--sparql-endpoint",
+        "localhost:8890",
+        "in",
+        "out",
+    ];
+    assert!(Args::try_parse_from(args).is_err());
+}
+
+#[test]
+fn invalid_version() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = fixture("osm2rdf/nodense_bad_version.osm.pbf");
+    let stats = parser::parse(Args::parse_from([
+        "osm2rdf",
+        "parse",
+        "--emit-issues",
+        file.to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+    ]))
+    .unwrap();
+    assert_eq!(stats.warnings.get(Warning::InvalidVersion), 2);
+    let issues = std::fs::read_to_string(temp_dir.path().join("issues.json")).unwrap();
+    let issues = json::parse(&issues).unwrap();
+    assert_eq!(issues.len(), 2);
+    assert_eq!(issues[0]["element"], "osmnode:1");
+    assert_eq!(issues[0]["kind"], "invalid_version");
+    assert_eq!(issues[0]["value"], "0");
+    assert_eq!(issues[1]["element"], "osmrel:20");
+    assert_eq!(issues[1]["value"], "-1");
+}
+
+#[test]
+fn ntriples() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let temp_dir = parse_to_dir(&[], &[&file], &["--format", "ntriples"]);
+    let files = list_files(temp_dir.path());
+    assert!(files
+        .iter()
+        .all(|v| v.to_str().unwrap().ends_with(".nt.gz")));
+
+    let result = run_parse(&[], &[&file], &["--format", "ntriples"]);
+    assert!(!result.contains("@prefix"));
+    for line in result.lines() {
+        assert!(line.starts_with('<') && line.ends_with(" ."), "{line}");
+    }
+    assert!(result.contains(
+        "<https://www.openstreetmap.org/node/14> <https://wiki.openstreetmap.org/wiki/Key:wikidata> <http://www.wikidata.org/entity/Q12345> .\n"
+    ));
+    assert!(result.contains(
+        "<https://www.openstreetmap.org> <http://schema.org/dateModified> \"2014-01-01 00:00:00 UTC\"^^<http://www.w3.org/2001/XMLSchema#dateTime> .\n"
+    ));
+    // The same statements as in Turtle, one per line
+    let turtle = run_parse(&[], &[&file], &[]);
+    let statements = element(&turtle, "osmway:20").lines().count() - 1;
+    let way = "<https://www.openstreetmap.org/way/20> ";
+    assert_eq!(
+        result.lines().filter(|v| v.starts_with(way)).count(),
+        statements
+    );
+}
+
+#[test]
+fn jsonld() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let temp_dir = parse_to_dir(&[], &[&file], &["--format", "jsonld"]);
+    let mut result = String::new();
+    for file in list_files(temp_dir.path()) {
+        let name = file.file_name().unwrap().to_str().unwrap();
+        if name == "context.jsonld" {
+            continue;
+        }
+        assert!(name.ends_with(".jsonld.gz"), "{name}");
+        GzDecoder::new(File::open(file).unwrap())
+            .read_to_string(&mut result)
+            .unwrap();
+    }
+    let context = std::fs::read_to_string(temp_dir.path().join("context.jsonld")).unwrap();
+    let context = json::parse(&context).unwrap();
+    assert_eq!(
+        context["@context"]["osmnode"],
+        "https://www.openstreetmap.org/node/"
+    );
+
+    let elements: Vec<_> = result.lines().map(|v| json::parse(v).unwrap()).collect();
+    assert!(elements.iter().all(|v| v["@context"] == "context.jsonld"));
+    let node = elements.iter().find(|v| v["@id"] == "osmnode:14").unwrap();
+    assert_eq!(node["osmt:wikidata"], json::object! {"@id": "wd:Q12345"});
+    assert_eq!(
+        node["osmm:version"],
+        json::object! {"@value": "1", "@type": "xsd:integer"}
+    );
+    let root = elements
+        .iter()
+        .find(|v| v.has_key("schema:dateModified"))
+        .unwrap();
+    assert_eq!(root["@id"], "https://www.openstreetmap.org");
+    assert_eq!(root["schema:dateModified"]["@type"], "xsd:dateTime");
+
+    // The same statements as in Turtle
+    let turtle = run_parse(&[], &[&file], &[]);
+    let statements = element(&turtle, "osmway:20").lines().count() - 1;
+    let way = elements.iter().find(|v| v["@id"] == "osmway:20").unwrap();
+    let values: usize = way
+        .entries()
+        .filter(|(k, _)| !k.starts_with('@'))
+        .map(|(_, v)| if v.is_array() { v.len() } else { 1 })
+        .sum();
+    assert_eq!(values + usize::from(way.has_key("@type")), statements);
+}
+
+#[test]
+fn skip_tagless() {
+    let tagless = fixture("libosmium/examples_t_count_data.osm.pbf");
+    let result = run_parse(&[], &[&tagless], &[]);
+    element(&result, "osmway:10");
+    element(&result, "osmrel:20");
+
+    let result = run_parse(&[], &[&tagless], &["--skip-tagless-ways"]);
+    assert!(!result.contains("osmway:10\n"));
+    element(&result, "osmrel:20");
+
+    let result = run_parse(&[], &[&tagless], &["--skip-tagless-relations"]);
+    element(&result, "osmway:10");
+    assert!(!result.contains("osmrel:20\n"));
+
+    let temp_dir = TempDir::new().unwrap();
+    let stats = parser::parse(Args::parse_from([
+        "osm2rdf",
+        "parse",
+        tagless.to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+        "--skip-tagless-ways",
+        "--skip-tagless-relations",
+    ]))
+    .unwrap();
+    assert_eq!(stats.skipped_tagless, 2);
+
+    // Tagged ways and relations are not affected
+    let tagged = fixture("osm2rdf/dense_test1.osm.pbf");
+    let args = ["--skip-tagless-ways", "--skip-tagless-relations"];
+    let result = run_parse(&[], &[&tagged], &args);
+    assert!(element(&result, "osmway:20").contains("osmm:loc "));
+    element(&result, "osmway:21");
+    element(&result, "osmrel:30");
+}
+
+#[test]
+fn compression() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let expected = run_parse(&[], &[&file], &[]);
+
+    let temp_dir = parse_to_dir(&[], &[&file], &["--compression", "zstd"]);
+    let mut result = String::new();
+    for file in list_files(temp_dir.path()) {
+        assert!(file.to_str().unwrap().ends_with(".ttl.zst"));
+        zstd::Decoder::new(File::open(file).unwrap())
+            .unwrap()
+            .read_to_string(&mut result)
+            .unwrap();
+    }
+    assert_eq!(result, expected);
+
+    let temp_dir = parse_to_dir(&[], &[&file], &["--compression", "none"]);
+    let mut result = String::new();
+    for file in list_files(temp_dir.path()) {
+        assert!(file.to_str().unwrap().ends_with(".ttl"));
+        result.push_str(&std::fs::read_to_string(file).unwrap());
+    }
+    assert_eq!(result, expected);
+}
+
+#[test]
+fn emit_role_counts() {
+    let file = fixture("osm2rdf/dense_route.osm.pbf");
+    let result = run_parse(&[], &[&file], &[]);
+    assert!(!result.contains("osmm:roleCount"));
+
+    let result = run_parse(&[], &[&file], &["--emit-role-counts"]);
+    // Members without a role are counted as `none`
+    assert!(element(&result, "osmrel:20").contains("osmm:roleCount \"stop=1;none=3\";"));
+    assert!(element(&result, "osmrel:21").contains("osmm:roleCount \"none=1\";"));
+    assert!(!element(&result, "osmway:10").contains("osmm:roleCount"));
+
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--emit-role-counts"]);
+    let counts = "osmm:roleCount \"from=1;via=1;to=1\";";
+    assert!(element(&result, "osmrel:31").contains(counts));
+}
+
+#[test]
+fn compression_level() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let size = |level: &str| {
+        let temp_dir = parse_to_dir(&[], &[&file], &["--compression-level", level]);
+        let path = temp_dir.path().join("osm-000000.ttl.gz");
+        std::fs::metadata(path).unwrap().len()
+    };
+    assert!(size("9") < size("1"));
+
+    let expected = run_parse(&[], &[&file], &[]);
+    let result = run_parse(&[], &[&file], &["--compression-level", "1"]);
+    assert_eq!(result, expected);
+
+    let temp_dir = TempDir::new().unwrap();
+    let args = |level: &str, compression: &str| {
+        Args::try_parse_from([
+            "osm2rdf",
+            "parse",
+            "--compression-level",
+            level,
+            "--compression",
+            compression,
+            file.to_str().unwrap(),
+            temp_dir.path().to_str().unwrap(),
+        ])
+    };
+    assert!(args("10", "gzip").is_err());
+    assert!(parser::parse(args("1", "none").unwrap()).is_err());
+}
+
+#[test]
+fn emit_geometry() {
+    let file = fixture("osm2rdf/dense_closed.osm.pbf");
+    let args = ["--emit-geometry", "--coord-precision", "3"];
+    let result = run_parse(&[], &[&file], &args);
+    let way = element(&result, "osmway:10");
+    assert!(way.contains(r#"osmm:geometry "LINESTRING(0 0, 1 0, 1 1, 0 0)"^^geo:wktLiteral;"#));
+    assert!(way.contains("osmm:isClosed \"true\"^^xsd:boolean;"));
+    assert!(way.contains("osmm:loc "));
+
+    // Degenerate ways have no line string
+    let file = fixture("osm2rdf/dense_degenerate.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--emit-geometry"]);
+    let way = element(&result, "osmway:10");
+    assert!(way.contains("osmm:loc:error \"no_nodes\";"));
+    assert!(!way.contains("osmm:geometry"));
+    assert!(!element(&result, "osmway:11").contains("osmm:geometry "));
+
+    let result = run_parse(&[], &[&file], &[]);
+    assert!(!result.contains("osmm:geometry "));
+}
+
+#[test]
+fn emit_metrics() {
+    let file = fixture("osm2rdf/dense_closed.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--emit-metrics"]);
+    let way = element(&result, "osmway:10");
+    assert!(way.contains("osmm:nodeCount \"4\"^^xsd:integer;"), "{way}");
+    // Two 1° segments along the equator and a meridian, and the diagonal back, on a sphere of 6371008.8 m
+    let length = way
+        .lines()
+        .find_map(|v| v.strip_prefix("osmm:length \""))
+        .and_then(|v| v.strip_suffix("\"^^xsd:decimal;"))
+        .unwrap();
+    let length: f64 = length.parse().unwrap();
+    assert!((length - 379_639.76).abs() < 1.0, "{length}");
+
+    let result = run_parse(&[], &[&file], &[]);
+    assert!(!result.contains("osmm:nodeCount"));
+    assert!(!result.contains("osmm:length"));
+
+    // Ways without all node locations are still counted, but have no length
+    let file = fixture("osm2rdf/nodense_negative_id.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--emit-metrics"]);
+    let way = element(&result, "osmway:11");
+    assert!(way.contains("osmm:loc:error \"negative_node_id\";"));
+    assert!(way.contains("osmm:nodeCount "));
+    assert!(!way.contains("osmm:length"));
+}
+
+#[test]
+fn no_metadata() {
+    let file = fixture("osm2rdf/dense_closed.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--no-metadata"]);
+    insta::assert_display_snapshot!(element(&result, "osmway:10"));
+    for predicate in [
+        vocab::VERSION,
+        vocab::USER,
+        vocab::TIMESTAMP,
+        vocab::CHANGESET,
+    ] {
+        assert!(!result.contains(&format!("\n{} ", predicate.name)));
+    }
+    assert!(result.contains("osmroot: schema:dateModified "));
+}
+
+#[test]
+fn negative_ids() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = fixture("osm2rdf/nodense_negative_id.osm.pbf");
+    let stats = parser::parse(Args::parse_from([
+        "osm2rdf",
+        "parse",
+        file.to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+    ]))
+    .unwrap();
+    // Node -1 and way -10 are skipped, and never stored in the cache
+    assert_eq!(stats.warnings.get(Warning::NegativeId), 2);
+    assert_eq!(stats.cached_nodes, 1);
+
+    let result = run_parse(&[], &[&file], &[]);
+    assert!(!result.contains(":-1"));
+    element(&result, "osmnode:1");
+    let way = element(&result, "osmway:11");
+    assert!(way.contains("osmm:loc:error \"negative_node_id\";"));
+    assert!(element(&result, "osmrel:20").contains("osmm:has osmnode:1;"));
+}
+
+#[test]
+fn way_centroid() {
+    let file = fixture("osm2rdf/dense_asymmetric.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--coord-precision", "3"]);
+    // The nodes are at lat 50, lon 10 and lat 52, lon 12, the same order as the node locations
+    let way = element(&result, "osmway:10");
+    assert!(way.contains("osmm:loc \"Point(11 51)\"^^geo:wktLiteral;"));
+    // The bounding box is not rounded
+    let (lon, lat) = ("10.99999997811392", "50.99999998044223");
+    assert!(result.contains(&format!(
+        "osmm:datasetBbox \"POLYGON(({lon} {lat}, {lon} {lat}, {lon} {lat}, {lon} {lat}, {lon} {lat}))\""
+    )));
+}
+
+#[test]
+fn wkt_crs_uri() {
+    let file = fixture("osm2rdf/dense_asymmetric.osm.pbf");
+    let args = ["--wkt-crs-uri", "--coord-precision", "3", "--emit-geometry"];
+    let result = run_parse(&[], &[&file], &args);
+    let crs = "<http://www.opengis.net/def/crs/OGC/1.3/CRS84>";
+    let way = element(&result, "osmway:10");
+    assert!(way.contains(&format!(
+        r#"osmm:loc "{crs} Point(11 51)"^^geo:wktLiteral;"#
+    )));
+    assert!(way.contains(&format!(
+        r#"osmm:geometry "{crs} LINESTRING(10 50, 12 52)"^^geo:wktLiteral;"#
+    )));
+    assert!(result.contains(&format!(r#"osmm:datasetBbox "{crs} POLYGON(("#)));
+
+    let result = run_parse(&[], &[&file], &[]);
+    assert!(!result.contains(crs));
+    // Web Mercator literals always declare their CRS
+    let result = run_parse(&[], &[&file], &["--wkt-crs-uri", "--crs", "3857"]);
+    assert!(!result.contains(crs));
+    assert!(result.contains("<http://www.opengis.net/def/crs/EPSG/0/3857> Point("));
+}
+
+#[test]
+fn two_pass() {
+    // The way is stored in a blob before the blob of its nodes, so a single pass gets no node locations,
+    // see the `decode_osm_pbf_files` snapshot of this file
+    let file = fixture("osm2rdf/dense_ways_first.osm.pbf");
+    let args = ["--two-pass", "--coord-precision", "3"];
+    let result = run_parse(&[], &[&file], &args);
+    let way = element(&result, "osmway:10");
+    assert!(way.contains(r#"osmm:loc "Point(3 2)"^^geo:wktLiteral;"#));
+    assert!(way.contains(r#"osmm:isClosed "false"^^xsd:boolean;"#));
+    assert!(!result.contains("\nosmnode:"));
+}
+
+#[test]
+fn shard_by_id() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let temp_dir = parse_to_dir(&[], &[&file], &["--shard-by-id", "2"]);
+    let shards = list_files(temp_dir.path());
+    assert_eq!(shards.len(), 2);
+    let mut subjects = Vec::new();
+    for shard in &shards {
+        let mut result = String::new();
+        for file in list_files(shard) {
+            GzDecoder::new(File::open(file).unwrap())
+                .read_to_string(&mut result)
+                .unwrap();
+        }
+        let mut ids: Vec<_> = result
+            .lines()
+            .filter(|v| !v.contains(' ') && v.starts_with("osm"))
+            .map(str::to_string)
+            .collect();
+        ids.sort();
+        subjects.push((shard.file_name().unwrap().to_owned(), ids, result));
+    }
+
+    let (name, ids, result) = &subjects[0];
+    assert_eq!(name, "shard-000");
+    assert_eq!(ids, &["osmnode:14", "osmrel:30", "osmrel:32", "osmway:20"]);
+    assert!(result.contains("\nosmroot: schema:dateModified "));
+    let (name, ids, result) = &subjects[1];
+    assert_eq!(name, "shard-001");
+    assert_eq!(ids, &["osmrel:31", "osmway:21"]);
+    assert!(!result.contains("osmroot: schema:dateModified"));
+}
+
+#[test]
+fn bbox() {
+    // Node 14 is inside, ways 20 and 21 cross the box, and relation 31 has the node 11 inside
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--bbox", "1.05,0.5,1.5,1.5"]);
+    for subject in ["osmnode:14", "osmway:20", "osmway:21", "osmrel:31"] {
+        element(&result, subject);
+    }
+    assert!(!result.contains("\nosmrel:30\n"));
+    assert!(!result.contains("\nosmrel:32\n"));
+
+    // Only the node 3 is inside, so way 11 and the relation 20 using it are kept
+    let file = fixture("osm2rdf/dense_route.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--bbox", "1.5,-0.5,2.5,0.5"]);
+    element(&result, "osmway:11");
+    element(&result, "osmrel:20");
+    assert!(!result.contains("\nosmway:10\n"));
+    assert!(!result.contains("\nosmrel:21\n"));
+
+    let args =
+        |bbox: &str| Args::try_parse_from(["osm2rdf", "parse", "in.pbf", ".", "--bbox", bbox]);
+    assert!(args("1,2,3").is_err());
+    assert!(args("3,2,1,4").is_err());
+    assert!(args("1,2,3,95").is_err());
+    assert!(args("1,2,3,4").is_ok());
+}
+
+#[test]
+fn prefixes_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let prefixes = temp_dir.path().join("prefixes.ttl");
+    std::fs::write(
+        &prefixes,
+        "# Custom prefixes\n\
+         prefix osmnode: <https://example.org/node/>\n\
+         @prefix ex: <https://example.org/ns#> .\n",
+    )
+    .unwrap();
+    let prefixes = prefixes.to_str().unwrap();
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+
+    let result = run_parse(&[], &[&file], &["--prefixes", prefixes]);
+    assert!(result.contains("@prefix osmnode: <https://example.org/node/>.\n"));
+    assert!(result.contains("@prefix ex: <https://example.org/ns#>.\n"));
+    assert!(!result.contains("<https://www.openstreetmap.org/node/>"));
+    assert!(result.contains("@prefix osmway: <https://www.openstreetmap.org/way/>.\n"));
+
+    let args = ["--prefixes", prefixes, "--format", "ntriples"];
+    let result = run_parse(&[], &[&file], &args);
+    assert!(result.contains("<https://example.org/node/14> "));
+
+    let args = Args::parse_from([
+        "osm2rdf",
+        "parse",
+        "--prefixes",
+        prefixes,
+        "--key-namespace",
+        "addr=ex",
+        file.to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+    ]);
+    assert!(parser::parse(args).is_err());
+
+    assert!(PrefixFile::parse("prefix ex <https://example.org/>").is_err());
+    assert!(PrefixFile::parse("prefix ex: https://example.org/").is_err());
+    assert!(PrefixFile::parse("prefix 1ex: <https://example.org/>").is_err());
+    assert!(PrefixFile::parse("prefix ex: <a>\nprefix ex: <b>").is_err());
+    let err = PrefixFile::parse("\nbase <https://example.org/>").unwrap_err();
+    assert!(err.to_string().starts_with("Line 2: "));
+}
+
+#[test]
+fn deflate_strategy() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let expected = run_parse(&[], &[&file], &[]);
+    for strategy in ["filtered", "rle"] {
+        let result = run_parse(&[], &[&file], &["--deflate-strategy", strategy]);
+        assert_eq!(result, expected);
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let args = Args::parse_from([
+        "osm2rdf",
+        "parse",
+        "--compression",
+        "zstd",
+        "--deflate-strategy",
+        "rle",
+        file.to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+    ]);
+    assert!(parser::parse(args).is_err());
+}
+
+#[test]
+fn progress() {
+    // Without a terminal, the progress is only logged, and does not change the output
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let expected = run_parse(&[], &[&file, &file], &[]);
+    let result = run_parse(&[], &[&file, &file], &["--progress"]);
+    assert_eq!(result, expected);
+    let result = run_parse(&[], &[&file], &["--progress", "--two-pass"]);
+    element(&result, "osmway:20");
+}
+
+#[test]
+fn validate_members() {
+    // Relation 31 references the missing way 22, and node 11 without tags, which is not emitted but exists
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--validate-members"]);
+    let rel = element(&result, "osmrel:31");
+    assert!(rel.contains("osmm:danglingMembers \"1\"^^xsd:integer;"));
+    assert!(rel.contains("osmm:has osmway:22;"));
+    assert!(rel.contains("osmm:has osmnode:11;"));
+    assert!(!element(&result, "osmrel:32").contains("osmm:danglingMembers"));
+
+    let args = ["--validate-members", "--omit-dangling-members"];
+    let result = run_parse(&[], &[&file], &args);
+    let rel = element(&result, "osmrel:31");
+    assert!(rel.contains("osmm:danglingMembers \"1\"^^xsd:integer;"));
+    assert!(!rel.contains("osmway:22"));
+    assert!(rel.contains("osmm:has osmway:20;"));
+
+    let temp_dir = TempDir::new().unwrap();
+    let args = Args::parse_from([
+        "osm2rdf",
+        "parse",
+        "--omit-dangling-members",
+        file.to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+    ]);
+    assert!(parser::parse(args).is_err());
+}
+
+#[test]
+fn stats_json() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("stats.json");
+    let out_dir = temp_dir.path().join("out");
+    std::fs::create_dir(&out_dir).unwrap();
+    parser::parse(Args::parse_from([
+        "osm2rdf",
+        "parse",
+        fixture("osm2rdf/dense_test1.osm.pbf").to_str().unwrap(),
+        out_dir.to_str().unwrap(),
+        "--stats-json",
+        path.to_str().unwrap(),
+    ]))
+    .unwrap();
+
+    let stats = json::parse(&std::fs::read_to_string(path).unwrap()).unwrap();
+    assert_eq!(stats["added_nodes"], 1);
+    assert_eq!(stats["added_ways"], 2);
+    assert_eq!(stats["added_rels"], 3);
+    // The untagged nodes are skipped
+    assert_eq!(stats["skipped_nodes"], 4);
+    assert_eq!(stats["added_total"], 6);
+    assert_eq!(stats["skipped_total"], 4);
+    assert_eq!(stats["deleted_total"], 0);
+    assert_eq!(stats["elements"], 10);
+    assert!(stats["bbox"]["min_lat"].is_number());
+    assert!(stats["elapsed_secs"].as_f64().unwrap() >= 0.0);
+    assert!(stats["error"].is_null());
+}
+
+#[test]
+fn dry_run() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let parse = |extra_args: &[&str]| {
+        let temp_dir = TempDir::new().unwrap();
+        let mut args = vec![
+            "osm2rdf",
+            "parse",
+            file.to_str().unwrap(),
+            temp_dir.path().to_str().unwrap(),
+            "--emit-issues",
+        ];
+        args.extend(extra_args);
+        let stats = parser::parse(Args::parse_from(args)).unwrap();
+        (stats.to_json(Duration::ZERO), list_files(temp_dir.path()))
+    };
+    let (expected, files) = parse(&[]);
+    assert!(!files.is_empty());
+    let (stats, files) = parse(&["--dry-run", "--shard-by-id", "2"]);
+    assert_eq!(stats, expected);
+    assert_eq!(files, Vec::<PathBuf>::new());
+
+    let args = Args::parse_from([
+        "osm2rdf",
+        "parse",
+        file.to_str().unwrap(),
+        ".",
+        "--dry-run",
+        "--socket",
+        "out.sock",
+    ]);
+    assert!(parser::parse(args).is_err());
+}
+
+#[test]
+fn batch_size() {
+    let file = fixture("libosmium/examples_t_area_test_data.osm.pbf");
+    let expected = run_parse(&[], &[&file], &[]);
+    for args in [
+        &["--batch-size", "1"][..],
+        &["--batch-size", "2", "--queue-depth", "1"],
+    ] {
+        assert_eq!(run_parse(&[], &[&file], args), expected, "{args:?}");
+    }
+    let index = |args: &[&str]| {
+        let temp_dir = parse_to_dir(&[], &[&file], args);
+        let path = temp_dir.path().join("osm-000000.ttl.gz.gzi");
+        std::fs::read_to_string(path).unwrap().lines().count()
+    };
+    assert!(index(&["--emit-index", "--batch-size", "1"]) > index(&["--emit-index"]));
+    assert!(
+        Args::try_parse_from(["osm2rdf", "parse", "in.pbf", ".", "--batch-size", "0"]).is_err()
+    );
+}
+
+#[test]
+fn emit_file_index() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let args = ["--emit-file-index", "--shard-by-id", "2"];
+    let dir = parse_to_dir(&[], &[&file], &args);
+    let index = std::fs::read_to_string(dir.path().join("index.csv")).unwrap();
+    assert_eq!(
+        index.lines().collect::<Vec<_>>(),
+        [
+            "file,type,min_id,max_id",
+            "shard-000/osm-000000.ttl.gz,node,14,14",
+            "shard-000/osm-000000.ttl.gz,way,20,20",
+            "shard-000/osm-000000.ttl.gz,relation,30,32",
+            "shard-001/osm-000001.ttl.gz,way,21,21",
+            "shard-001/osm-000001.ttl.gz,relation,31,31",
+        ]
+    );
+    assert!(!parse_to_dir(&[], &[&file], &[])
+        .path()
+        .join("index.csv")
+        .exists());
+}
+
+#[test]
+fn invalid_input() {
+    let temp_dir = TempDir::new().unwrap();
+    let out_dir = temp_dir.path().join("out");
+    std::fs::create_dir(&out_dir).unwrap();
+    let empty = temp_dir.path().join("empty.osm.pbf");
+    std::fs::write(&empty, "").unwrap();
+    let missing = temp_dir.path().join("missing.osm.pbf");
+    let xml = fixture("libosmium/src/examples_t_area_test_data.osm");
+    for (file, message) in [
+        (&missing, "Unable to open"),
+        (&empty, "is empty"),
+        (&xml, "is not an OSM PBF file"),
+    ] {
+        let args = Args::parse_from([
+            "osm2rdf",
+            "parse",
+            file.to_str().unwrap(),
+            out_dir.to_str().unwrap(),
+            "--shard-by-id",
+            "2",
+        ]);
+        let err = parser::parse(args).unwrap_err();
+        assert!(format!("{err:#}").contains(message), "{err:#}");
+    }
+    assert!(list_files(&out_dir).is_empty());
+}
+
+#[test]
+fn corrupt_blob() {
+    let temp_dir = TempDir::new().unwrap();
+    let file = temp_dir.path().join("corrupt.osm.pbf");
+    let mut data = std::fs::read(fixture("osm2rdf/dense_test1.osm.pbf")).unwrap();
+    // Overwrite the checksum of the last compressed blob, leaving the header block intact
+    let len = data.len();
+    data[len - 4..].fill(0);
+    std::fs::write(&file, data).unwrap();
+    for args in [
+        &[][..],
+        &["--workers", "0"],
+        &["--two-pass"],
+        &["--skip-multipolygon-ways"],
+    ] {
+        let out_dir = TempDir::new().unwrap();
+        let args = Args::parse_from(
+            ["osm2rdf", "parse", file.to_str().unwrap()]
+                .into_iter()
+                .chain([out_dir.path().to_str().unwrap()])
+                .chain(args.iter().copied()),
+        );
+        assert!(parser::parse(args).is_err());
+    }
+}
+
+#[test]
+fn cache_page_size() {
+    let input = fixture("osm2rdf/dense_test1.osm.pbf");
+    let expected = run_parse(&[], &[&input], &[]);
+    let temp_dir = TempDir::new().unwrap();
+    let cache = temp_dir.path().join("nodes.cache");
+    let cache = cache.to_str().unwrap();
+    let output = run_parse(
+        &["--planet-cache", cache, "--cache-page-size", "1"],
+        &[&input],
+        &[],
+    );
+    assert_eq!(output, expected);
+
+    let args = [
+        "osm2rdf",
+        "--cache-page-size",
+        "0",
+        "parse",
+        "in.osm.pbf",
+        ".",
+    ];
+    assert!(Args::try_parse_from(args).is_err());
+}
+
+#[test]
+fn interrupted() {
+    let temp_dir = TempDir::new().unwrap();
+    let args = Args::parse_from([
+        "osm2rdf",
+        "parse",
+        fixture("osm2rdf/dense_test1.osm.pbf").to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+    ]);
+    let stats = parser::parse_interruptible(args, &AtomicBool::new(true)).unwrap();
+    assert_eq!(stats.elements(), 0);
+    assert_eq!(stats.warnings.get(Warning::Interrupted), 1);
+
+    // The output files are finished, and the footer is still written
+    let mut output = String::new();
+    for file in list_files(temp_dir.path()) {
+        GzDecoder::new(File::open(file).unwrap())
+            .read_to_string(&mut output)
+            .unwrap();
+    }
+    assert!(output.contains("osmroot: schema:dateModified "));
+    assert!(!output.contains("\nosmnode:"));
+}
+
+#[test]
+fn resume() {
+    let input = fixture("osm2rdf/dense_test1.osm.pbf");
+    let expected = run_parse(&[], &[&input], &[]);
+
+    let temp_dir = TempDir::new().unwrap();
+    let output_dir = temp_dir.path().join("out");
+    std::fs::create_dir(&output_dir).unwrap();
+    let cache = temp_dir.path().join("nodes.cache");
+    let state_file = output_dir.join(".osm2rdf-state");
+    let run = |parse_args: &[&str]| {
+        let mut args = vec!["osm2rdf", "--small-cache", cache.to_str().unwrap(), "parse"];
+        args.push(input.to_str().unwrap());
+        args.push(output_dir.to_str().unwrap());
+        args.extend(parse_args);
+        parser::parse(Args::parse_from(args))
+    };
+    // Nothing to resume yet
+    assert!(run(&["--resume"]).is_err());
+
+    // Stop after the header and the nodes blobs, and convert the ways and relations in the resumed run
+    let stats = run(&["--stop-after", "2"]).unwrap();
+    assert_eq!(stats.warnings.get(Warning::Interrupted), 1);
+    assert!(state_file.exists());
+    let stats = run(&["--resume"]).unwrap();
+    assert_eq!(stats.warnings.get(Warning::Interrupted), 0);
+    assert!(!state_file.exists());
+
+    // The footer of the stopped run is replaced by the first file of the resumed one
+    let files = list_files(&output_dir);
+    let names: Vec<_> = files.iter().map(|v| v.file_name().unwrap()).collect();
+    assert_eq!(
+        names,
+        [
+            "osm-000000.ttl.gz",
+            "osm-000001.ttl.gz",
+            "osm-000002.ttl.gz"
+        ]
+    );
+    let mut output = String::new();
+    for file in files {
+        GzDecoder::new(File::open(file).unwrap())
+            .read_to_string(&mut output)
+            .unwrap();
+    }
+    // Each file repeats the prefixes, otherwise the lines are the same, including the footer
+    let lines = |v: &str| v.lines().map(str::to_string).collect::<BTreeSet<_>>();
+    assert_eq!(lines(&output), lines(&expected));
+
+    // The resumed run needs the node locations of the skipped blobs
+    let args = Args::parse_from([
+        "osm2rdf",
+        "parse",
+        input.to_str().unwrap(),
+        output_dir.to_str().unwrap(),
+        "--resume",
+    ]);
+    assert!(parser::parse(args).is_err());
+}
+
+#[test]
+fn checkpoint_every() {
+    let input = fixture("osm2rdf/dense_test1.osm.pbf");
+    let expected = run_parse(&[], &[&input], &[]);
+    let temp_dir = parse_to_dir(&[], &[&input], &[]);
+    let default_files = list_files(temp_dir.path()).len();
+
+    // The output files are finished at each checkpoint, and the state file is removed at the end
+    let temp_dir = parse_to_dir(&[], &[&input], &["--checkpoint-every", "1"]);
+    let files = list_files(temp_dir.path());
+    assert!(files.len() > default_files);
+    assert!(!temp_dir.path().join(".osm2rdf-state").exists());
+    let mut output = String::new();
+    for file in files {
+        GzDecoder::new(File::open(file).unwrap())
+            .read_to_string(&mut output)
+            .unwrap();
+    }
+    let lines = |v: &str| v.lines().map(str::to_string).collect::<BTreeSet<_>>();
+    assert_eq!(lines(&output), lines(&expected));
+
+    let args = Args::parse_from([
+        "osm2rdf",
+        "parse",
+        input.to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+        "--checkpoint-every",
+        "1",
+        "--sorted-output",
+    ]);
+    assert!(parser::parse(args).is_err());
+}
+
+#[test]
+fn stdin_input() {
+    // A stream is read like the standard input, including the compressed ones
+    let blob_kinds = |reader: PbfReader| -> Vec<bool> {
+        reader
+            .map(|v| matches!(v.unwrap().decode().unwrap(), BlobDecode::OsmHeader(_)))
+            .collect()
+    };
+    let expected =
+        blob_kinds(PbfReader::from_path(fixture("osm2rdf/dense_test1.osm.pbf")).unwrap());
+    assert!(expected[0]);
+    for file in [
+        "dense_test1.osm.pbf",
+        "dense_test1.osm.pbf.gz",
+        "dense_test1.osm.pbf.bz2",
+    ] {
+        let bytes = std::fs::read(fixture(&format!("osm2rdf/{file}"))).unwrap();
+        let reader = PbfReader::from_reader(Cursor::new(bytes)).unwrap();
+        assert!(matches!(reader, PbfReader::Stream(_)));
+        assert_eq!(blob_kinds(reader), expected, "{file}");
+    }
+
+    // The standard input cannot be read twice
+    let temp_dir = TempDir::new().unwrap();
+    let out_dir = temp_dir.path().to_str().unwrap();
+    let stdin: &[&str] = &["-"];
+    for (inputs, options) in [
+        (&["-", "-"][..], &[][..]),
+        (stdin, &["--two-pass"]),
+        (stdin, &["--validate-members"]),
+        (stdin, &["--date-source", "header"]),
+        (stdin, &["--progress"]),
+    ] {
+        let args = [&["osm2rdf", "parse"], inputs, &[out_dir], options].concat();
+        assert!(
+            parser::parse(Args::parse_from(args)).is_err(),
+            "{options:?}"
+        );
+    }
+    assert!(list_files(temp_dir.path()).is_empty());
+}
+
+#[test]
+fn compressed_input() {
+    let expected = run_parse(&[], &[&fixture("osm2rdf/dense_test1.osm.pbf")], &[]);
+    for file in ["dense_test1.osm.pbf.gz", "dense_test1.osm.pbf.bz2"] {
+        let file = fixture(&format!("osm2rdf/{file}"));
+        assert_eq!(run_parse(&[], &[&file], &[]), expected);
+        // The pre-pass reads the file again
+        let args = ["--bbox", "1.05,0.5,1.5,1.5"];
+        element(&run_parse(&[], &[&file], &args), "osmway:21");
+
+        let report = check::check_refs(Args::parse_from([
+            "osm2rdf",
+            "check-refs",
+            file.to_str().unwrap(),
+        ]))
+        .unwrap();
+        assert_eq!((report.ways, report.relations), (2, 3));
+    }
+}
+
+#[test]
+fn member_order() {
+    let file = fixture("libosmium/examples_t_area_test_data.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--emit-member-order"]);
+    insta::assert_display_snapshot!(element(&result, "osmrel:701900"));
+    assert!(!run_parse(&[], &[&file], &[]).contains("osmm:member"));
+
+    let ntriples = run_parse(
+        &[],
+        &[&file],
+        &["--emit-member-order", "--format", "ntriples"],
+    );
+    let rel = "<https://www.openstreetmap.org/relation/701900>";
+    assert!(ntriples.contains(&format!(
+        "{rel} <https://www.openstreetmap.org/meta/member> _:osmrel_701900_1 .\n"
+    )));
+    assert!(ntriples.contains(
+        "_:osmrel_701900_1 <https://www.openstreetmap.org/meta/ref> <https://www.openstreetmap.org/way/701801> .\n"
+    ));
+}
+
+#[test]
+fn vocab_predicates() {
+    let runs: [(&str, &[&str]); 5] = [
+        (
+            "osm2rdf/dense_test1.osm.pbf",
+            &[
+                "--emit-hash",
+                "--emit-has-geometry",
+                "--emit-geometry",
+                "--validate-members",
+                "--emit-new-flag",
+                "--emit-raw-tags-json",
+                "--emit-metrics",
+            ],
+        ),
+        (
+            "osm2rdf/dense_route.osm.pbf",
+            &[
+                "--route-geometry",
+                "--emit-role-counts",
+                "--emit-member-order",
+            ],
+        ),
+        ("osm2rdf/dense_boundary.osm.pbf", &["--emit-special-points"]),
+        (
+            "osm2rdf/nodense_null_island.osm.pbf",
+            &["--flag-null-island"],
+        ),
+        (
+            "libosmium/examples_t_read_data.osm.pbf",
+            &["--emit-membership-count", "--check-geometry"],
+        ),
+    ];
+    let mut emitted = BTreeSet::new();
+    for (file, args) in runs {
+        let result = run_parse(&[], &[&fixture(file)], args);
+        for line in result.lines() {
+            let line = line.strip_prefix("osmroot: ").unwrap_or(line);
+            let Some((predicate, _)) = line.split_once(' ') else {
+                continue;
+            };
+            if predicate.starts_with("osmm:") || predicate.starts_with("schema:") {
+                emitted.insert(predicate.to_string());
+            }
+        }
+    }
+    let known: BTreeSet<_> = vocab::PREDICATES
+        .iter()
+        .map(|v| v.name.to_string())
+        .collect();
+    assert_eq!(known.len(), vocab::PREDICATES.len());
+    let unknown: Vec<_> = emitted.difference(&known).collect();
+    assert!(
+        unknown.is_empty(),
+        "Predicates missing from the vocabulary: {unknown:?}"
+    );
+    assert!(emitted.contains("osmm:routeGeometry"));
+
+    let schema = json::parse(&vocab::describe(SchemaFormat::Json)).unwrap();
+    assert_eq!(schema.len(), vocab::PREDICATES.len());
+    assert_eq!(schema[1]["name"], "osmm:loc");
+    assert_eq!(schema[1]["datatype"], "geo:wktLiteral");
+    let turtle = vocab::describe(SchemaFormat::Turtle);
+    assert!(turtle.contains("\nosmm:isClosed\n  a rdf:Property;\n  rdfs:range xsd:boolean;\n"));
+}
+
+#[test]
+fn io_threads() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let expected = run_parse(&[], &[&file], &[]);
+    let result = run_parse(&[], &[&file], &["--io-threads", "3"]);
+    for subject in [
+        "osmnode:14",
+        "osmway:20",
+        "osmway:21",
+        "osmrel:30",
+        "osmrel:31",
+    ] {
+        assert_eq!(element(&result, subject), element(&expected, subject));
+        assert_eq!(result.matches(&format!("\n{subject}\n")).count(), 1);
+    }
+    assert_eq!(result.matches("schema:dateModified").count(), 1);
+
+    // The latest timestamp is shared by all writer threads
+    let date_modified = |result: &str| {
+        let start = result.find("osmroot: schema:dateModified ").unwrap();
+        result[start..].lines().next().unwrap().to_string()
+    };
+    let result = run_parse(&[], &[&file], &["--writers", "2"]);
+    assert_eq!(date_modified(&result), date_modified(&expected));
+}
+
+#[test]
+fn null_island() {
+    let file = fixture("osm2rdf/nodense_null_island.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--flag-null-island"]);
+    let node = element(&result, "osmnode:1");
+    assert!(node.contains("osmm:loc \"Point(0 0)\"^^geo:wktLiteral;"));
+    assert!(node.contains("osmm:locWarning \"null_island\";"));
+    // Very close to the origin, but not exactly at it
+    assert!(!element(&result, "osmnode:2").contains("osmm:locWarning"));
+
+    let result = run_parse(&[], &[&file], &[]);
+    assert!(!result.contains("osmm:locWarning"));
+}
+
+#[test]
+fn dataset_bbox() {
+    let file = fixture("osm2rdf/nodense_null_island.osm.pbf");
+    let result = run_parse(&[], &[&file], &[]);
+    // Node 3 is untagged and not emitted, and node 2 is slightly north of node 1
+    assert!(result.contains(
+        r#"osmroot: osmm:datasetBbox "POLYGON((0 0, 0 0, 0 0.00001, 0 0.00001, 0 0))"^^geo:wktLiteral."#
+    ));
+}
+
+#[test]
+fn geohash_precision() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let result = run_parse(&[], &[&file], &["--geohash-precision", "5"]);
+    // Node 14 is at lat 1, lon 1.4
+    assert!(element(&result, "osmnode:14").contains("osmm:geohash \"s00vx\";"));
+    assert!(element(&result, "osmway:20").contains("osmm:geohash "));
+    assert!(!run_parse(&[], &[&file], &[]).contains("osmm:geohash"));
+}
+
+#[test]
+fn date_source() {
+    let file = fixture("osm2rdf/dense_replication.osm.pbf");
+    let date_modified = |args: &[&str]| {
+        let result = run_parse(&[], &[&file], args);
+        let start = result.find("osmroot: schema:dateModified ").unwrap();
+        result[start..].lines().next().unwrap().to_string()
+    };
+    assert_eq!(
+        date_modified(&[]),
+        r#"osmroot: schema:dateModified "2014-01-01 00:00:00 UTC"^^xsd:dateTime."#
+    );
+    assert_eq!(
+        date_modified(&["--date-source", "header"]),
+        r#"osmroot: schema:dateModified "2020-02-02 12:00:00 UTC"^^xsd:dateTime."#
+    );
+
+    // The header of this file has no replication timestamp
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let temp_dir = TempDir::new().unwrap();
+    let args = Args::parse_from([
+        "osm2rdf",
+        "parse",
+        "--date-source",
+        "header",
+        file.to_str().unwrap(),
+        temp_dir.path().to_str().unwrap(),
+    ]);
+    assert!(parser::parse(args).is_err());
+}
+
+#[test]
+fn footer_command() {
+    let file = fixture("osm2rdf/dense_replication.osm.pbf");
+    let temp_dir = parse_to_dir(&[], &[&file], &[]);
+    let footer = |args: &[&str]| {
+        let mut cmd = vec!["osm2rdf", "footer"];
+        cmd.extend(args);
+        cmd.extend([file.to_str().unwrap(), temp_dir.path().to_str().unwrap()]);
+        parser::footer(Args::parse_from(cmd)).unwrap();
+        let path = list_files(temp_dir.path()).pop().unwrap();
+        let mut result = String::new();
+        GzDecoder::new(File::open(&path).unwrap())
+            .read_to_string(&mut result)
+            .unwrap();
+        (path, result)
+    };
+
+    // The parse output has the elements and the footer files, so the new footer is the third file
+    let (path, result) = footer(&[]);
+    assert_eq!(path.file_name().unwrap(), "osm-000002.ttl.gz");
+    assert!(result.starts_with("@prefix "));
+    assert!(result
+        .ends_with("\nosmroot: schema:dateModified \"2014-01-01 00:00:00 UTC\"^^xsd:dateTime.\n"));
+    assert!(!result.contains("osmm:datasetBbox"));
+
+    let (path, result) = footer(&["--date-source", "header"]);
+    assert_eq!(path.file_name().unwrap(), "osm-000003.ttl.gz");
+    assert!(result
+        .contains("\nosmroot: schema:dateModified \"2020-02-02 12:00:00 UTC\"^^xsd:dateTime.\n"));
+}
+
+#[test]
+fn protobuf_format() {
+    let file = fixture("osm2rdf/dense_test1.osm.pbf");
+    let temp_dir = parse_to_dir(&[], &[&file], &["--format", "protobuf"]);
+    let files = list_files(temp_dir.path());
+    assert_eq!(files.len(), 1);
+    assert!(files[0].to_str().unwrap().ends_with("osm-000000.pb.gz"));
+
+    let mut data = Vec::new();
+    GzDecoder::new(File::open(&files[0]).unwrap())
+        .read_to_end(&mut data)
+        .unwrap();
+    let mut buf = data.as_slice();
+    let mut elements = Vec::new();
+    while !buf.is_empty() {
+        elements.push(Element::decode_length_delimited(&mut buf).unwrap());
+    }
+    let ids: Vec<_> = elements.iter().map(|v| (v.r#type(), v.id)).collect();
+    assert_eq!(
+        ids,
+        [
+            (ElementType::Node, 14),
+            (ElementType::Way, 20),
+            (ElementType::Way, 21),
+            (ElementType::Relation, 30),
+            (ElementType::Relation, 31),
+            (ElementType::Relation, 32),
+        ]
+    );
+
+    let node = &elements[0];
+    assert_eq!(node.tags.len(), 5);
+    assert_eq!(node.tags[0].key, "test");
+    let loc = node.loc.as_ref().unwrap();
+    assert_eq!((loc.lat, loc.lon), (1.0, 1.4000000000000001));
+    assert_eq!(node.info.as_ref().unwrap().user.as_deref(), Some("test"));
+
+    let way = &elements[1];
+    assert_eq!(way.geometry.len(), 2);
+    assert_eq!(way.is_closed, Some(false));
+
+    let rel = &elements[4];
+    let roles: Vec<_> = rel.members.iter().map(|m| m.role.as_str()).collect();
+    assert_eq!(roles, ["from", "via", "to"]);
+}
+
+#[test]
+fn check_refs() {
+    let check = |file: &str| {
+        let file = fixture(file);
+        check::check_refs(Args::parse_from([
+            "osm2rdf",
+            "check-refs",
+            file.to_str().unwrap(),
+        ]))
+        .unwrap()
+    };
+
+    let report = check("osm2rdf/dense_test1.osm.pbf");
+    assert_eq!((report.ways, report.relations), (2, 3));
+    // Relation 31 references a missing way 22
+    assert_eq!(report.missing_relation_members, 1);
+    assert_eq!(report.samples, ["relation/31 -> way/22"]);
+
+    let report = check("libosmium/t_relations_missing_members.osm.pbf");
+    assert_eq!(report.missing_way_nodes, 0);
+    assert_eq!(report.relations_with_missing_members, 2);
+    assert_eq!(report.missing_relation_members, 6);
+
+    let report = check("libosmium/examples_t_location_cache_way.osm.pbf");
+    assert_eq!(report.ways_with_missing_nodes, 1);
+    assert_eq!(report.missing_way_nodes, 3);
+    assert!(!report.is_valid());
+}
+
+#[test]
+fn key_namespace_arg() {
+    let parse = |rule: &str| {
+        Args::try_parse_from([
+            "osm2rdf",
+            "parse",
+            "--key-namespace",
+            rule,
+            "input.osm.pbf",
+            ".",
+        ])
+    };
+    assert!(parse("addr=osmaddr").is_ok());
+    assert!(parse("addr").is_err());
+    assert!(parse("addr=osmt").is_err());
+    assert!(parse("addr=1addr").is_err());
+    assert!(parse("bad key=osmbad").is_err());
+}
+
+#[test]
+fn compact_files() {
+    let first = parse_to_dir(&[], &[&fixture("osm2rdf/dense_test1.osm.pbf")], &[]);
+    let second = parse_to_dir(
+        &[],
+        &[&fixture("libosmium/examples_t_pub_names_pub-way.osm.pbf")],
+        &["--key-namespace", "addr=osmaddr"],
+    );
+    let input = TempDir::new().unwrap();
+    for (name, dir) in [("a", &first), ("b", &second)] {
+        for file in list_files(dir.path()) {
+            let filename = format!("{name}-{}", file.file_name().unwrap().to_str().unwrap());
+            std::fs::copy(&file, input.path().join(filename)).unwrap();
+        }
+    }
+    let output = TempDir::new().unwrap();
+    let stats = compact::compact(Args::parse_from([
+        "osm2rdf",
+        "compact",
+        input.path().to_str().unwrap(),
+        output.path().to_str().unwrap(),
+    ]))
+    .unwrap();
+    assert_eq!(stats.input_files, 4);
+    assert_eq!(stats.output_files, 1);
+
+    let files = list_files(output.path());
+    assert_eq!(files.len(), 1);
+    let mut result = String::new();
+    GzDecoder::new(File::open(&files[0]).unwrap())
+        .read_to_string(&mut result)
+        .unwrap();
+    assert!(result.starts_with("@prefix wd: <http://www.wikidata.org/entity/>.\n"));
+    assert_eq!(result.matches("@prefix osmway:").count(), 1);
+    assert_eq!(result.matches("@prefix osmaddr:").count(), 1);
+    let body = &result[result.find("\n\n").unwrap()..];
+    assert!(!body.contains("@prefix"));
+    element(&result, "osmway:20");
+    element(&result, "osmway:40948654");
+    assert_eq!(result.matches("schema:dateModified").count(), 2);
+}
+
+#[test]
+fn bench_compression() {
+    let report = bench::bench_compression(Args::parse_from([
+        "osm2rdf",
+        "bench-compression",
+        "--sample-size",
+        "5",
+        fixture("osm2rdf/dense_test1.osm.pbf").to_str().unwrap(),
+    ]))
+    .unwrap();
+    // Node 14, ways 20 and 21, and the first two relations
+    assert_eq!(report.elements, 5);
+    let sizes: Vec<_> = report.results.iter().map(|v| (v.codec, v.size)).collect();
+    assert_eq!(sizes[0], (Codec::None, report.uncompressed));
+    assert_eq!(sizes[1].0, Codec::Gzip);
+    assert_eq!(sizes[2].0, Codec::GzipFiltered);
+    assert_eq!(sizes[3].0, Codec::GzipRle);
+    assert_eq!(sizes[4].0, Codec::Zstd);
+    for (_, size) in &sizes[1..] {
+        assert!(*size < report.uncompressed);
+    }
+    let table = report.to_string();
+    assert!(table.starts_with("Sample of 5 elements, "));
+    assert_eq!(table.lines().count(), 7);
+}
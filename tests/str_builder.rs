@@ -0,0 +1,815 @@
+use std::fmt::{Display, Write as _};
+
+use clap::Parser as _;
+use osm2rdf::str_builder::{
+    normalize_email, normalize_phone, StringBuf, XsdBoolean, XsdCommons, XsdDateTime,
+    XsdLineString, XsdPoint,
+};
+use osm2rdf::tag_schema::TagSchema;
+use osm2rdf::turtle::Serializer;
+use osm2rdf::utils::{Element, ElementInfo};
+use osm2rdf::{
+    Args, Crs, KeyNamespace, ParserOpts, Section, TagFilter, TagRename, TimestampFormat,
+};
+use regex::Regex;
+
+fn info(version: i32) -> ElementInfo<'static> {
+    ElementInfo {
+        is_deleted: false,
+        version: Some(version),
+        user: Some("test"),
+        milli_timestamp: Some(1388534400000),
+        changeset: Some(1),
+    }
+}
+
+fn build(tags: &[(&str, &str)], version: i32, opts: &ParserOpts) -> String {
+    let mut value = StringBuf::default();
+    value.add_tags(tags.iter().copied(), opts);
+    value.finalize(info(version), opts).buf
+}
+
+#[test]
+fn element_hash() {
+    let opts = ParserOpts {
+        emit_hash: true,
+        ..ParserOpts::default()
+    };
+    let tags = [("highway", "primary")];
+    let first = build(&tags, 1, &opts);
+    assert!(
+        first.contains("osmm:hash \"908762aeeea8f2af\";\n"),
+        "{first}"
+    );
+    assert_eq!(first, build(&tags, 1, &opts));
+    assert_ne!(first, build(&tags, 2, &opts));
+    assert_ne!(first, build(&[("highway", "secondary")], 1, &opts));
+    assert!(!build(&tags, 1, &ParserOpts::default()).contains("osmm:hash"));
+}
+
+/// Writes each statement on its own line with the subject, like N-Triples
+#[derive(Default)]
+struct SubjectLines {
+    subject: String,
+}
+
+impl Serializer for SubjectLines {
+    fn begin_subject(&mut self, out: &mut String, elem: Element, id: i64) {
+        self.subject = format!("{elem}:{id}");
+        writeln!(out, "# {}", self.subject).unwrap();
+    }
+
+    fn predicate_value(&mut self, out: &mut String, predicate: &dyn Display, value: &dyn Display) {
+        writeln!(out, "{} {predicate} {value} .", self.subject).unwrap();
+    }
+
+    fn end_subject(&mut self, out: &mut String) {
+        out.push_str("# end\n");
+    }
+}
+
+#[test]
+fn serializer() {
+    let opts = ParserOpts {
+        no_metadata: true,
+        ..ParserOpts::default()
+    };
+    let tags = [("name", "Foo"), ("highway", "primary")];
+    let mut value = StringBuf::new(Box::new(SubjectLines::default()));
+    value.begin_subject(Element::Way, 1);
+    assert!(value.is_empty());
+    value.add_tags(tags.iter().copied(), &opts);
+    assert_eq!(
+        value.finalize(info(1), &opts).buf,
+        "# osmway:1\nosmway:1 osmt:name \"Foo\" .\nosmway:1 osmt:highway \"primary\" .\n# end\n"
+    );
+
+    // An element without statements is not completed
+    let mut value = StringBuf::new(Box::new(SubjectLines::default()));
+    value.begin_subject(Element::Way, 1);
+    assert_eq!(value.finalize(info(1), &opts).buf, "# osmway:1\n");
+
+    // The Turtle predicate-object list has no subject, it is added by the writer
+    let mut value = StringBuf::default();
+    value.begin_subject(Element::Way, 1);
+    value.add_tags(tags.iter().copied(), &opts);
+    assert_eq!(
+        value.finalize(info(1), &opts).buf,
+        "osmt:name \"Foo\";\nosmt:highway \"primary\".\n"
+    );
+}
+
+#[test]
+fn point_precision() {
+    let point = |precision| {
+        let (lat, lon) = (53.565_728_9, 1.400_000_000_000_000_1);
+        XsdPoint {
+            lat,
+            lon,
+            precision,
+            crs: Crs::Wgs84,
+        }
+        .to_string()
+    };
+    assert_eq!(
+        point(None),
+        r#""Point(1.4000000000000001 53.5657289)"^^geo:wktLiteral"#
+    );
+    assert_eq!(point(Some(7)), r#""Point(1.4 53.5657289)"^^geo:wktLiteral"#);
+    assert_eq!(point(Some(3)), r#""Point(1.4 53.566)"^^geo:wktLiteral"#);
+    assert_eq!(point(Some(0)), r#""Point(1 54)"^^geo:wktLiteral"#);
+}
+
+#[test]
+fn key_namespace() {
+    let ns = |key_prefix: &str, namespace: &str| KeyNamespace {
+        key_prefix: key_prefix.to_string(),
+        namespace: namespace.to_string(),
+    };
+    let opts = ParserOpts {
+        key_namespace: vec![ns("addr", "osmaddr"), ns("name", "osmname")],
+        ..ParserOpts::default()
+    };
+    let tags = [
+        ("addr:street", "Main St"),
+        ("name:en", "Name"),
+        ("name", "Nom"),
+        ("addresses", "1"),
+        ("brand:wikidata", "Q1"),
+        ("name:wikidata", "Q2"),
+    ];
+    let result = build(&tags, 1, &opts);
+    assert!(result.contains("osmaddr:street \"Main St\";\n"), "{result}");
+    assert!(result.contains("osmname:en \"Name\";\n"), "{result}");
+    assert!(result.contains("osmt:name \"Nom\";\n"), "{result}");
+    assert!(result.contains("osmt:addresses \"1\";\n"), "{result}");
+    assert!(result.contains("osmt:brand:wikidata wd:Q1;\n"), "{result}");
+    assert!(result.contains("osmname:wikidata wd:Q2;\n"), "{result}");
+    assert_eq!(
+        ns("addr", "osmaddr").declaration(),
+        "prefix osmaddr: <https://wiki.openstreetmap.org/wiki/Key:addr:>"
+    );
+}
+
+#[test]
+fn tag_rename() {
+    let rename = |from: &str, to: &str| TagRename {
+        from: from.to_string(),
+        to: to.to_string(),
+    };
+    let opts = ParserOpts {
+        tag_rename: vec![
+            rename("phone", "contact:phone"),
+            rename("bad key", "note"),
+            rename("wd", "wikidata"),
+        ],
+        key_namespace: vec![KeyNamespace {
+            key_prefix: "contact".to_string(),
+            namespace: "osmcontact".to_string(),
+        }],
+        ..ParserOpts::default()
+    };
+    let tags = [
+        ("phone", "+1 555"),
+        ("bad key", "text"),
+        ("wd", "Q42"),
+        ("contact:phone", "+1 666"),
+    ];
+    let result = build(&tags, 1, &opts);
+    assert!(
+        result.contains("osmcontact:phone \"+1 555\";\n"),
+        "{result}"
+    );
+    assert!(
+        result.contains("osmcontact:phone \"+1 666\";\n"),
+        "{result}"
+    );
+    assert!(result.contains("osmt:note \"text\";\n"), "{result}");
+    assert!(result.contains("osmt:wikidata wd:Q42;\n"), "{result}");
+    assert!(!result.contains("osmm:badkey"), "{result}");
+    assert!(!result.contains("osmt:phone"), "{result}");
+
+    let parse =
+        |arg: &str| Args::try_parse_from(["osm2rdf", "parse", "in.pbf", ".", "--tag-rename", arg]);
+    assert!(parse("phone=contact:phone").is_ok());
+    assert!(parse("phone").is_err());
+    assert!(parse("=phone").is_err());
+    assert!(parse("phone=bad key").is_err());
+}
+
+#[test]
+fn prefixed_keys() {
+    let tags = [
+        ("addr:housenumber", "12"),
+        ("ref:FR:FANTOIR", "751010001A"),
+        ("name:zh-Hant", "Name"),
+        ("4wd_only", "yes"),
+        ("ref:", "1"),
+        ("a.b", "2"),
+        ("-x", "3"),
+    ];
+    let result = build(&tags, 1, &ParserOpts::default());
+    assert!(
+        result.contains("osmt:addr:housenumber \"12\";\n"),
+        "{result}"
+    );
+    assert!(
+        result.contains("osmt:ref:FR:FANTOIR \"751010001A\";\n"),
+        "{result}"
+    );
+    assert!(result.contains("osmt:name:zh-Hant \"Name\";\n"), "{result}");
+    assert!(result.contains("osmt:4wd_only \"yes\";\n"), "{result}");
+    for key in ["ref:", "a.b", "-x"] {
+        assert!(
+            result.contains(&format!("osmm:badkey \"{key}\";\n")),
+            "{result}"
+        );
+    }
+
+    // Every key emitted as a predicate must be a valid Turtle local name without escaping,
+    // see the PN_LOCAL rule of https://www.w3.org/TR/turtle/#grammar-production-PN_LOCAL
+    let pn_local = Regex::new(r"^[0-9A-Za-z_:]([-.0-9A-Za-z_:]*[-0-9A-Za-z_:])?$").unwrap();
+    let chars = ["a", "0", "_", "-", ":", ".", "/", "%", "~", "#", " ", "é"];
+    let mut keys: Vec<String> = chars.iter().map(|v| v.to_string()).collect();
+    for _ in 0..2 {
+        let longer: Vec<_> = keys
+            .iter()
+            .flat_map(|key| chars.iter().map(move |v| format!("{key}{v}")))
+            .collect();
+        keys.extend(longer);
+    }
+    for key in &keys {
+        let result = build(&[(key, "v")], 1, &ParserOpts::default());
+        let line = result.lines().next().unwrap();
+        if line.starts_with("osmm:badkey ") {
+            continue;
+        }
+        let name = line.strip_prefix("osmt:").unwrap().strip_suffix(" \"v\";");
+        assert!(
+            name.is_some_and(|v| pn_local.is_match(v)),
+            "{key:?}: {line}"
+        );
+    }
+}
+
+#[test]
+fn wikimedia_commons() {
+    let tags = [
+        ("wikimedia_commons", "Category:Eiffel Tower"),
+        ("image:wikimedia_commons", "File:Tour Eiffel (1889).jpg"),
+        ("subject:wikimedia_commons", "Category:A; File:B.png"),
+        ("architect:wikimedia_commons", "Eiffel Tower"),
+        ("brand:wikimedia_commons", "File:a<b>.jpg"),
+        ("operator:wikimedia_commons", "Category:A;"),
+    ];
+    let result = build(&tags, 1, &ParserOpts::default());
+    let commons = "https://commons.wikimedia.org/wiki/";
+    for expected in [
+        format!("osmt:wikimedia_commons <{commons}Category:Eiffel_Tower>;"),
+        format!("osmt:image:wikimedia_commons <{commons}File:Tour_Eiffel_%281889%29.jpg>;"),
+        format!("osmt:subject:wikimedia_commons <{commons}Category:A>,<{commons}File:B.png>;"),
+        r#"osmt:architect:wikimedia_commons "Eiffel Tower";"#.to_string(),
+        r#"osmt:brand:wikimedia_commons "File:a<b>.jpg";"#.to_string(),
+        r#"osmt:operator:wikimedia_commons "Category:A;";"#.to_string(),
+    ] {
+        assert!(
+            result.contains(&format!("{expected}\n")),
+            "{expected}\n{result}"
+        );
+    }
+    assert_eq!(
+        XsdCommons::parse("File:Città/1.jpg").unwrap().to_string(),
+        format!("<{commons}File:Citt%C3%A0%2F1.jpg>")
+    );
+    assert!(XsdCommons::parse("category:A").is_none());
+}
+
+#[test]
+fn wikipedia_multi_values() {
+    let tags = [
+        ("wikipedia", "fr:Tour Eiffel"),
+        ("subject:wikipedia", "en:A; de:B (Stadt)"),
+        ("brand:wikipedia", "en:A;Some title"),
+        ("operator:wikipedia", "en:A;"),
+    ];
+    let opts = ParserOpts {
+        flag_bad_wiki: true,
+        ..ParserOpts::default()
+    };
+    let result = build(&tags, 1, &opts);
+    for expected in [
+        "osmt:wikipedia <https://fr.wikipedia.org/wiki/Tour_Eiffel>;",
+        "osmt:subject:wikipedia <https://en.wikipedia.org/wiki/A>,<https://de.wikipedia.org/wiki/B_%28Stadt%29>;",
+        // A single invalid entry keeps the whole value as a string
+        r#"osmt:brand:wikipedia "en:A;Some title";"#,
+        r#"osmm:badWikipedia "en:A;Some title";"#,
+        r#"osmt:operator:wikipedia "en:A;";"#,
+    ] {
+        assert!(
+            result.contains(&format!("{expected}\n")),
+            "{expected}\n{result}"
+        );
+    }
+}
+
+#[test]
+fn raw_tags_json() {
+    let tags = [
+        ("name", "Caf\u{e9} \"Zur Post\"\n"),
+        ("created_by", "JOSM"),
+        ("my !@#$ key", "a\\b"),
+        ("wikipedia", "en:Some title"),
+    ];
+    let opts = ParserOpts {
+        emit_raw_tags_json: true,
+        ..ParserOpts::default()
+    };
+    let result = build(&tags, 1, &opts);
+    let line = result
+        .lines()
+        .find_map(|v| v.strip_prefix("osmm:tags "))
+        .unwrap();
+    let literal = line
+        .strip_suffix("^^<http://www.w3.org/1999/02/22-rdf-syntax-ns#JSON>;")
+        .unwrap();
+    let raw_tags = json::parse(&json::parse(literal).unwrap().to_string()).unwrap();
+    assert_eq!(raw_tags.len(), tags.len());
+    for (key, val) in tags {
+        assert_eq!(raw_tags[key], val, "{key}");
+    }
+    assert!(
+        result.contains("osmm:badkey \"my !@#$ key\";\n"),
+        "{result}"
+    );
+
+    assert!(!build(&tags, 1, &ParserOpts::default()).contains("osmm:tags"));
+    // Only tags that are dropped anyway do not make the element non-empty
+    let mut value = StringBuf::default();
+    value.add_tags([("created_by", "JOSM")].into_iter(), &opts);
+    assert!(value.is_empty());
+}
+
+#[test]
+fn month_day() {
+    let tags = [
+        ("check_date", "--05-01"),
+        ("start_date", "12-25"),
+        ("end_date", "02-29"),
+        ("survey:date", "02-30"),
+        ("opening_date", "13-01"),
+        ("date", "2023-05-01"),
+        ("ref", "05-01"),
+    ];
+    let result = build(&tags, 1, &ParserOpts::default());
+    assert!(
+        result.contains("osmt:check_date \"--05-01\"^^xsd:gMonthDay;\n"),
+        "{result}"
+    );
+    assert!(
+        result.contains("osmt:start_date \"--12-25\"^^xsd:gMonthDay;\n"),
+        "{result}"
+    );
+    assert!(
+        result.contains("osmt:end_date \"--02-29\"^^xsd:gMonthDay;\n"),
+        "{result}"
+    );
+    assert!(result.contains("osmt:survey:date \"02-30\";\n"), "{result}");
+    assert!(
+        result.contains("osmt:opening_date \"13-01\";\n"),
+        "{result}"
+    );
+    assert!(result.contains("osmt:date \"2023-05-01\";\n"), "{result}");
+    assert!(result.contains("osmt:ref \"05-01\";\n"), "{result}");
+}
+
+#[test]
+fn line_string() {
+    let line = [(1.0, 2.0), (1.5, 2.5), (1.234_56, 2.0)];
+    let wkt = |crs| {
+        XsdLineString {
+            line: &line,
+            precision: Some(3),
+            crs,
+        }
+        .to_string()
+    };
+    assert_eq!(
+        wkt(Crs::Wgs84),
+        r#""LINESTRING(2 1, 2.5 1.5, 2 1.235)"^^geo:wktLiteral"#
+    );
+    assert!(wkt(Crs::WebMercator).starts_with(
+        r#""<http://www.opengis.net/def/crs/EPSG/0/3857> LINESTRING(222638.982 111325.143, "#
+    ));
+}
+
+#[test]
+fn crs84_point() {
+    let point = XsdPoint {
+        lat: 53.5,
+        lon: 1.25,
+        precision: None,
+        crs: Crs::Crs84,
+    };
+    assert_eq!(
+        point.to_string(),
+        r#""<http://www.opengis.net/def/crs/OGC/1.3/CRS84> Point(1.25 53.5)"^^geo:wktLiteral"#
+    );
+}
+
+#[test]
+fn web_mercator_point() {
+    let point = |lat, lon, precision| {
+        XsdPoint {
+            lat,
+            lon,
+            precision,
+            crs: Crs::WebMercator,
+        }
+        .to_string()
+    };
+    let crs = "<http://www.opengis.net/def/crs/EPSG/0/3857>";
+    assert_eq!(
+        point(50.0, 10.0, None),
+        format!(r#""{crs} Point(1113194.9079327357 6446275.841017158)"^^geo:wktLiteral"#)
+    );
+    assert_eq!(
+        point(40.6892, -74.0445, Some(2)),
+        format!(r#""{crs} Point(-8242596.04 4966606.26)"^^geo:wktLiteral"#)
+    );
+    // Latitudes are clamped to the edge of the Web Mercator square
+    assert_eq!(
+        point(90.0, 180.0, Some(0)),
+        format!(r#""{crs} Point(20037508 20037508)"^^geo:wktLiteral"#)
+    );
+}
+
+#[test]
+fn tag_schema() {
+    let schema = TagSchema::parse(
+        "
+        # comment
+        height: decimal
+        levels: integer
+        name:*: langString
+        start_date: date
+        oneway: boolean
+        *wikidata: wikidata-ref
+        ",
+    )
+    .unwrap();
+    let opts = ParserOpts {
+        tag_schema: Some(schema),
+        ..ParserOpts::default()
+    };
+    let tags = [
+        ("height", "12.5"),
+        ("levels", "3"),
+        ("name:fr", "Paris"),
+        ("name:zh-Hant", "巴黎"),
+        ("start_date", "2020-01-31"),
+        ("oneway", "yes"),
+        ("wikidata", "Q90"),
+        // Values not matching their datatype are strings
+        ("max_height", "12.5"),
+        ("building:levels", "3"),
+        ("name", "Paris"),
+        ("name:EN", "Paris"),
+        ("end_date", "2020-01-31"),
+        ("height:note", "12 m"),
+        ("wikipedia", "fr:Paris"),
+    ];
+    let result = build(&tags, 1, &opts);
+    for expected in [
+        r#"osmt:height "12.5"^^xsd:decimal;"#,
+        r#"osmt:levels "3"^^xsd:integer;"#,
+        r#"osmt:name:fr "Paris"@fr;"#,
+        r#"osmt:name:zh-Hant "巴黎"@zh-Hant;"#,
+        r#"osmt:start_date "2020-01-31"^^xsd:date;"#,
+        r#"osmt:oneway "true"^^xsd:boolean;"#,
+        r#"osmt:wikidata wd:Q90;"#,
+        r#"osmt:max_height "12.5";"#,
+        r#"osmt:building:levels "3";"#,
+        r#"osmt:name "Paris";"#,
+        r#"osmt:name:EN "Paris";"#,
+        r#"osmt:end_date "2020-01-31";"#,
+        r#"osmt:wikipedia "fr:Paris";"#,
+    ] {
+        assert!(
+            result.contains(&format!("{expected}\n")),
+            "{expected}\n{result}"
+        );
+    }
+
+    let invalid = |tags: &[(&str, &str)]| {
+        let schema = "height: decimal\nlevels: integer\nstart_date: date";
+        let opts = ParserOpts {
+            tag_schema: Some(TagSchema::parse(schema).unwrap()),
+            ..ParserOpts::default()
+        };
+        build(tags, 1, &opts)
+    };
+    assert!(invalid(&[("height", "12 m")]).contains(r#"osmt:height "12 m";"#));
+    assert!(invalid(&[("levels", "007")]).contains(r#"osmt:levels "007";"#));
+    assert!(invalid(&[("start_date", "2020-02-30")]).contains(r#"osmt:start_date "2020-02-30";"#));
+
+    assert!(TagSchema::parse("height: number").is_err());
+    assert!(TagSchema::parse("height").is_err());
+}
+
+#[test]
+fn only_keys() {
+    let opts = ParserOpts {
+        only_keys: vec!["name".to_string(), "highway".to_string()],
+        ..ParserOpts::default()
+    };
+    let tags = [
+        ("highway", "primary"),
+        ("name:en", "Main Street"),
+        ("bad key", "value"),
+        ("name", "Main"),
+    ];
+    let mut value = StringBuf::default();
+    let bad_keys = value.add_tags(tags.iter().copied(), &opts);
+    assert_eq!(bad_keys, 0);
+    assert_eq!(
+        value.buf,
+        "osmt:highway \"primary\";\nosmt:name \"Main\";\n"
+    );
+
+    let mut value = StringBuf::default();
+    value.add_tags([("building", "yes")].iter().copied(), &opts);
+    assert!(value.is_empty());
+}
+
+#[test]
+fn tag_filter() {
+    let tags = [
+        ("highway", "primary"),
+        ("name", "Main"),
+        ("name:en", "Main Street"),
+        ("name_1", "Old Main"),
+        ("note", "check"),
+        ("created_by", "JOSM"),
+    ];
+    let run = |include: &[&str], exclude: &[&str]| {
+        let opts = ParserOpts {
+            tag_filter: TagFilter {
+                include_tags: include.iter().map(ToString::to_string).collect(),
+                exclude_tags: exclude.iter().map(ToString::to_string).collect(),
+            },
+            ..ParserOpts::default()
+        };
+        let mut value = StringBuf::default();
+        value.add_tags(tags.iter().copied(), &opts);
+        let keys: Vec<_> = value
+            .buf
+            .lines()
+            .map(|v| {
+                v.split_once(' ')
+                    .unwrap()
+                    .0
+                    .trim_start_matches("osmt:")
+                    .to_string()
+            })
+            .collect();
+        keys
+    };
+    // `created_by` is always dropped
+    assert_eq!(
+        run(&[], &[]),
+        ["highway", "name", "name:en", "name_1", "note"]
+    );
+    assert_eq!(
+        run(&["*"], &[]),
+        ["highway", "name", "name:en", "name_1", "note"]
+    );
+    assert_eq!(run(&["created_by"], &[]), Vec::<String>::new());
+    // A trailing `*` matches any key with the prefix, including the prefix itself
+    assert_eq!(run(&["highway", "name:*"], &[]), ["highway", "name:en"]);
+    assert_eq!(run(&["name*"], &[]), ["name", "name:en", "name_1"]);
+    // Exclusions win over inclusions
+    assert_eq!(run(&["name*"], &["name:*", "name_1"]), ["name"]);
+    assert_eq!(run(&[], &["n*"]), ["highway"]);
+
+    let parse = |arg: &str| {
+        Args::try_parse_from(["osm2rdf", "parse", "in.pbf", ".", "--include-tags", arg])
+    };
+    assert!(parse("name:*,highway").is_ok());
+    assert!(parse("na*me").is_err());
+    assert!(parse("name**").is_err());
+}
+
+#[test]
+fn predicate_order() {
+    let build = |order: Vec<Section>| {
+        let opts = ParserOpts {
+            predicate_order: order,
+            ..ParserOpts::default()
+        };
+        let mut value = StringBuf::default();
+        value.add_tags([("highway", "primary")].iter().copied(), &opts);
+        value.start_section(Section::Geometry);
+        value.add_value("osmm:isClosed", XsdBoolean(false));
+        value.finalize(info(1), &opts).buf
+    };
+    let metadata = concat!(
+        "osmm:version \"1\"^^xsd:integer;\n",
+        "osmm:user \"test\";\n",
+        "osmm:timestamp \"2014-01-01 00:00:00 UTC\"^^xsd:dateTime;\n",
+        "osmm:changeset \"1\"^^xsd:integer.\n",
+    );
+    let tags = "osmt:highway \"primary\";\n";
+    let geometry = "osmm:isClosed \"false\"^^xsd:boolean;\n";
+    assert_eq!(build(vec![]), format!("{tags}{geometry}{metadata}"));
+    // Metadata is not listed, so it stays last
+    assert_eq!(
+        build(vec![Section::Geometry, Section::Tags]),
+        format!("{geometry}{tags}{metadata}")
+    );
+    let result = build(vec![Section::Metadata, Section::Geometry, Section::Tags]);
+    assert!(result.starts_with("osmm:version \"1\"^^xsd:integer;\n"));
+    assert!(result.ends_with(&format!("{geometry}osmt:highway \"primary\".\n")));
+}
+
+#[test]
+fn flag_bad_wiki() {
+    let tags = [
+        ("wikidata", "Q"),
+        ("brand:wikidata", "Q123 (brand)"),
+        ("subject:wikidata", "Q42"),
+        ("wikipedia", "Some title"),
+        ("name:wikipedia", "en:Some title"),
+    ];
+    let result = build(&tags, 1, &ParserOpts::default());
+    assert!(!result.contains("osmm:badWiki"), "{result}");
+
+    let opts = ParserOpts {
+        flag_bad_wiki: true,
+        ..ParserOpts::default()
+    };
+    let result = build(&tags, 1, &opts);
+    // The invalid values are kept as plain strings
+    assert!(result.contains("osmt:wikidata \"Q\";\n"), "{result}");
+    assert!(result.contains("osmm:badWikidata \"Q\";\n"), "{result}");
+    assert!(
+        result.contains("osmm:badWikidata \"Q123 (brand)\";\n"),
+        "{result}"
+    );
+    assert!(
+        result.contains("osmt:subject:wikidata wd:Q42;\n"),
+        "{result}"
+    );
+    assert!(!result.contains("osmm:badWikidata \"Q42\""), "{result}");
+    assert!(
+        result.contains("osmt:wikipedia \"Some title\";\n"),
+        "{result}"
+    );
+    assert!(
+        result.contains("osmm:badWikipedia \"Some title\";\n"),
+        "{result}"
+    );
+    assert_eq!(result.matches("osmm:badWikipedia").count(), 1, "{result}");
+}
+
+#[test]
+fn validate_contacts() {
+    assert_eq!(
+        normalize_phone("+1 (555) 123-4567").as_deref(),
+        Some("+15551234567")
+    );
+    assert_eq!(
+        normalize_phone("0044 20 7946.0958").as_deref(),
+        Some("+442079460958")
+    );
+    assert_eq!(normalize_phone("555-1234"), None);
+    assert_eq!(normalize_phone("+1 555 CALL NOW"), None);
+    assert_eq!(
+        normalize_email("mailto:Info@Example.COM").as_deref(),
+        Some("Info@example.com")
+    );
+    assert_eq!(normalize_email("info@localhost"), None);
+    assert_eq!(normalize_email("info at example.com"), None);
+
+    let opts = ParserOpts {
+        validate_contacts: true,
+        ..ParserOpts::default()
+    };
+    let tags = [
+        ("phone", "+1 555 123 4567; 555-1234"),
+        ("contact:email", "info@example.com"),
+        ("email", "not an email"),
+    ];
+    let result = build(&tags, 1, &opts);
+    assert!(
+        result.contains("osmt:phone \"+1 555 123 4567; 555-1234\";\n"),
+        "{result}"
+    );
+    assert!(result.contains("schema:telephone \"+15551234567\";\n"));
+    assert!(result.contains("osmm:contactWarning \"phone\";\n"));
+    assert!(result.contains("schema:email \"info@example.com\";\n"));
+    assert!(result.contains("osmm:contactWarning \"email\";\n"));
+    assert!(!result.contains("osmm:contactWarning \"contact:email\""));
+    assert!(!build(&tags, 1, &ParserOpts::default()).contains("schema:"));
+}
+
+#[test]
+fn missing_metadata() {
+    let mut value = StringBuf::default();
+    let opts = ParserOpts::default();
+    value.add_tags([("highway", "primary")].into_iter(), &opts);
+    let result = value.finalize(ElementInfo::default(), &opts).buf;
+    assert_eq!(result, "osmt:highway \"primary\".\n");
+
+    // Only the missing fields are skipped
+    let mut value = StringBuf::default();
+    value.add_tags([("highway", "primary")].into_iter(), &opts);
+    let info = ElementInfo {
+        changeset: None,
+        ..info(2)
+    };
+    let result = value.finalize(info, &opts).buf;
+    assert!(result.contains("osmm:version \"2\"^^xsd:integer;\n"));
+    assert!(result.ends_with("osmm:timestamp \"2014-01-01 00:00:00 UTC\"^^xsd:dateTime.\n"));
+
+    // Elements without any statement are left empty, so that the parser can skip them
+    let opts = ParserOpts {
+        no_metadata: true,
+        ..ParserOpts::default()
+    };
+    let result = StringBuf::default()
+        .finalize(ElementInfo::default(), &opts)
+        .buf;
+    assert_eq!(result, "");
+}
+
+#[test]
+fn new_flag() {
+    let opts = ParserOpts {
+        emit_new_flag: true,
+        ..ParserOpts::default()
+    };
+    let tags = [("highway", "primary")];
+    let result = build(&tags, 1, &opts);
+    assert!(
+        result.contains("osmm:version \"1\"^^xsd:integer;\nosmm:isNew \"true\"^^xsd:boolean;\n")
+    );
+    assert!(!build(&tags, 2, &opts).contains("osmm:isNew"));
+    assert!(!build(&tags, 1, &ParserOpts::default()).contains("osmm:isNew"));
+
+    // Without a version, it is unknown whether the element is new
+    let mut value = StringBuf::default();
+    value.add_tags(tags.into_iter(), &opts);
+    let result = value.finalize(ElementInfo::default(), &opts).buf;
+    assert!(!result.contains("osmm:isNew"));
+}
+
+#[test]
+fn timestamp_format() {
+    let ms = 1_700_000_000_000;
+    let formats = [
+        (
+            TimestampFormat::Iso,
+            r#""2023-11-14 22:13:20 UTC"^^xsd:dateTime"#,
+        ),
+        (
+            TimestampFormat::EpochMillis,
+            r#""1700000000000"^^xsd:integer"#,
+        ),
+        (
+            TimestampFormat::EpochSeconds,
+            r#""1700000000"^^xsd:integer"#,
+        ),
+    ];
+    for (format, expected) in formats {
+        assert_eq!(XsdDateTime(ms, format).to_string(), expected);
+    }
+
+    let opts = ParserOpts {
+        timestamp_format: TimestampFormat::EpochSeconds,
+        ..ParserOpts::default()
+    };
+    let result = build(&[("highway", "primary")], 1, &opts);
+    assert!(result.contains("osmm:timestamp \"1388534400\"^^xsd:integer;\n"));
+}
+
+#[test]
+fn mark_anonymous_users() {
+    let opts = ParserOpts {
+        mark_anonymous_users: true,
+        ..ParserOpts::default()
+    };
+    let build_as = |user, opts: &ParserOpts| {
+        let mut value = StringBuf::default();
+        value.add_tags([("highway", "primary")].into_iter(), opts);
+        let info = ElementInfo { user, ..info(1) };
+        value.finalize(info, opts).buf
+    };
+    for user in [None, Some("")] {
+        let result = build_as(user, &opts);
+        assert!(result.contains("osmm:user osmm:anonymous;\n"), "{result}");
+        assert!(!build_as(user, &ParserOpts::default()).contains("osmm:anonymous"));
+    }
+    assert!(!build_as(None, &ParserOpts::default()).contains("osmm:user"));
+    let result = build_as(Some("test"), &opts);
+    assert!(result.contains("osmm:user \"test\";\n"), "{result}");
+}